@@ -0,0 +1,124 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::Context, Result};
+use serde_derive::{Deserialize, Serialize};
+
+pub const ERC20_TRANSFERS: &str = "erc20_transfers";
+pub const ERC721_MINTS: &str = "erc721_mints";
+pub const ERC20_APPROVALS: &str = "erc20_approvals";
+pub const ACCOUNT_DEPLOYMENTS: &str = "account_deployments";
+pub const MIXED: &str = "mixed";
+/// Only counts the progress of a replay, a replay can't be resumed
+pub const REPLAY: &str = "replay";
+
+/// Minimum time between two writes of the checkpoint file while a workload runs
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Progress of a run, saved to disk as transactions get submitted
+/// so that an interrupted run can be resumed
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointState {
+    /// Amount of transactions accepted by the node, by workload
+    completed: HashMap<String, u64>,
+    /// Workloads that ran to completion
+    finished: HashSet<String>,
+}
+
+/// Tracks the progress of the workloads, only kept in memory when there is no checkpoint file
+pub struct Checkpoint {
+    path: Option<PathBuf>,
+    state: Mutex<(CheckpointState, Instant)>,
+}
+
+impl Checkpoint {
+    /// Start tracking from scratch, overwriting the checkpoint file at `path` if any
+    pub fn new(path: Option<&Path>) -> Result<Self> {
+        let checkpoint = Self::with_state(path, CheckpointState::default());
+        checkpoint.flush()?;
+
+        Ok(checkpoint)
+    }
+
+    /// Continue from the progress saved in the checkpoint file at `path`
+    pub fn resume(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .wrap_err_with(|| format!("Could not open checkpoint file {}", path.display()))?;
+        let state: CheckpointState = serde_json::from_reader(file)
+            .wrap_err_with(|| format!("Could not parse checkpoint file {}", path.display()))?;
+
+        Ok(Self::with_state(Some(path), state))
+    }
+
+    fn with_state(path: Option<&Path>, state: CheckpointState) -> Self {
+        Self {
+            path: path.map(Path::to_path_buf),
+            state: Mutex::new((state, Instant::now())),
+        }
+    }
+
+    pub fn completed(&self, workload: &str) -> u64 {
+        let (state, _) = &*self.state.lock().expect("Checkpoint lock is poisoned");
+
+        state.completed.get(workload).copied().unwrap_or_default()
+    }
+
+    pub fn is_finished(&self, workload: &str) -> bool {
+        let (state, _) = &*self.state.lock().expect("Checkpoint lock is poisoned");
+
+        state.finished.contains(workload)
+    }
+
+    /// Record a transaction of `workload` accepted by the node
+    ///
+    /// The file is written at most once every [`FLUSH_INTERVAL`], a failed write
+    /// is only logged so that it doesn't fail the transaction
+    pub fn record(&self, workload: &str) {
+        let mut guard = self.state.lock().expect("Checkpoint lock is poisoned");
+        let (state, last_flush) = &mut *guard;
+
+        *state.completed.entry(workload.to_owned()).or_default() += 1;
+
+        if last_flush.elapsed() >= FLUSH_INTERVAL {
+            *last_flush = Instant::now();
+
+            if let Err(err) = self.write(state) {
+                log::warn!("Could not write checkpoint: {err:#}");
+            }
+        }
+    }
+
+    /// Mark `workload` as done so that resuming skips it
+    pub fn finish(&self, workload: &str) -> Result<()> {
+        {
+            let (state, _) = &mut *self.state.lock().expect("Checkpoint lock is poisoned");
+            state.finished.insert(workload.to_owned());
+        }
+
+        self.flush()
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        let (state, _) = &*self.state.lock().expect("Checkpoint lock is poisoned");
+
+        self.write(state)
+    }
+
+    /// Write to a temporary file first so that an interruption never leaves a truncated checkpoint
+    fn write(&self, state: &CheckpointState) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let tmp_path = path.with_extension("tmp");
+        let writer = std::fs::File::create(&tmp_path)?;
+        serde_json::to_writer_pretty(writer, state)?;
+
+        std::fs::rename(&tmp_path, path)
+            .wrap_err_with(|| format!("Could not write checkpoint file {}", path.display()))
+    }
+}
@@ -0,0 +1,164 @@
+use std::{collections::BTreeMap, fmt, fs::File, io::BufReader, path::Path};
+
+use color_eyre::{eyre::Context, Result};
+use serde_derive::Deserialize;
+
+/// Metrics compared between two reports, with whether a higher value is better
+const COMPARED_METRICS: [(&str, bool); 4] = [
+    ("Average TPS", true),
+    ("Average UOPS", true),
+    ("P50 Time to Inclusion", false),
+    ("P90 Time to Inclusion", false),
+];
+
+/// The parts of a JSON report that are compared
+#[derive(Debug, Deserialize)]
+struct Report {
+    all_bench_report: Benchmark,
+    benches: Vec<Benchmark>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Benchmark {
+    #[serde(default)]
+    name: String,
+    metrics: Vec<Metric>,
+    #[serde(default)]
+    time_percentiles: BTreeMap<String, TimePercentiles>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Metric {
+    name: String,
+    value: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimePercentiles {
+    submission: Option<f64>,
+    verification: Option<f64>,
+}
+
+impl Report {
+    fn read(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Could not open report {}", path.display()))?;
+
+        serde_json::from_reader(BufReader::new(file))
+            .wrap_err_with(|| format!("Could not read report {}", path.display()))
+    }
+
+    /// Benchmarks by name, the report over all of them being `All Benchmarks` like in the csv report
+    fn benchmarks(&self) -> impl Iterator<Item = (&str, &Benchmark)> {
+        std::iter::once(("All Benchmarks", &self.all_bench_report)).chain(
+            self.benches
+                .iter()
+                .map(|benchmark| (benchmark.name.as_str(), benchmark)),
+        )
+    }
+}
+
+impl Benchmark {
+    /// Compared values by name, with whether a higher value is better
+    fn values(&self) -> Vec<(String, f64, bool)> {
+        let metrics = COMPARED_METRICS
+            .iter()
+            .filter_map(|&(name, higher_is_better)| {
+                let metric = self.metrics.iter().find(|metric| metric.name == name)?;
+
+                Some((name.to_owned(), metric.value?, higher_is_better))
+            });
+
+        let percentiles = self
+            .time_percentiles
+            .iter()
+            .flat_map(|(percentile, times)| {
+                [
+                    ("Submission", times.submission),
+                    ("Verification", times.verification),
+                ]
+                .into_iter()
+                .filter_map(move |(phase, time)| {
+                    Some((format!("P{percentile} {phase} Time"), time?, false))
+                })
+            });
+
+        metrics.chain(percentiles).collect()
+    }
+}
+
+/// Change of a value between the old and the new report
+pub struct Delta {
+    pub benchmark: String,
+    pub name: String,
+    pub old: f64,
+    pub new: f64,
+    higher_is_better: bool,
+}
+
+impl Delta {
+    /// Change relative to the old value in percent, `None` when the old value is `0`
+    pub fn percentage(&self) -> Option<f64> {
+        (self.old != 0.0).then(|| (self.new - self.old) / self.old.abs() * 100.0)
+    }
+
+    /// Whether the value got worse by more than `threshold` percent
+    pub fn is_regression(&self, threshold: f64) -> bool {
+        match self.percentage() {
+            Some(percentage) if self.higher_is_better => percentage < -threshold,
+            Some(percentage) => percentage > threshold,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percentage = match self.percentage() {
+            Some(percentage) => format!("{percentage:+.2}%"),
+            None => "n/a".into(),
+        };
+
+        write!(
+            f,
+            "{:<24} {:<28} {:>14.2} {:>14.2} {:>10}",
+            self.benchmark, self.name, self.old, self.new, percentage
+        )
+    }
+}
+
+/// Deltas of the values found in both reports, benchmarks and metrics missing
+/// from either of them are left out
+pub fn compare(old_path: &Path, new_path: &Path) -> Result<Vec<Delta>> {
+    let (old, new) = (Report::read(old_path)?, Report::read(new_path)?);
+
+    let mut deltas = Vec::new();
+
+    for (benchmark, new_benchmark) in new.benchmarks() {
+        let Some((_, old_benchmark)) = old.benchmarks().find(|(name, _)| *name == benchmark) else {
+            log::info!(
+                "{benchmark} is not in {}, not comparing it",
+                old_path.display()
+            );
+            continue;
+        };
+
+        let old_values = old_benchmark.values();
+
+        for (name, new_value, higher_is_better) in new_benchmark.values() {
+            let Some((_, old_value, _)) = old_values.iter().find(|(old, _, _)| *old == name) else {
+                continue;
+            };
+
+            deltas.push(Delta {
+                benchmark: benchmark.to_owned(),
+                name,
+                old: *old_value,
+                new: new_value,
+                higher_is_better,
+            });
+        }
+    }
+
+    Ok(deltas)
+}
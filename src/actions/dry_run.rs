@@ -0,0 +1,406 @@
+use std::{cell::Cell, fmt};
+
+use color_eyre::{eyre::eyre, Result};
+use starknet::{
+    accounts::{Account, Call, ConnectedAccount},
+    core::types::{BlockId, BlockTag, BroadcastedTransaction, FieldElement, StarknetError},
+    providers::{Provider, ProviderError},
+};
+
+use crate::{
+    actions::{
+        goose::{
+            erc20_approve_call, erc20_transfer_call, erc20_transfer_from_call, erc721_mint_call,
+            sign_execution,
+        },
+        shoot::{
+            overall_fee, GatlingShooterSetup, InvokeVersion, StarknetAccount, FUNDING_BATCH_SIZE,
+        },
+    },
+    config::ContractSourceConfig,
+};
+
+/// How many transactions of every workload get their fee estimated
+const SAMPLE_SIZE: usize = 5;
+
+/// Fee of a workload projected from the fees estimated for a sample of its transactions
+pub struct FeeProjection {
+    pub name: String,
+    pub transactions: u64,
+    /// Fees of the sampled transactions, in the unit of `fees`
+    pub sampled_fees: Vec<u64>,
+    /// How the transactions pay their fee, the setup and the deployments always sending v1 ones
+    pub fees: InvokeVersion,
+}
+
+impl FeeProjection {
+    pub fn average_fee(&self) -> u128 {
+        let sum: u128 = self.sampled_fees.iter().map(|&fee| u128::from(fee)).sum();
+
+        sum / self.sampled_fees.len().max(1) as u128
+    }
+
+    pub fn total_fee(&self) -> u128 {
+        self.average_fee() * u128::from(self.transactions)
+    }
+
+    /// Whether a sampled transaction costs more than the max fee the benchmarks send with,
+    /// the node would then reject the transactions
+    pub fn exceeds_max_fee(&self) -> bool {
+        let max_fee = self.fees.max_fee();
+
+        self.sampled_fees
+            .iter()
+            .any(|&fee| u128::from(fee) > max_fee)
+    }
+}
+
+impl fmt::Display for FeeProjection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} transactions, {} {unit} on average over {} estimated, {} {unit} in total",
+            self.name,
+            self.transactions,
+            self.average_fee(),
+            self.sampled_fees.len(),
+            self.total_fee(),
+            unit = self.fees.fee_unit()
+        )
+    }
+}
+
+/// Estimate the fees of the configured workloads by signing their transactions like
+/// the benchmarks do, the transactions are only simulated by the node and never sent
+pub async fn estimate_fees(shooter: &GatlingShooterSetup) -> Result<Vec<FeeProjection>> {
+    let environment = shooter.environment()?;
+    let run = &shooter.config().run;
+    let account = environment.accounts.first().ok_or(eyre!(
+        "The setup state has no benchmark account to estimate from"
+    ))?;
+
+    let mut projections = Vec::new();
+
+    if run.num_erc20_transfers != 0 {
+        let sampled_fees = estimate_invokes(shooter, account, || {
+            (0..run.calls_per_tx)
+                .map(|_| erc20_transfer_call(environment.erc20_address))
+                .collect()
+        })
+        .await?;
+
+        projections.push(FeeProjection {
+            name: "Erc20 Transfers".into(),
+            transactions: run.num_erc20_transfers,
+            sampled_fees,
+            fees: InvokeVersion::from_config(run),
+        });
+    }
+
+    if run.num_erc721_mints != 0 {
+        let sampled_fees = estimate_invokes(shooter, shooter.deployer_account(), || {
+            (0..run.calls_per_tx)
+                .map(|_| erc721_mint_call(environment.erc721_address, account.address()))
+                .collect()
+        })
+        .await?;
+
+        projections.push(FeeProjection {
+            name: "Erc721 Mints".into(),
+            transactions: run.num_erc721_mints,
+            sampled_fees,
+            fees: InvokeVersion::from_config(run),
+        });
+    }
+
+    if run.num_erc20_approvals != 0 {
+        // The sampled account spends its own approvals, the transactions alternate like in the benchmark
+        let approving = Cell::new(false);
+        let sampled_fees = estimate_invokes(shooter, account, || {
+            approving.set(!approving.get());
+
+            if approving.get() {
+                vec![erc20_approve_call(
+                    environment.erc20_address,
+                    account.address(),
+                )]
+            } else {
+                vec![erc20_transfer_from_call(
+                    environment.erc20_address,
+                    account.address(),
+                )]
+            }
+        })
+        .await?;
+
+        projections.push(FeeProjection {
+            name: "Erc20 Approvals".into(),
+            transactions: run.num_erc20_approvals,
+            sampled_fees,
+            fees: InvokeVersion::from_config(run),
+        });
+    }
+
+    if run.num_account_deployments != 0 {
+        let deployments: Vec<BroadcastedTransaction> = environment
+            .benchmark_deployments
+            .iter()
+            .take(SAMPLE_SIZE)
+            .cloned()
+            .map(BroadcastedTransaction::DeployAccount)
+            .collect();
+
+        let sampled_fees = estimate(shooter, &deployments).await?;
+
+        projections.push(FeeProjection {
+            name: "Account Deployments".into(),
+            transactions: run.num_account_deployments,
+            sampled_fees,
+            fees: InvokeVersion::V1,
+        });
+    }
+
+    for custom_call in &run.custom_calls {
+        let call = custom_call.call(environment.custom_contract_address)?;
+        let sampled_fees = estimate_invokes(shooter, account, || vec![call.clone()]).await?;
+
+        projections.push(FeeProjection {
+            name: custom_call.selector.clone(),
+            transactions: custom_call.count,
+            sampled_fees,
+            fees: InvokeVersion::from_config(run),
+        });
+    }
+
+    if let Some(mixed) = run
+        .mixed
+        .as_ref()
+        .filter(|mixed| mixed.num_transactions != 0)
+    {
+        let iteration_transactions = mixed.iteration_transactions();
+
+        for (scenario, weight) in mixed.reduced_weights() {
+            let sampled_fees = match scenario {
+                "erc20" => {
+                    estimate_invokes(shooter, account, || {
+                        (0..run.calls_per_tx)
+                            .map(|_| erc20_transfer_call(environment.erc20_address))
+                            .collect()
+                    })
+                    .await?
+                }
+                "erc721" => {
+                    estimate_invokes(shooter, shooter.deployer_account(), || {
+                        (0..run.calls_per_tx)
+                            .map(|_| {
+                                erc721_mint_call(environment.erc721_address, account.address())
+                            })
+                            .collect()
+                    })
+                    .await?
+                }
+                selector => {
+                    let call = run
+                        .custom_calls
+                        .iter()
+                        .find(|custom_call| custom_call.selector == selector)
+                        .ok_or(eyre!(
+                            "run.mixed.weights has an unknown scenario `{selector}`"
+                        ))?
+                        .call(environment.custom_contract_address)?;
+
+                    estimate_invokes(shooter, account, || vec![call.clone()]).await?
+                }
+            };
+
+            projections.push(FeeProjection {
+                name: format!("Mixed {scenario}"),
+                transactions: mixed.num_transactions * weight / iteration_transactions,
+                sampled_fees,
+                fees: InvokeVersion::from_config(run),
+            });
+        }
+    }
+
+    Ok(projections)
+}
+
+/// Estimate the fees of the setup without running it, for a dry run without a state
+///
+/// A contract whose class isn't declared yet can't be simulated, its deployment is left
+/// out of the projection, and the ERC20 funding is projected with the fees of the fee
+/// token funding as the ERC20 isn't deployed either
+pub async fn estimate_setup_fees(shooter: &GatlingShooterSetup) -> Result<Vec<FeeProjection>> {
+    let config = shooter.config();
+    let (setup, run) = (&config.setup, &config.run);
+
+    let mut declarations = Vec::new();
+    let mut declared = |estimate: (FieldElement, Option<u64>)| {
+        let (class_hash, fee) = estimate;
+        declarations.extend(fee);
+
+        // Only a class already declared has instances to simulate
+        fee.is_none().then_some(class_hash)
+    };
+
+    let erc721_class = match setup.erc721_class_hash {
+        Some(class_hash) => Some(class_hash),
+        None => declared(shooter.estimate_declaration(&setup.erc721_contract).await?),
+    };
+    let account_class = declared(
+        shooter
+            .estimate_declaration(&setup.account_contract)
+            .await?,
+    );
+    let erc20_class = match (&setup.existing_erc20, setup.erc20_class_hash) {
+        (Some(_), _) => None,
+        (None, Some(class_hash)) => Some(class_hash),
+        (None, None) => declared(shooter.estimate_declaration(&setup.erc20_contract).await?),
+    };
+    let custom_class = match &setup.declare_contract {
+        Some(declare_contract) => {
+            let source = ContractSourceConfig::V1(declare_contract.source.clone());
+            declared(shooter.estimate_declaration(&source).await?)
+        }
+        None => None,
+    };
+
+    let mut contracts = Vec::new();
+    if setup.existing_erc20.is_none() {
+        contracts.push(("ERC20", erc20_class, shooter.erc20_constructor_args()));
+    }
+    contracts.push(("ERC721", erc721_class, shooter.erc721_constructor_args()));
+    if let Some(declare_contract) = &setup.declare_contract {
+        contracts.push((
+            "Custom",
+            custom_class,
+            declare_contract.constructor_calldata.clone(),
+        ));
+    }
+
+    let mut deployments = Vec::new();
+    for (name, class_hash, constructor_args) in contracts {
+        match class_hash {
+            Some(class_hash) => deployments.extend(
+                shooter
+                    .estimate_contract_deployment(class_hash, constructor_args)
+                    .await?,
+            ),
+            None => log::warn!(
+                "The {name} contract can't be estimated before its class is declared, leaving its deployment out"
+            ),
+        }
+    }
+
+    let mut projections = vec![
+        FeeProjection {
+            name: "Setup Declarations".into(),
+            transactions: declarations.len() as u64,
+            sampled_fees: declarations,
+            fees: InvokeVersion::V1,
+        },
+        FeeProjection {
+            name: "Setup Contract Deployments".into(),
+            transactions: deployments.len() as u64,
+            sampled_fees: deployments,
+            fees: InvokeVersion::V1,
+        },
+    ];
+    // Nothing is declared or deployed when a previous setup already did it
+    projections.retain(|projection| projection.transactions != 0);
+
+    let file_accounts = shooter.file_accounts_count() as u64;
+    let created_accounts = if file_accounts == 0 {
+        run.num_accounts()
+    } else {
+        0
+    };
+    // The accounts of the accounts file only get ERC20s, when the setup deploys the ERC20
+    let funded_accounts = if setup.existing_erc20.is_none() {
+        file_accounts
+    } else {
+        0
+    };
+    let batches = |accounts: u64| accounts.div_ceil(FUNDING_BATCH_SIZE as u64);
+    // The created accounts get both fee tokens and ERC20s, the benchmark deployments only fee tokens
+    let funding_transactions = 2 * batches(created_accounts)
+        + batches(funded_accounts)
+        + batches(run.num_account_deployments);
+
+    if funding_transactions != 0 {
+        projections.push(FeeProjection {
+            name: "Setup Funding".into(),
+            transactions: funding_transactions,
+            sampled_fees: vec![shooter.estimate_funding_batch().await?],
+            fees: InvokeVersion::V1,
+        });
+    }
+
+    if created_accounts != 0 && !setup.counterfactual_accounts {
+        match account_class {
+            Some(class_hash) => projections.push(FeeProjection {
+                name: "Setup Account Deployments".into(),
+                transactions: created_accounts,
+                sampled_fees: shooter
+                    .estimate_account_deployment(class_hash)
+                    .await?
+                    .into_iter()
+                    .collect(),
+                fees: InvokeVersion::V1,
+            }),
+            None => log::warn!(
+                "The accounts can't be estimated before their class is declared, leaving their deployments out"
+            ),
+        }
+    }
+
+    Ok(projections)
+}
+
+/// Fees of [`SAMPLE_SIZE`] transactions of `calls` sent in a row from `sender`, a counterfactual
+/// sender is deployed first in the simulation without counting the fee of its deployment
+async fn estimate_invokes(
+    shooter: &GatlingShooterSetup,
+    sender: &StarknetAccount,
+    calls: impl Fn() -> Vec<Call>,
+) -> Result<Vec<u64>> {
+    let deployment = shooter
+        .environment()?
+        .account_deployments
+        .get(&sender.address());
+
+    let (mut transactions, mut nonce) = match (sender.get_nonce().await, deployment) {
+        (Ok(nonce), _) => (Vec::new(), nonce),
+        (Err(ProviderError::StarknetError(StarknetError::ContractNotFound)), Some(deployment)) => (
+            vec![BroadcastedTransaction::DeployAccount(deployment.clone())],
+            FieldElement::ONE,
+        ),
+        (Err(err), _) => return Err(err.into()),
+    };
+    let deployments = transactions.len();
+
+    for _ in 0..SAMPLE_SIZE {
+        let transaction = sign_execution(calls(), nonce, sender).await?;
+        transactions.push(BroadcastedTransaction::Invoke(transaction));
+        nonce += FieldElement::ONE;
+    }
+
+    let mut fees = estimate(shooter, &transactions).await?;
+    fees.drain(..deployments);
+
+    Ok(fees)
+}
+
+/// Fees of `transactions`, simulated in order on top of the pending block
+async fn estimate(
+    shooter: &GatlingShooterSetup,
+    transactions: &[BroadcastedTransaction],
+) -> Result<Vec<u64>> {
+    let estimates = shooter
+        .deployer_account()
+        .provider()
+        .estimate_fee(transactions, [], BlockId::Tag(BlockTag::Pending))
+        .await?;
+
+    estimates.iter().map(overall_fee).collect()
+}
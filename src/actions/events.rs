@@ -0,0 +1,117 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::{eyre::Context, Result};
+use serde_derive::Serialize;
+
+/// Events of a run, written as one JSON object per line along with their timestamp
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    RunStarted {
+        erc20_transfers: u64,
+        erc721_mints: u64,
+        erc20_approvals: u64,
+        account_deployments: u64,
+        /// Summed over all the custom calls
+        custom_calls: u64,
+        /// Transactions of the mixed workload, over all of its workloads
+        mixed: u64,
+        resumed: bool,
+    },
+    SetupComplete {
+        /// Whether the environment was loaded from a state file instead of being set up
+        loaded_state: bool,
+    },
+    BenchStarted {
+        name: &'a str,
+        amount: u64,
+    },
+    BenchComplete {
+        name: &'a str,
+        transactions: usize,
+        average_tps: Option<f64>,
+    },
+    RunComplete {
+        report: &'a Path,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    timestamp: f64,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Appends the events of a run to a file or to stdout, does nothing when disabled
+pub struct EventEmitter {
+    writer: Option<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl EventEmitter {
+    /// Emit to `path`, which is appended to, or to stdout when it is `-`
+    pub fn new(path: Option<&PathBuf>) -> Result<Self> {
+        let writer: Option<Box<dyn Write + Send>> = match path {
+            None => None,
+            Some(path) if path.as_os_str() == "-" => Some(Box::new(std::io::stdout())),
+            Some(path) => Some(Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .wrap_err_with(|| format!("Could not open events file {}", path.display()))?,
+            )),
+        };
+
+        Ok(Self {
+            writer: writer.map(Mutex::new),
+        })
+    }
+
+    /// Write `event` on its own line, a failed write is only logged so that it doesn't fail the run
+    pub fn emit(&self, event: Event) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs_f64())
+            .unwrap_or_default();
+
+        let mut line = match serde_json::to_vec(&Line {
+            timestamp,
+            event: &event,
+        }) {
+            Ok(line) => line,
+            Err(err) => {
+                log::warn!("Could not serialize event {event:?}: {err}");
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let mut writer = writer.lock().expect("Event writer lock is poisoned");
+        if let Err(err) = writer.write_all(&line).and_then(|()| writer.flush()) {
+            log::warn!("Could not write event: {err}");
+        }
+    }
+
+    /// Emit an error event when `result` is an error
+    pub fn emit_error<T>(&self, result: &Result<T>) {
+        if let Err(err) = result {
+            self.emit(Event::Error {
+                message: format!("{err:#}"),
+            });
+        }
+    }
+}
@@ -1,76 +1,275 @@
 use std::{
+    cmp::Reverse,
+    collections::HashMap,
     mem,
-    sync::Arc,
-    time::{Duration, SystemTime},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use color_eyre::eyre::ensure;
-use crossbeam_queue::ArrayQueue;
-use goose::{config::GooseConfiguration, metrics::GooseRequestMetric, prelude::*};
+use color_eyre::eyre::{bail, ensure, eyre};
+use crossbeam_queue::{ArrayQueue, SegQueue};
+use goose::{
+    config::GooseConfiguration,
+    metrics::{GooseMetric, GooseRawRequest, GooseRequestMetric},
+    prelude::*,
+};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
 use serde::{de::DeserializeOwned, Serialize};
 use starknet::{
-    accounts::{
-        Account, Call, ConnectedAccount, ExecutionEncoder, RawExecution, SingleOwnerAccount,
-    },
+    accounts::{Account, Call, ConnectedAccount, ExecutionEncoder},
     core::types::{
+        BroadcastedDeployAccountTransaction, BroadcastedInvokeTransaction,
+        BroadcastedInvokeTransactionV3, DataAvailabilityMode, DeployAccountTransactionResult,
         ExecutionResult, FieldElement, InvokeTransactionResult, MaybePendingTransactionReceipt,
+        StarknetError,
     },
     macros::{felt, selector},
     providers::{
-        jsonrpc::{
-            HttpTransport, HttpTransportError, JsonRpcClientError, JsonRpcError, JsonRpcMethod,
-            JsonRpcResponse,
-        },
-        JsonRpcClient, ProviderError,
+        jsonrpc::{JsonRpcError, JsonRpcMethod, JsonRpcResponse},
+        ProviderError,
     },
-    signers::LocalWallet,
+    signers::Signer,
 };
 
 use crate::{
-    actions::shoot::{GatlingShooterSetup, CHECK_INTERVAL, MAX_FEE},
+    actions::{
+        checkpoint::{
+            Checkpoint, ACCOUNT_DEPLOYMENTS, ERC20_APPROVALS, ERC20_TRANSFERS, ERC721_MINTS, MIXED,
+            REPLAY,
+        },
+        interrupt,
+        profile::LoadProfile,
+        recording::{self, RecordedInvoke, RecordedSender},
+        shoot::{GatlingShooterSetup, MAX_FEE},
+    },
+    config::{AccountSelection, CustomCall, GatlingConfig, RunConfig},
     generators::get_rng,
+    metrics::{
+        AccountShare, DuplicateSubmissions, PhaseTiming, RequestedRate, RpcExchange,
+        SubmittedTransaction,
+    },
+    utils::{self, backoff, compute_contract_address, NewHeads, StarknetRpc},
 };
 
-use super::shoot::StarknetAccount;
+use super::shoot::{InvokeVersion, StarknetAccount};
+
+/// Output of a goose benchmark, used to build its report
+pub struct GooseBenchOutput {
+    pub metrics: GooseMetrics,
+    /// Every transaction that was successfully submitted to the node
+    pub submitted: Vec<SubmittedTransaction>,
+    /// When each submission was attempted, failed ones included
+    pub attempted: Vec<SystemTime>,
+    /// Wall-clock time the users spent waiting for their last transaction
+    pub finalizing: Option<PhaseTiming>,
+    /// Wall-clock time the users spent verifying their transactions
+    pub verification: Option<PhaseTiming>,
+    /// Amount of transactions found reverted while verifying them
+    pub reverted: u64,
+    /// Raw exchanges of the first failed requests
+    pub failed_requests: Vec<RpcExchange>,
+    /// How the node answered the resubmitted transactions, only with duplicate injection
+    pub duplicates: Option<DuplicateSubmissions>,
+    /// Amount of distinct keys transactions were signed with, only with key rotation
+    pub signing_keys: Option<u64>,
+    /// How the transactions were spread over the accounts, only with weighted account selection
+    pub account_distribution: Vec<AccountShare>,
+    /// Rates requested by the load profile, empty without one
+    pub requested_rates: Vec<RequestedRate>,
+    /// Calls packed into every transaction
+    pub calls_per_tx: u64,
+    /// Time from submission to inclusion of every transaction, only with `report.inclusion_times`
+    pub inclusion_times: Vec<Duration>,
+}
 
-pub async fn erc20(shooter: &GatlingShooterSetup) -> color_eyre::Result<GooseMetrics> {
+/// Run `amount` erc20 transfers, or as many as fit in `run.duration_secs` when it is set,
+/// recording each accepted one in `checkpoint`
+pub async fn erc20(
+    shooter: &GatlingShooterSetup,
+    amount: u64,
+    checkpoint: &Arc<Checkpoint>,
+) -> color_eyre::Result<GooseBenchOutput> {
     let environment = shooter.environment()?;
     let erc20_address = environment.erc20_address;
     let config = shooter.config();
+    let calls_per_tx = config.run.calls_per_tx;
 
-    ensure!(
-        config.run.num_erc20_transfers >= config.run.concurrency,
-        "Too few erc20 transfers for the amount of concurrency"
-    );
+    let (concurrency, user_iterations, goose_config) = match config.run.duration_of("erc20") {
+        Some(duration_secs) => {
+            let concurrency = config.run.concurrency;
+            log::info!("Sending erc20 transfers for {duration_secs} seconds");
 
-    // div_euclid will truncate integers when not evenly divisable
-    let user_iterations = config
-        .run
-        .num_erc20_transfers
-        .div_euclid(config.run.concurrency);
-    // this will always be a multiple of concurrency, unlike num_erc20_transfers
-    let total_transactions = user_iterations * config.run.concurrency;
+            // Goose stops the users once the run time is over instead of after their iterations
+            let mut default = goose_configuration(config, concurrency);
+            default.run_time = duration_secs.to_string();
 
-    // If these are not equal that means user_iterations was truncated
-    if total_transactions != config.run.num_erc20_transfers {
-        log::warn!("Number of erc20 transfers is not evenly divisble by concurrency, doing {total_transactions} transfers instead");
-    }
+            (concurrency, 0, default)
+        }
+        None => {
+            ensure!(
+                config.run.num_erc20_transfers >= config.run.concurrency,
+                "Too few erc20 transfers for the amount of concurrency"
+            );
+
+            // A resumed run can have less transfers left than there are users
+            let concurrency = config.run.concurrency.min(amount);
+            // div_euclid will truncate integers when not evenly divisable
+            let user_iterations = amount.div_euclid(concurrency);
+            // this will always be a multiple of concurrency, unlike amount
+            let total_transactions = user_iterations * concurrency;
+
+            // If these are not equal that means user_iterations was truncated
+            if total_transactions != amount {
+                log::warn!("Number of erc20 transfers is not evenly divisble by concurrency, doing {total_transactions} transfers instead");
+            }
 
-    let goose_config = {
-        let mut default = GooseConfiguration::default();
-        default.host = config.rpc.url.clone();
-        default.iterations = user_iterations as usize;
-        default.users = Some(config.run.concurrency as usize);
-        default
+            let mut default = goose_configuration(config, concurrency);
+            default.iterations = user_iterations as usize;
+
+            (concurrency, user_iterations, default)
+        }
+    };
+
+    let seed = config.run.seed.unwrap_or_else(rand::random);
+
+    let weighted_accounts = match config.run.account_selection {
+        AccountSelection::Uniform => None,
+        AccountSelection::Zipf { exponent } => {
+            ensure!(
+                environment.account_deployments.is_empty(),
+                "Weighted account selection needs the accounts to be deployed during setup"
+            );
+
+            log::info!("Picking the accounts of erc20 transfers following a Zipf distribution of exponent {exponent} with seed {seed}");
+
+            Some(Arc::new(
+                WeightedAccounts::zipf(&environment.accounts, exponent, seed).await?,
+            ))
+        }
     };
 
-    let transfer_setup: TransactionFunction =
-        setup(environment.accounts.clone(), user_iterations as usize).await?;
+    let duplicates = match config.run.duplicate_rate {
+        0.0 => None,
+        rate => {
+            log::info!("Resubmitting {rate} of the erc20 transfers with seed {seed}");
 
-    let transfer: TransactionFunction =
-        Arc::new(move |user| Box::pin(transfer(user, erc20_address)));
+            // Offset the seed so that duplicates are not picked along with the accounts
+            Some(Arc::new(DuplicateInjection::new(
+                rate,
+                seed.wrapping_add(1),
+            )?))
+        }
+    };
+
+    let key_rotation = if config.run.rotate_signing_keys {
+        ensure!(
+            weighted_accounts.is_none(),
+            "Rotating the signing keys sends from every account in turn, it can't be combined with a Zipf run.account_selection"
+        );
+        ensure!(
+            environment.account_deployments.is_empty(),
+            "Rotating the signing keys needs the accounts to be deployed during setup"
+        );
+        ensure!(
+            shooter.accounts_have_own_keys(),
+            "Rotating the signing keys needs every account to have a key of its own, run the setup \
+            with run.rotate_signing_keys or use setup.accounts_file"
+        );
+
+        log::info!(
+            "Sending every erc20 transfer from the next of the {} accounts, each one signing with a key of its own",
+            environment.accounts.len()
+        );
+
+        Some(Arc::new(KeyRotation::new(&environment.accounts).await?))
+    } else {
+        None
+    };
+
+    let failures = Arc::new(FailureCapture::new(config.report.capture_failures));
+    let tx_timeout = Duration::from_secs(config.run.tx_timeout_secs);
+    let check_interval = config.run.confirmation_interval();
+    let max_check_interval = config.run.max_confirmation_interval();
+    let transfer_setup: TransactionFunction = setup(
+        environment.accounts.clone(),
+        &environment.account_deployments,
+        concurrency,
+        config.run.share_accounts,
+        false,
+        user_iterations as usize,
+        &failures,
+        shooter.new_heads(),
+    )
+    .await?;
+
+    let submitted = Arc::new(SegQueue::new());
+    let attempted = Arc::new(SegQueue::new());
+    let load_profile = load_profile(&config.run)?;
+    let finalizing = Arc::new(PhaseTimer::default());
+    let inclusion = InclusionTracker::new(shooter);
+
+    let transfer: TransactionFunction = {
+        let submitted = submitted.clone();
+        let inclusion = inclusion.clone();
+        let attempted = attempted.clone();
+        let checkpoint = checkpoint.clone();
+        let load_profile = load_profile.clone();
+        let weighted_accounts = weighted_accounts.clone();
+        let duplicates = duplicates.clone();
+        let key_rotation = key_rotation.clone();
+        Arc::new(move |user| {
+            let submitted = submitted.clone();
+            let inclusion = inclusion.clone();
+            let attempted = attempted.clone();
+            let checkpoint = checkpoint.clone();
+            let load_profile = load_profile.clone();
+            let weighted_accounts = weighted_accounts.clone();
+            let duplicates = duplicates.clone();
+            let key_rotation = key_rotation.clone();
+            Box::pin(async move {
+                // Goose can't be stopped from here, its remaining iterations do nothing instead
+                if interrupt::is_interrupted() {
+                    return Ok(());
+                }
 
-    let transfer_wait: TransactionFunction = goose_user_wait_last_tx();
+                if let Some(load_profile) = load_profile {
+                    load_profile.wait().await;
+                }
+
+                attempted.push(SystemTime::now());
+                transfer(
+                    user,
+                    erc20_address,
+                    calls_per_tx,
+                    &submitted,
+                    inclusion.as_deref(),
+                    weighted_accounts.as_deref(),
+                    duplicates.as_deref(),
+                    key_rotation.as_deref(),
+                )
+                .await?;
+                checkpoint.record(ERC20_TRANSFERS);
+
+                Ok(())
+            })
+        })
+    };
+
+    let transfer_wait: TransactionFunction = goose_user_wait_last_tx(
+        finalizing.clone(),
+        tx_timeout,
+        check_interval,
+        max_check_interval,
+    );
+    let verification = Arc::new(PhaseTimer::default());
+    let reverted = Arc::new(AtomicU64::new(0));
 
     let metrics = GooseAttack::initialize_with_config(goose_config.clone())?
         .register_scenario(
@@ -92,73 +291,722 @@ pub async fn erc20(shooter: &GatlingShooterSetup) -> color_eyre::Result<GooseMet
                         .set_on_stop(),
                 )
                 .register_transaction(
-                    transaction!(verify_transactions)
-                        .set_name("Verification")
-                        .set_sequence(3)
+                    Transaction::new(goose_user_verify_transactions(
+                        verification.clone(),
+                        reverted.clone(),
+                    ))
+                    .set_name("Verification")
+                    .set_sequence(3)
+                    .set_on_stop(),
+                ),
+        )
+        .execute()
+        .await?;
+
+    Ok(GooseBenchOutput {
+        metrics,
+        submitted: drain_queue(&submitted),
+        attempted: drain_queue(&attempted),
+        finalizing: finalizing.timing(),
+        verification: verification.timing(),
+        reverted: reverted.load(Ordering::Relaxed),
+        failed_requests: failures.exchanges(),
+        duplicates: duplicates.map(|duplicates| duplicates.submissions()),
+        signing_keys: key_rotation.map(|key_rotation| key_rotation.used_keys()),
+        account_distribution: weighted_accounts
+            .map(|weighted_accounts| weighted_accounts.distribution())
+            .unwrap_or_default(),
+        requested_rates: load_profile
+            .map(|load_profile| load_profile.requested_rates())
+            .unwrap_or_default(),
+        calls_per_tx: config.run.calls_per_tx,
+        inclusion_times: InclusionTracker::times(inclusion).await,
+    })
+}
+
+/// Send erc20 transfers for `duration` without measuring them, so that the node
+/// is warmed up once the benchmarks start
+pub async fn warmup(shooter: &GatlingShooterSetup, duration: Duration) -> color_eyre::Result<()> {
+    let environment = shooter.environment()?;
+    let erc20_address = environment.erc20_address;
+    let config = shooter.config();
+
+    let goose_config = {
+        let mut default = goose_configuration(config, config.run.concurrency);
+        default.run_time = duration.as_secs().to_string();
+        default
+    };
+
+    let failures = Arc::new(FailureCapture::new(0));
+    let tx_timeout = Duration::from_secs(config.run.tx_timeout_secs);
+    let check_interval = config.run.confirmation_interval();
+    let max_check_interval = config.run.max_confirmation_interval();
+    let warmup_setup: TransactionFunction = setup(
+        environment.accounts.clone(),
+        &environment.account_deployments,
+        config.run.concurrency,
+        config.run.share_accounts,
+        false,
+        0,
+        &failures,
+        shooter.new_heads(),
+    )
+    .await?;
+
+    // Warmup transactions are not part of any benchmark
+    let submitted = Arc::new(SegQueue::new());
+
+    let warmup_transfer: TransactionFunction = Arc::new(move |user| {
+        let submitted = submitted.clone();
+        Box::pin(async move {
+            transfer(user, erc20_address, 1, &submitted, None, None, None, None).await?;
+
+            Ok(())
+        })
+    });
+
+    // The last transactions are waited for so that the benchmarks start from settled nonces
+    let warmup_wait: TransactionFunction = goose_user_wait_last_tx(
+        Arc::new(PhaseTimer::default()),
+        tx_timeout,
+        check_interval,
+        max_check_interval,
+    );
+
+    GooseAttack::initialize_with_config(goose_config)?
+        .register_scenario(
+            scenario!("Warmup")
+                .register_transaction(
+                    Transaction::new(warmup_setup)
+                        .set_name("Warmup Setup")
+                        .set_on_start(),
+                )
+                .register_transaction(
+                    Transaction::new(warmup_transfer)
+                        .set_name("Warmup Transfer")
+                        .set_sequence(1),
+                )
+                .register_transaction(
+                    Transaction::new(warmup_wait)
+                        .set_name("Warmup Finalizing")
+                        .set_sequence(2)
                         .set_on_stop(),
                 ),
         )
         .execute()
         .await?;
 
-    Ok(metrics)
+    Ok(())
+}
+
+/// Run `amount` transactions of erc20 approvals each followed by a `transferFrom` spending
+/// them, recording each accepted transaction in `checkpoint`
+///
+/// Every account approves the next one of the funded accounts as its spender, which
+/// sends the `transferFrom` from its own nonces
+pub async fn erc20_approvals(
+    shooter: &GatlingShooterSetup,
+    amount: u64,
+    checkpoint: &Arc<Checkpoint>,
+) -> color_eyre::Result<GooseBenchOutput> {
+    let erc20_address = shooter.environment()?.erc20_address;
+
+    own_account_bench(
+        shooter,
+        OwnAccountBench {
+            name: "Approval",
+            workload: ERC20_APPROVALS.to_owned(),
+            configured: shooter.config().run.num_erc20_approvals,
+            amount,
+            sends: vec![
+                OwnAccountSend {
+                    name: "Approval".into(),
+                    from_spender: false,
+                    call: Arc::new(move |_, spender| erc20_approve_call(erc20_address, spender)),
+                },
+                OwnAccountSend {
+                    name: "Approval Transfer From".into(),
+                    from_spender: true,
+                    call: Arc::new(move |owner, _| erc20_transfer_from_call(erc20_address, owner)),
+                },
+            ],
+        },
+        checkpoint,
+    )
+    .await
+}
+
+/// Run `amount` times the invocation of a contract from the config,
+/// recording each accepted one in `checkpoint` as `workload`
+pub async fn custom_call(
+    shooter: &GatlingShooterSetup,
+    custom_call: &CustomCall,
+    workload: String,
+    amount: u64,
+    checkpoint: &Arc<Checkpoint>,
+) -> color_eyre::Result<GooseBenchOutput> {
+    let call = custom_call.call(shooter.environment()?.custom_contract_address)?;
+
+    own_account_bench(
+        shooter,
+        OwnAccountBench {
+            name: &custom_call.selector,
+            workload,
+            configured: custom_call.count,
+            amount,
+            sends: vec![OwnAccountSend {
+                name: custom_call.selector.clone(),
+                from_spender: false,
+                call: Arc::new(move |_, _| call.clone()),
+            }],
+        },
+        checkpoint,
+    )
+    .await
 }
 
-pub async fn erc721(shooter: &GatlingShooterSetup) -> color_eyre::Result<GooseMetrics> {
+/// Send the recorded `invokes` again in order, signed anew by the account of the user sending
+/// them or by the deployer, recording each accepted one in `checkpoint`
+pub async fn replay(
+    shooter: &GatlingShooterSetup,
+    invokes: &[RecordedInvoke],
+    checkpoint: &Arc<Checkpoint>,
+) -> color_eyre::Result<GooseBenchOutput> {
+    let environment = shooter.environment()?;
     let config = shooter.config();
+    let amount = invokes.len() as u64;
+
+    ensure!(
+        config.run.concurrency != 0,
+        "run.concurrency should be at least 1 to replay transactions"
+    );
+
+    let concurrency = config.run.concurrency.min(amount);
+    // div_euclid will truncate integers when not evenly divisable
+    let user_iterations = amount.div_euclid(concurrency);
+    // this will always be a multiple of concurrency, unlike amount
+    let total_iterations = user_iterations * concurrency;
+
+    // If these are not equal that means user_iterations was truncated
+    if total_iterations != amount {
+        log::warn!("Number of recorded transactions is not evenly divisble by concurrency, replaying the first {total_iterations} of them");
+    }
+
+    let goose_config = {
+        let mut default = goose_configuration(config, concurrency);
+        default.iterations = user_iterations as usize;
+        default
+    };
+
+    let failures = Arc::new(FailureCapture::new(config.report.capture_failures));
+    let tx_timeout = Duration::from_secs(config.run.tx_timeout_secs);
+    let check_interval = config.run.confirmation_interval();
+    let max_check_interval = config.run.max_confirmation_interval();
+    let replay_setup: TransactionFunction = setup(
+        environment.accounts.clone(),
+        &environment.account_deployments,
+        concurrency,
+        config.run.share_accounts,
+        false,
+        user_iterations as usize,
+        &failures,
+        shooter.new_heads(),
+    )
+    .await?;
+
+    let queue = Arc::new(ArrayQueue::new(total_iterations as usize));
+    for invoke in invokes.iter().take(total_iterations as usize) {
+        queue
+            .push(invoke.clone())
+            .expect("Queue should have enough space for all the replayed transactions");
+    }
+
+    // The deployer sends one transaction at a time so that its nonces follow each other
+    let deployer = Arc::new(shooter.deployer_account().clone());
+    let deployer_nonce = Arc::new(tokio::sync::Mutex::new(deployer.get_nonce().await?));
+
+    let submitted = Arc::new(SegQueue::new());
+    let attempted = Arc::new(SegQueue::new());
+    let load_profile = load_profile(&config.run)?;
+    let finalizing = Arc::new(PhaseTimer::default());
+    let inclusion = InclusionTracker::new(shooter);
+
+    let send: TransactionFunction = {
+        let submitted = submitted.clone();
+        let inclusion = inclusion.clone();
+        let attempted = attempted.clone();
+        let checkpoint = checkpoint.clone();
+        let load_profile = load_profile.clone();
+        Arc::new(move |user| {
+            let queue = queue.clone();
+            let deployer = deployer.clone();
+            let deployer_nonce = deployer_nonce.clone();
+            let submitted = submitted.clone();
+            let inclusion = inclusion.clone();
+            let attempted = attempted.clone();
+            let checkpoint = checkpoint.clone();
+            let load_profile = load_profile.clone();
+            Box::pin(async move {
+                if interrupt::is_interrupted() {
+                    return Ok(());
+                }
+
+                let Some(invoke) = queue.pop() else {
+                    return Ok(());
+                };
+
+                if let Some(load_profile) = load_profile {
+                    load_profile.wait().await;
+                }
+
+                attempted.push(SystemTime::now());
+                let response = match invoke.sender {
+                    RecordedSender::Account => {
+                        send_from_own_account(user, invoke.calls(), None).await?
+                    }
+                    RecordedSender::Deployer => {
+                        let mut nonce = deployer_nonce.lock().await;
+                        let (response, _): (InvokeTransactionResult, _) = send_execution(
+                            user,
+                            invoke.calls(),
+                            *nonce,
+                            &deployer,
+                            JsonRpcMethod::AddInvokeTransaction,
+                        )
+                        .await?;
+                        *nonce += FieldElement::ONE;
+
+                        response
+                    }
+                };
+                record_submission(
+                    user,
+                    response.transaction_hash,
+                    &submitted,
+                    inclusion.as_deref(),
+                );
+                checkpoint.record(REPLAY);
+
+                Ok(())
+            })
+        })
+    };
+
+    let replay_wait: TransactionFunction = goose_user_wait_last_tx(
+        finalizing.clone(),
+        tx_timeout,
+        check_interval,
+        max_check_interval,
+    );
+    let verification = Arc::new(PhaseTimer::default());
+    let reverted = Arc::new(AtomicU64::new(0));
+
+    let metrics = GooseAttack::initialize_with_config(goose_config.clone())?
+        .register_scenario(
+            scenario!("Replay")
+                .register_transaction(
+                    Transaction::new(replay_setup)
+                        .set_name("Replay Setup")
+                        .set_on_start(),
+                )
+                .register_transaction(Transaction::new(send).set_name("Replay").set_sequence(1))
+                .register_transaction(
+                    Transaction::new(replay_wait)
+                        .set_name("Replay Finalizing")
+                        .set_sequence(2)
+                        .set_on_stop(),
+                )
+                .register_transaction(
+                    Transaction::new(goose_user_verify_transactions(
+                        verification.clone(),
+                        reverted.clone(),
+                    ))
+                    .set_name("Verification")
+                    .set_sequence(3)
+                    .set_on_stop(),
+                ),
+        )
+        .execute()
+        .await?;
+
+    Ok(GooseBenchOutput {
+        metrics,
+        submitted: drain_queue(&submitted),
+        attempted: drain_queue(&attempted),
+        finalizing: finalizing.timing(),
+        verification: verification.timing(),
+        reverted: reverted.load(Ordering::Relaxed),
+        failed_requests: failures.exchanges(),
+        duplicates: None,
+        signing_keys: None,
+        account_distribution: Vec::new(),
+        requested_rates: load_profile
+            .map(|load_profile| load_profile.requested_rates())
+            .unwrap_or_default(),
+        calls_per_tx: 1,
+        inclusion_times: InclusionTracker::times(inclusion).await,
+    })
+}
+
+/// Call of a transaction sent at each iteration, given the addresses of the account
+/// of the user and of its spender
+type OwnAccountCall = Arc<dyn Fn(FieldElement, FieldElement) -> Call + Send + Sync>;
+
+/// Transaction of an iteration, registered as a goose transaction of its own
+struct OwnAccountSend {
+    /// Name of the goose transaction
+    name: String,
+    /// Whether it's sent by the spender of the user instead of its account
+    from_spender: bool,
+    call: OwnAccountCall,
+}
+
+struct OwnAccountBench<'a> {
+    /// Name of the goose scenario
+    name: &'a str,
+    /// Checkpoint workload every transaction is recorded as
+    workload: String,
+    /// Transactions in the config, `amount` can be less when resuming
+    configured: u64,
+    amount: u64,
+    /// Transactions every user sends at each iteration, in order
+    sends: Vec<OwnAccountSend>,
+}
+
+/// Run a benchmark where every user sends `bench.sends` from its own account, or from its
+/// spender, at each iteration
+///
+/// On its first iteration a spender waits for the transaction sent before it to be accepted,
+/// so that its first `transferFrom` finds the approval
+async fn own_account_bench(
+    shooter: &GatlingShooterSetup,
+    bench: OwnAccountBench<'_>,
+    checkpoint: &Arc<Checkpoint>,
+) -> color_eyre::Result<GooseBenchOutput> {
     let environment = shooter.environment()?;
+    let config = shooter.config();
+    let OwnAccountBench {
+        name,
+        workload,
+        configured,
+        amount,
+        sends,
+    } = bench;
+    let iteration_transactions = sends.len() as u64;
 
     ensure!(
-        config.run.num_erc721_mints >= config.run.concurrency,
-        "Too few erc721 mints for the amount of concurrency"
+        configured >= config.run.concurrency * iteration_transactions,
+        "Too few {name} transactions for the amount of concurrency, every user sends {iteration_transactions} of them at each iteration"
     );
 
+    // A resumed run can have less iterations left than there are users
+    let concurrency = config.run.concurrency.min(amount / iteration_transactions);
     // div_euclid will truncate integers when not evenly divisable
-    let user_iterations = config
-        .run
-        .num_erc721_mints
-        .div_euclid(config.run.concurrency);
-    // this will always be a multiple of concurrency, unlike num_erc721_mints
-    let total_transactions = user_iterations * config.run.concurrency;
+    let user_iterations = amount.div_euclid(concurrency * iteration_transactions);
+    // this will always be a multiple of concurrency and of the iteration, unlike amount
+    let total_transactions = user_iterations * concurrency * iteration_transactions;
 
     // If these are not equal that means user_iterations was truncated
-    if total_transactions != config.run.num_erc721_mints {
-        log::warn!("Number of erc721 mints is not evenly divisble by concurrency, doing {total_transactions} mints instead");
+    if total_transactions != amount {
+        log::warn!("Number of {name} transactions is not evenly divisble by concurrency times the {iteration_transactions} transactions of an iteration, doing {total_transactions} transactions instead");
     }
 
-    let goose_mint_config = {
-        let mut default = GooseConfiguration::default();
-        default.host = config.rpc.url.clone();
+    let goose_config = {
+        let mut default = goose_configuration(config, concurrency);
         default.iterations = user_iterations as usize;
-        default.users = Some(config.run.concurrency as usize);
         default
     };
 
-    let nonces = Arc::new(ArrayQueue::new(total_transactions as usize));
-    let erc721_address = environment.erc721_address;
-    let mut nonce = shooter.deployer_account().get_nonce().await?;
+    let failures = Arc::new(FailureCapture::new(config.report.capture_failures));
+    let tx_timeout = Duration::from_secs(config.run.tx_timeout_secs);
+    let check_interval = config.run.confirmation_interval();
+    let max_check_interval = config.run.max_confirmation_interval();
+    let bench_setup: TransactionFunction = setup(
+        environment.accounts.clone(),
+        &environment.account_deployments,
+        concurrency,
+        config.run.share_accounts,
+        sends.iter().any(|send| send.from_spender),
+        (user_iterations * iteration_transactions) as usize,
+        &failures,
+        shooter.new_heads(),
+    )
+    .await?;
+
+    let submitted = Arc::new(SegQueue::new());
+    let attempted = Arc::new(SegQueue::new());
+    let load_profile = load_profile(&config.run)?;
+    let finalizing = Arc::new(PhaseTimer::default());
+    let inclusion = InclusionTracker::new(shooter);
+    let workload = Arc::new(workload);
+
+    let mut scenario = scenario!(name).register_transaction(
+        Transaction::new(bench_setup)
+            .set_name(&format!("{name} Setup"))
+            .set_on_start(),
+    );
+
+    for OwnAccountSend {
+        name,
+        from_spender,
+        call,
+    } in sends
+    {
+        let submitted = submitted.clone();
+        let inclusion = inclusion.clone();
+        let attempted = attempted.clone();
+        let checkpoint = checkpoint.clone();
+        let load_profile = load_profile.clone();
+        let workload = workload.clone();
+        let send: TransactionFunction = Arc::new(move |user| {
+            let submitted = submitted.clone();
+            let inclusion = inclusion.clone();
+            let attempted = attempted.clone();
+            let checkpoint = checkpoint.clone();
+            let load_profile = load_profile.clone();
+            let workload = workload.clone();
+            let call = call.clone();
+            Box::pin(async move {
+                if interrupt::is_interrupted() {
+                    return Ok(());
+                }
+
+                if let Some(load_profile) = load_profile {
+                    load_profile.wait().await;
+                }
 
-    for _ in 0..total_transactions {
-        nonces
-            .push(nonce)
-            .expect("ArrayQueue has capacity for all mints");
-        nonce += FieldElement::ONE;
+                let state = user
+                    .get_session_data::<GooseUserState>()
+                    .expect("Should be in a goose user with GooseUserState session data");
+                let address = state.account.address();
+                let spender = state
+                    .spender
+                    .as_ref()
+                    .map_or(FieldElement::ZERO, |(spender, _)| spender.address());
+                let first_transaction = match state.prev_tx.as_slice() {
+                    [first] if from_spender => Some(*first),
+                    _ => None,
+                };
+
+                if let Some(first_transaction) = first_transaction {
+                    wait_for_tx(
+                        user,
+                        first_transaction,
+                        tx_timeout,
+                        check_interval,
+                        max_check_interval,
+                    )
+                    .await?;
+                }
+
+                attempted.push(SystemTime::now());
+                let calls = vec![call(address, spender)];
+                let response = if from_spender {
+                    send_from_spender(user, calls).await?
+                } else {
+                    send_from_own_account(user, calls, None).await?
+                };
+                record_submission(
+                    user,
+                    response.transaction_hash,
+                    &submitted,
+                    inclusion.as_deref(),
+                );
+                checkpoint.record(&workload);
+
+                Ok(())
+            })
+        });
+
+        scenario =
+            scenario.register_transaction(Transaction::new(send).set_name(&name).set_sequence(1));
     }
 
+    let bench_wait: TransactionFunction = goose_user_wait_last_tx(
+        finalizing.clone(),
+        tx_timeout,
+        check_interval,
+        max_check_interval,
+    );
+    let verification = Arc::new(PhaseTimer::default());
+    let reverted = Arc::new(AtomicU64::new(0));
+
+    let metrics = GooseAttack::initialize_with_config(goose_config.clone())?
+        .register_scenario(
+            scenario
+                .register_transaction(
+                    Transaction::new(bench_wait)
+                        .set_name(&format!("{name} Finalizing"))
+                        .set_sequence(2)
+                        .set_on_stop(),
+                )
+                .register_transaction(
+                    Transaction::new(goose_user_verify_transactions(
+                        verification.clone(),
+                        reverted.clone(),
+                    ))
+                    .set_name("Verification")
+                    .set_sequence(3)
+                    .set_on_stop(),
+                ),
+        )
+        .execute()
+        .await?;
+
+    Ok(GooseBenchOutput {
+        metrics,
+        submitted: drain_queue(&submitted),
+        attempted: drain_queue(&attempted),
+        finalizing: finalizing.timing(),
+        verification: verification.timing(),
+        reverted: reverted.load(Ordering::Relaxed),
+        failed_requests: failures.exchanges(),
+        duplicates: None,
+        signing_keys: None,
+        account_distribution: Vec::new(),
+        requested_rates: load_profile
+            .map(|load_profile| load_profile.requested_rates())
+            .unwrap_or_default(),
+        calls_per_tx: 1,
+        inclusion_times: InclusionTracker::times(inclusion).await,
+    })
+}
+
+/// Run `amount` erc721 mints, or as many as fit in `run.duration_secs` when it is set,
+/// recording each accepted one in `checkpoint`
+pub async fn erc721(
+    shooter: &GatlingShooterSetup,
+    amount: u64,
+    checkpoint: &Arc<Checkpoint>,
+) -> color_eyre::Result<GooseBenchOutput> {
+    let config = shooter.config();
+    let environment = shooter.environment()?;
+
+    let (concurrency, user_iterations, goose_mint_config) = match config.run.duration_of("erc721") {
+        Some(duration_secs) => {
+            let concurrency = config.run.concurrency;
+            log::info!("Sending erc721 mints for {duration_secs} seconds");
+
+            // Goose stops the users once the run time is over instead of after their iterations
+            let mut default = goose_configuration(config, concurrency);
+            default.run_time = duration_secs.to_string();
+
+            (concurrency, 0, default)
+        }
+        None => {
+            ensure!(
+                config.run.num_erc721_mints >= config.run.concurrency,
+                "Too few erc721 mints for the amount of concurrency"
+            );
+
+            // A resumed run can have less mints left than there are users
+            let concurrency = config.run.concurrency.min(amount);
+            // div_euclid will truncate integers when not evenly divisable
+            let user_iterations = amount.div_euclid(concurrency);
+            // this will always be a multiple of concurrency, unlike amount
+            let total_transactions = user_iterations * concurrency;
+
+            // If these are not equal that means user_iterations was truncated
+            if total_transactions != amount {
+                log::warn!("Number of erc721 mints is not evenly divisble by concurrency, doing {total_transactions} mints instead");
+            }
+
+            let mut default = goose_configuration(config, concurrency);
+            default.iterations = user_iterations as usize;
+
+            (concurrency, user_iterations, default)
+        }
+    };
+
+    let erc721_address = environment.erc721_address;
+    let calls_per_tx = config.run.calls_per_tx;
+    let nonces = Arc::new(DeployerNonces::new(
+        shooter.deployer_account().get_nonce().await?,
+    ));
+
     let from_account = shooter.deployer_account().clone();
 
-    let mint_setup: TransactionFunction =
-        setup(environment.accounts.clone(), user_iterations as usize).await?;
+    let recipients = config.run.erc721_unique_recipients.then(|| {
+        let seed = config.run.seed.unwrap_or_else(rand::random);
+        log::info!("Minting every erc721 token to a new address derived from seed {seed}");
 
-    let mint: TransactionFunction = Arc::new(move |user| {
-        let nonce = nonces
-            .pop()
-            .expect("Nonce ArrayQueue should have enough nonces for all mints");
-        let from_account = from_account.clone();
-        Box::pin(async move { mint(user, erc721_address, nonce, &from_account).await })
+        Arc::new(UniqueRecipients::new(seed))
     });
 
-    let mint_wait: TransactionFunction = goose_user_wait_last_tx();
+    let failures = Arc::new(FailureCapture::new(config.report.capture_failures));
+    let tx_timeout = Duration::from_secs(config.run.tx_timeout_secs);
+    let check_interval = config.run.confirmation_interval();
+    let max_check_interval = config.run.max_confirmation_interval();
+    let mint_setup: TransactionFunction = setup(
+        environment.accounts.clone(),
+        &environment.account_deployments,
+        concurrency,
+        config.run.share_accounts,
+        false,
+        user_iterations as usize,
+        &failures,
+        shooter.new_heads(),
+    )
+    .await?;
+
+    let submitted = Arc::new(SegQueue::new());
+    let attempted = Arc::new(SegQueue::new());
+    let load_profile = load_profile(&config.run)?;
+    let finalizing = Arc::new(PhaseTimer::default());
+    let inclusion = InclusionTracker::new(shooter);
+
+    let mint: TransactionFunction = {
+        let submitted = submitted.clone();
+        let inclusion = inclusion.clone();
+        let attempted = attempted.clone();
+        let checkpoint = checkpoint.clone();
+        let load_profile = load_profile.clone();
+        let recipients = recipients.clone();
+        Arc::new(move |user| {
+            let nonce = nonces.next();
+            let from_account = from_account.clone();
+            let submitted = submitted.clone();
+            let inclusion = inclusion.clone();
+            let attempted = attempted.clone();
+            let checkpoint = checkpoint.clone();
+            let load_profile = load_profile.clone();
+            let recipients = recipients.clone();
+            Box::pin(async move {
+                if interrupt::is_interrupted() {
+                    return Ok(());
+                }
+
+                if let Some(load_profile) = load_profile {
+                    load_profile.wait().await;
+                }
+
+                attempted.push(SystemTime::now());
+                mint(
+                    user,
+                    erc721_address,
+                    calls_per_tx,
+                    nonce,
+                    &from_account,
+                    &submitted,
+                    inclusion.as_deref(),
+                    recipients.as_deref(),
+                )
+                .await?;
+                checkpoint.record(ERC721_MINTS);
+
+                Ok(())
+            })
+        })
+    };
+
+    let mint_wait: TransactionFunction = goose_user_wait_last_tx(
+        finalizing.clone(),
+        tx_timeout,
+        check_interval,
+        max_check_interval,
+    );
+    let verification = Arc::new(PhaseTimer::default());
+    let reverted = Arc::new(AtomicU64::new(0));
 
     let metrics = GooseAttack::initialize_with_config(goose_mint_config.clone())?
         .register_scenario(
@@ -176,133 +1024,1034 @@ pub async fn erc721(shooter: &GatlingShooterSetup) -> color_eyre::Result<GooseMe
                         .set_on_stop(),
                 )
                 .register_transaction(
-                    transaction!(verify_transactions)
-                        .set_name("Verification")
-                        .set_sequence(3)
+                    Transaction::new(goose_user_verify_transactions(
+                        verification.clone(),
+                        reverted.clone(),
+                    ))
+                    .set_name("Verification")
+                    .set_sequence(3)
+                    .set_on_stop(),
+                ),
+        )
+        .execute()
+        .await?;
+
+    Ok(GooseBenchOutput {
+        metrics,
+        submitted: drain_queue(&submitted),
+        attempted: drain_queue(&attempted),
+        finalizing: finalizing.timing(),
+        verification: verification.timing(),
+        reverted: reverted.load(Ordering::Relaxed),
+        failed_requests: failures.exchanges(),
+        duplicates: None,
+        signing_keys: None,
+        account_distribution: Vec::new(),
+        requested_rates: load_profile
+            .map(|load_profile| load_profile.requested_rates())
+            .unwrap_or_default(),
+        calls_per_tx: config.run.calls_per_tx,
+        inclusion_times: InclusionTracker::times(inclusion).await,
+    })
+}
+
+/// Transaction of the mixed benchmark, sent by a user as many times as its weight
+/// at every iteration
+#[derive(Clone)]
+enum MixedSend {
+    Transfer,
+    /// Mint from the deployer with the next of the nonces handed out to the mints
+    Mint(Arc<DeployerNonces>),
+    Call(Call),
+}
+
+/// Run `amount` transactions of the workloads of `run.mixed`, or as many as fit in
+/// `run.duration_secs` when it is set, in a single attack, each user interleaving them
+/// in the proportions of their weights, and recording each accepted one in `checkpoint`
+pub async fn mixed(
+    shooter: &GatlingShooterSetup,
+    amount: u64,
+    checkpoint: &Arc<Checkpoint>,
+) -> color_eyre::Result<GooseBenchOutput> {
+    let environment = shooter.environment()?;
+    let config = shooter.config();
+    let mixed = config
+        .run
+        .mixed
+        .as_ref()
+        .ok_or(eyre!("run.mixed should be set to run a mixed workload"))?;
+    let weights = mixed.reduced_weights();
+    let iteration_transactions = mixed.iteration_transactions();
+
+    let (concurrency, user_iterations, goose_config) = match config.run.duration_of("mixed") {
+        Some(duration_secs) => {
+            let concurrency = config.run.concurrency;
+            log::info!("Sending the mixed workload for {duration_secs} seconds");
+
+            // Goose stops the users once the run time is over instead of after their iterations
+            let mut default = goose_configuration(config, concurrency);
+            default.run_time = duration_secs.to_string();
+
+            (concurrency, 0, default)
+        }
+        None => {
+            ensure!(
+                mixed.num_transactions >= config.run.concurrency * iteration_transactions,
+                "Too few mixed transactions for the amount of concurrency, every user sends {iteration_transactions} of them at each iteration"
+            );
+
+            // A resumed run can have less iterations left than there are users
+            let concurrency = config.run.concurrency.min(amount / iteration_transactions);
+            // div_euclid will truncate integers when not evenly divisable
+            let user_iterations = amount.div_euclid(concurrency * iteration_transactions);
+            // this will always be a multiple of concurrency and of the iteration, unlike amount
+            let total_transactions = user_iterations * concurrency * iteration_transactions;
+
+            // If these are not equal that means user_iterations was truncated
+            if total_transactions != amount {
+                log::warn!("Number of mixed transactions is not evenly divisble by concurrency times the {iteration_transactions} transactions of an iteration, doing {total_transactions} transactions instead");
+            }
+
+            let mut default = goose_configuration(config, concurrency);
+            default.iterations = user_iterations as usize;
+
+            (concurrency, user_iterations, default)
+        }
+    };
+
+    let erc20_address = environment.erc20_address;
+    let erc721_address = environment.erc721_address;
+    let calls_per_tx = config.run.calls_per_tx;
+    let from_account = shooter.deployer_account().clone();
+    let recipients = config.run.erc721_unique_recipients.then(|| {
+        let seed = config.run.seed.unwrap_or_else(rand::random);
+        log::info!("Minting every erc721 token to a new address derived from seed {seed}");
+
+        Arc::new(UniqueRecipients::new(seed))
+    });
+
+    let mut sends = Vec::with_capacity(weights.len());
+    for &(scenario, weight) in &weights {
+        let send = match scenario {
+            "erc20" => MixedSend::Transfer,
+            "erc721" => MixedSend::Mint(Arc::new(DeployerNonces::new(
+                shooter.deployer_account().get_nonce().await?,
+            ))),
+            selector => {
+                let custom_call = config
+                    .run
+                    .custom_calls
+                    .iter()
+                    .find(|custom_call| custom_call.selector == selector)
+                    .ok_or(eyre!(
+                        "run.mixed.weights has an unknown scenario `{selector}`"
+                    ))?;
+
+                MixedSend::Call(custom_call.call(environment.custom_contract_address)?)
+            }
+        };
+
+        sends.push((scenario, weight, send));
+    }
+
+    let failures = Arc::new(FailureCapture::new(config.report.capture_failures));
+    let tx_timeout = Duration::from_secs(config.run.tx_timeout_secs);
+    let check_interval = config.run.confirmation_interval();
+    let max_check_interval = config.run.max_confirmation_interval();
+    let mixed_setup: TransactionFunction = setup(
+        environment.accounts.clone(),
+        &environment.account_deployments,
+        concurrency,
+        config.run.share_accounts,
+        false,
+        (user_iterations * iteration_transactions) as usize,
+        &failures,
+        shooter.new_heads(),
+    )
+    .await?;
+
+    let submitted = Arc::new(SegQueue::new());
+    let attempted = Arc::new(SegQueue::new());
+    let load_profile = load_profile(&config.run)?;
+    let finalizing = Arc::new(PhaseTimer::default());
+    let inclusion = InclusionTracker::new(shooter);
+
+    let mut scenario = scenario!("Mixed").register_transaction(
+        Transaction::new(mixed_setup)
+            .set_name("Mixed Setup")
+            .set_on_start(),
+    );
+
+    for (name, weight, send) in sends {
+        let submitted = submitted.clone();
+        let inclusion = inclusion.clone();
+        let attempted = attempted.clone();
+        let checkpoint = checkpoint.clone();
+        let load_profile = load_profile.clone();
+        let from_account = from_account.clone();
+        let recipients = recipients.clone();
+        let transaction: TransactionFunction = Arc::new(move |user| {
+            // The nonces of the mints are taken in the order goose runs them, like in the erc721 benchmark
+            let nonce = match &send {
+                MixedSend::Mint(nonces) => Some(nonces.next()),
+                _ => None,
+            };
+            let send = send.clone();
+            let submitted = submitted.clone();
+            let inclusion = inclusion.clone();
+            let attempted = attempted.clone();
+            let checkpoint = checkpoint.clone();
+            let load_profile = load_profile.clone();
+            let from_account = from_account.clone();
+            let recipients = recipients.clone();
+            Box::pin(async move {
+                if interrupt::is_interrupted() {
+                    return Ok(());
+                }
+
+                if let Some(load_profile) = load_profile {
+                    load_profile.wait().await;
+                }
+
+                attempted.push(SystemTime::now());
+                match send {
+                    MixedSend::Transfer => {
+                        transfer(
+                            user,
+                            erc20_address,
+                            calls_per_tx,
+                            &submitted,
+                            inclusion.as_deref(),
+                            None,
+                            None,
+                            None,
+                        )
+                        .await?
+                    }
+                    MixedSend::Mint(_) => {
+                        mint(
+                            user,
+                            erc721_address,
+                            calls_per_tx,
+                            nonce.expect("Mints should be given a nonce"),
+                            &from_account,
+                            &submitted,
+                            inclusion.as_deref(),
+                            recipients.as_deref(),
+                        )
+                        .await?
+                    }
+                    MixedSend::Call(call) => {
+                        let response = send_from_own_account(user, vec![call], None).await?;
+                        record_submission(
+                            user,
+                            response.transaction_hash,
+                            &submitted,
+                            inclusion.as_deref(),
+                        );
+                    }
+                }
+                checkpoint.record(MIXED);
+
+                Ok(())
+            })
+        });
+
+        scenario = scenario.register_transaction(
+            Transaction::new(transaction)
+                .set_name(name)
+                .set_weight(weight as usize)?
+                .set_sequence(1),
+        );
+    }
+
+    let mixed_wait: TransactionFunction = goose_user_wait_last_tx(
+        finalizing.clone(),
+        tx_timeout,
+        check_interval,
+        max_check_interval,
+    );
+    let verification = Arc::new(PhaseTimer::default());
+    let reverted = Arc::new(AtomicU64::new(0));
+
+    let metrics = GooseAttack::initialize_with_config(goose_config.clone())?
+        .register_scenario(
+            scenario
+                .register_transaction(
+                    Transaction::new(mixed_wait)
+                        .set_name("Mixed Finalizing")
+                        .set_sequence(2)
+                        .set_on_stop(),
+                )
+                .register_transaction(
+                    Transaction::new(goose_user_verify_transactions(
+                        verification.clone(),
+                        reverted.clone(),
+                    ))
+                    .set_name("Verification")
+                    .set_sequence(3)
+                    .set_on_stop(),
+                ),
+        )
+        .execute()
+        .await?;
+
+    Ok(GooseBenchOutput {
+        metrics,
+        submitted: drain_queue(&submitted),
+        attempted: drain_queue(&attempted),
+        finalizing: finalizing.timing(),
+        verification: verification.timing(),
+        reverted: reverted.load(Ordering::Relaxed),
+        failed_requests: failures.exchanges(),
+        duplicates: None,
+        signing_keys: None,
+        account_distribution: Vec::new(),
+        requested_rates: load_profile
+            .map(|load_profile| load_profile.requested_rates())
+            .unwrap_or_default(),
+        // Custom calls are sent alone, only the transfers and mints pack several calls
+        calls_per_tx: 1,
+        inclusion_times: InclusionTracker::times(inclusion).await,
+    })
+}
+
+/// Run `amount` deployments of the fresh accounts funded during setup,
+/// recording each accepted one in `checkpoint`
+///
+/// The deployments are sent in the order they were signed, a resumed run sends the
+/// last `amount` of them as the first ones were sent before the interruption
+pub async fn account_deployments(
+    shooter: &GatlingShooterSetup,
+    amount: u64,
+    checkpoint: &Arc<Checkpoint>,
+) -> color_eyre::Result<GooseBenchOutput> {
+    let config = shooter.config();
+    let environment = shooter.environment()?;
+
+    ensure!(
+        config.run.num_account_deployments >= config.run.concurrency,
+        "Too few account deployments for the amount of concurrency"
+    );
+    ensure!(
+        environment.benchmark_deployments.len() as u64 >= amount,
+        "Only {} accounts were funded for the deploy account benchmark but {amount} deployments are left, the setup state was saved with a lower run.num_account_deployments",
+        environment.benchmark_deployments.len()
+    );
+
+    // A resumed run can have less deployments left than there are users
+    let concurrency = config.run.concurrency.min(amount);
+    // div_euclid will truncate integers when not evenly divisable
+    let user_iterations = amount.div_euclid(concurrency);
+    // this will always be a multiple of concurrency, unlike amount
+    let total_transactions = user_iterations * concurrency;
+
+    // If these are not equal that means user_iterations was truncated
+    if total_transactions != amount {
+        log::warn!("Number of account deployments is not evenly divisble by concurrency, doing {total_transactions} deployments instead");
+    }
+
+    let goose_deploy_config = {
+        let mut default = goose_configuration(config, concurrency);
+        default.iterations = user_iterations as usize;
+        default
+    };
+
+    let deployments = Arc::new(ArrayQueue::new(total_transactions as usize));
+    let skipped = environment.benchmark_deployments.len() - amount as usize;
+
+    for deployment in environment
+        .benchmark_deployments
+        .iter()
+        .skip(skipped)
+        .take(total_transactions as usize)
+    {
+        deployments
+            .push(deployment.clone())
+            .expect("ArrayQueue has capacity for all deployments");
+    }
+
+    let failures = Arc::new(FailureCapture::new(config.report.capture_failures));
+    let tx_timeout = Duration::from_secs(config.run.tx_timeout_secs);
+    let check_interval = config.run.confirmation_interval();
+    let max_check_interval = config.run.max_confirmation_interval();
+    let deploy_setup: TransactionFunction = setup(
+        environment.accounts.clone(),
+        &environment.account_deployments,
+        concurrency,
+        config.run.share_accounts,
+        false,
+        user_iterations as usize,
+        &failures,
+        shooter.new_heads(),
+    )
+    .await?;
+
+    let submitted = Arc::new(SegQueue::new());
+    let attempted = Arc::new(SegQueue::new());
+    let load_profile = load_profile(&config.run)?;
+    let finalizing = Arc::new(PhaseTimer::default());
+    let inclusion = InclusionTracker::new(shooter);
+
+    let deploy: TransactionFunction = {
+        let submitted = submitted.clone();
+        let inclusion = inclusion.clone();
+        let attempted = attempted.clone();
+        let checkpoint = checkpoint.clone();
+        let load_profile = load_profile.clone();
+        Arc::new(move |user| {
+            let deployment = deployments
+                .pop()
+                .expect("Deployment ArrayQueue should have enough deployments for all users");
+            let submitted = submitted.clone();
+            let inclusion = inclusion.clone();
+            let attempted = attempted.clone();
+            let checkpoint = checkpoint.clone();
+            let load_profile = load_profile.clone();
+            Box::pin(async move {
+                if interrupt::is_interrupted() {
+                    return Ok(());
+                }
+
+                if let Some(load_profile) = load_profile {
+                    load_profile.wait().await;
+                }
+
+                attempted.push(SystemTime::now());
+                // A rejected deployment only fails this transaction, goose counts it and keeps going
+                let (response, _): (DeployAccountTransactionResult, _) =
+                    send_request(user, JsonRpcMethod::AddDeployAccountTransaction, deployment)
+                        .await?;
+                record_submission(
+                    user,
+                    response.transaction_hash,
+                    &submitted,
+                    inclusion.as_deref(),
+                );
+                checkpoint.record(ACCOUNT_DEPLOYMENTS);
+
+                Ok(())
+            })
+        })
+    };
+
+    let deploy_wait: TransactionFunction = goose_user_wait_last_tx(
+        finalizing.clone(),
+        tx_timeout,
+        check_interval,
+        max_check_interval,
+    );
+    let verification = Arc::new(PhaseTimer::default());
+    let reverted = Arc::new(AtomicU64::new(0));
+
+    let metrics = GooseAttack::initialize_with_config(goose_deploy_config.clone())?
+        .register_scenario(
+            scenario!("Deploying Accounts")
+                .register_transaction(
+                    Transaction::new(deploy_setup)
+                        .set_name("Deploy Setup")
+                        .set_on_start(),
+                )
+                .register_transaction(
+                    Transaction::new(deploy)
+                        .set_name("Deploying Accounts")
+                        .set_sequence(1),
+                )
+                .register_transaction(
+                    Transaction::new(deploy_wait)
+                        .set_name("Deploy Finalizing")
+                        .set_sequence(2)
                         .set_on_stop(),
+                )
+                .register_transaction(
+                    Transaction::new(goose_user_verify_transactions(
+                        verification.clone(),
+                        reverted.clone(),
+                    ))
+                    .set_name("Verification")
+                    .set_sequence(3)
+                    .set_on_stop(),
                 ),
         )
         .execute()
         .await?;
 
-    Ok(metrics)
+    Ok(GooseBenchOutput {
+        metrics,
+        submitted: drain_queue(&submitted),
+        attempted: drain_queue(&attempted),
+        finalizing: finalizing.timing(),
+        verification: verification.timing(),
+        reverted: reverted.load(Ordering::Relaxed),
+        failed_requests: failures.exchanges(),
+        duplicates: None,
+        signing_keys: None,
+        account_distribution: Vec::new(),
+        requested_rates: load_profile
+            .map(|load_profile| load_profile.requested_rates())
+            .unwrap_or_default(),
+        calls_per_tx: 1,
+        inclusion_times: InclusionTracker::times(inclusion).await,
+    })
+}
+
+/// Goose configuration of `users` users sending to the node, started linearly
+/// over `run.ramp_up_secs` or all at once without a ramp up
+fn goose_configuration(config: &GatlingConfig, users: u64) -> GooseConfiguration {
+    let mut default = GooseConfiguration::default();
+    default.host = config
+        .rpc
+        .url
+        .primary()
+        .expect("RPC urls are checked to not be empty")
+        .to_owned();
+    default.users = Some(users as usize);
+
+    if config.run.ramp_up_secs != 0 {
+        default.startup_time = format!("{}s", config.run.ramp_up_secs);
+    }
+
+    default
+}
+
+/// Pacing of the transactions by `run.load_profile` or `run.target_tps`, none when they are
+/// sent as fast as possible
+fn load_profile(run: &RunConfig) -> color_eyre::Result<Option<Arc<LoadProfile>>> {
+    let load_profile = match (&run.load_profile[..], run.target_tps) {
+        ([], None) => return Ok(None),
+        ([], Some(target_tps)) => LoadProfile::constant(target_tps)?,
+        (load_profile, None) => LoadProfile::new(load_profile)?,
+        (_, Some(_)) => bail!("run.target_tps and run.load_profile can't be both set"),
+    };
+
+    Ok(Some(Arc::new(load_profile)))
+}
+
+fn drain_queue<T>(queue: &SegQueue<T>) -> Vec<T> {
+    std::iter::from_fn(|| queue.pop()).collect()
+}
+
+#[derive(Debug, Clone)]
+struct GooseUserState {
+    account: StarknetAccount,
+    nonce: Arc<tokio::sync::Mutex<AccountNonce>>,
+    /// Account spending the approvals of the account, only in the benchmarks sending from it
+    spender: Option<(StarknetAccount, Arc<tokio::sync::Mutex<AccountNonce>>)>,
+    prev_tx: Vec<FieldElement>,
+    failures: Arc<FailureCapture>,
+    /// Subscription the transactions are confirmed on, only with `rpc.use_websocket`
+    new_heads: Option<NewHeads>,
+}
+
+/// Next nonce of an account, shared by the users sending from it
+///
+/// It stays locked while a transaction of the account is submitted, so the users
+/// sharing an account use its nonces in order instead of racing for them
+#[derive(Debug)]
+struct AccountNonce {
+    nonce: FieldElement,
+    /// Signed deployment of the account when it still has to be deployed
+    deployment: Option<BroadcastedDeployAccountTransaction>,
+}
+
+pub type RpcError = ProviderError;
+
+impl AccountNonce {
+    async fn fetch(
+        account: &StarknetAccount,
+        deployment: Option<BroadcastedDeployAccountTransaction>,
+    ) -> Result<Self, RpcError> {
+        // Counterfactual accounts are only deployed once, by the first benchmark using them
+        let (nonce, deployment) = match account.get_nonce().await {
+            Ok(nonce) => (nonce, None),
+            Err(ProviderError::StarknetError(StarknetError::ContractNotFound))
+                if deployment.is_some() =>
+            {
+                (FieldElement::ZERO, deployment)
+            }
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self { nonce, deployment })
+    }
+}
+
+/// Hand out the accounts to the `users`, each one gets its own account unless there are
+/// fewer accounts than users and `share_accounts` lets them take turns on the accounts
+///
+/// With `spenders` every user also gets the next account as its spender
+#[allow(clippy::too_many_arguments)]
+async fn setup(
+    accounts: Vec<StarknetAccount>,
+    deployments: &HashMap<FieldElement, BroadcastedDeployAccountTransaction>,
+    users: u64,
+    share_accounts: bool,
+    spenders: bool,
+    transactions_amount: usize,
+    failures: &Arc<FailureCapture>,
+    new_heads: Option<&NewHeads>,
+) -> color_eyre::Result<TransactionFunction> {
+    let users = users as usize;
+    ensure!(
+        users <= accounts.len() || (share_accounts && !accounts.is_empty()),
+        "{users} users can't each send from their own account out of {} accounts, \
+        raise run.accounts or set run.share_accounts",
+        accounts.len()
+    );
+
+    ensure!(
+        !spenders || accounts.len() >= 2,
+        "The accounts can't spend the approvals of another account out of {} accounts, \
+        raise run.accounts to at least 2",
+        accounts.len()
+    );
+
+    let taken = if spenders { users.max(2) } else { users };
+    let mut nonces = Vec::with_capacity(accounts.len().min(taken));
+    for account in accounts.into_iter().take(taken) {
+        let deployment = deployments.get(&account.address()).cloned();
+        let nonce = AccountNonce::fetch(&account, deployment).await?;
+        nonces.push((account, Arc::new(tokio::sync::Mutex::new(nonce))));
+    }
+
+    let queue = ArrayQueue::new(users);
+    for (index, (account, nonce)) in nonces.iter().enumerate().cycle().take(users) {
+        queue
+            .push(GooseUserState {
+                account: account.clone(),
+                nonce: nonce.clone(),
+                spender: spenders.then(|| nonces[(index + 1) % nonces.len()].clone()),
+                prev_tx: Vec::with_capacity(transactions_amount),
+                failures: failures.clone(),
+                new_heads: new_heads.cloned(),
+            })
+            .expect("Queue should have enough space for all users as it's length is the amount of users");
+    }
+    let queue = Arc::new(queue);
+
+    Ok(Arc::new(move |user| {
+        let queue = queue.clone();
+        let state = queue
+            .pop()
+            .expect("Not enough accounts were handed out for the amount of users");
+        user.set_session_data(state);
+
+        Box::pin(async move { Ok(()) })
+    }))
+}
+
+fn goose_user_wait_last_tx(
+    timer: Arc<PhaseTimer>,
+    tx_timeout: Duration,
+    check_interval: Duration,
+    max_check_interval: Duration,
+) -> TransactionFunction {
+    Arc::new(move |user| {
+        let tx = user
+            .get_session_data::<GooseUserState>()
+            .expect("Should be in a goose user with GooseUserState session data")
+            .prev_tx
+            .last()
+            .copied();
+        let timer = timer.clone();
+
+        Box::pin(async move {
+            let start = SystemTime::now();
+
+            // If all transactions failed, we can skip this step
+            if let Some(tx) = tx {
+                wait_for_tx(user, tx, tx_timeout, check_interval, max_check_interval).await?;
+            }
+
+            timer.record(start, usize::from(tx.is_some()));
+
+            Ok(())
+        })
+    })
+}
+
+fn goose_user_verify_transactions(
+    timer: Arc<PhaseTimer>,
+    reverted: Arc<AtomicU64>,
+) -> TransactionFunction {
+    Arc::new(move |user| {
+        let transactions = user
+            .get_session_data::<GooseUserState>()
+            .expect("Should be in a goose user with GooseUserState session data")
+            .prev_tx
+            .len();
+        let timer = timer.clone();
+        let reverted = reverted.clone();
+
+        Box::pin(async move {
+            let start = SystemTime::now();
+            let result = verify_transactions(user, &reverted).await;
+            timer.record(start, transactions);
+
+            result
+        })
+    })
+}
+
+/// Wall-clock span of a phase every user goes through, from the first user
+/// entering it to the last one leaving it
+#[derive(Default)]
+struct PhaseTimer {
+    spans: SegQueue<(SystemTime, SystemTime, usize)>,
+}
+
+impl PhaseTimer {
+    fn record(&self, start: SystemTime, transactions: usize) {
+        self.spans.push((start, SystemTime::now(), transactions));
+    }
+
+    fn timing(&self) -> Option<PhaseTiming> {
+        let spans = drain_queue(&self.spans);
+        let start = spans.iter().map(|(start, _, _)| *start).min()?;
+        let end = spans.iter().map(|(_, end, _)| *end).max()?;
+
+        Some(PhaseTiming {
+            duration: end.duration_since(start).unwrap_or_default(),
+            transactions: spans.iter().map(|(_, _, transactions)| transactions).sum(),
+        })
+    }
+}
+
+// Hex: 0xdead
+// from_hex_be isn't const whereas from_mont is
+const VOID_ADDRESS: FieldElement = FieldElement::from_mont([
+    18446744073707727457,
+    18446744073709551615,
+    18446744073709551615,
+    576460752272412784,
+]);
+
+/// Transfer of a token to the void address, sent by the erc20 benchmark
+pub fn erc20_transfer_call(erc20_address: FieldElement) -> Call {
+    let (amount_low, amount_high) = (felt!("1"), felt!("0"));
+
+    Call {
+        to: erc20_address,
+        selector: selector!("transfer"),
+        calldata: vec![VOID_ADDRESS, amount_low, amount_high],
+    }
+}
+
+/// Mint of a token with a random id to `recipient`, sent by the erc721 benchmark
+pub fn erc721_mint_call(erc721_address: FieldElement, recipient: FieldElement) -> Call {
+    let (token_id_low, token_id_high) = (get_rng(), felt!("0x0000"));
+
+    Call {
+        to: erc721_address,
+        selector: selector!("mint"),
+        calldata: vec![recipient, token_id_low, token_id_high],
+    }
+}
+
+/// Approval of `spender` by the sender, sent by the erc20 approvals benchmark
+///
+/// The allowance is above 2^128 so that the `transferFrom`s never exhaust it whatever
+/// order they land in, its random low part still changes it at every approval
+pub fn erc20_approve_call(erc20_address: FieldElement, spender: FieldElement) -> Call {
+    let (amount_low, amount_high) = (get_rng(), felt!("1"));
+
+    Call {
+        to: erc20_address,
+        selector: selector!("approve"),
+        calldata: vec![spender, amount_low, amount_high],
+    }
+}
+
+/// `transferFrom` of a token of `owner` to the void address, spending its approval
+/// of the sender
+pub fn erc20_transfer_from_call(erc20_address: FieldElement, owner: FieldElement) -> Call {
+    let (amount_low, amount_high) = (felt!("1"), felt!("0"));
+
+    Call {
+        to: erc20_address,
+        selector: selector!("transferFrom"),
+        calldata: vec![owner, VOID_ADDRESS, amount_low, amount_high],
+    }
+}
+
+/// Send `calls_per_tx` erc20 transfers in a single transaction
+#[allow(clippy::too_many_arguments)]
+async fn transfer(
+    user: &mut GooseUser,
+    erc20_address: FieldElement,
+    calls_per_tx: u64,
+    submitted: &SegQueue<SubmittedTransaction>,
+    inclusion: Option<&InclusionTracker>,
+    weighted_accounts: Option<&WeightedAccounts>,
+    duplicates: Option<&DuplicateInjection>,
+    key_rotation: Option<&KeyRotation>,
+) -> TransactionResult {
+    let calls = (0..calls_per_tx)
+        .map(|_| erc20_transfer_call(erc20_address))
+        .collect();
+
+    let response = match (weighted_accounts, key_rotation) {
+        (Some(weighted_accounts), _) => weighted_accounts.send(user, calls, duplicates).await?,
+        (None, Some(key_rotation)) => key_rotation.send(user, calls, duplicates).await?,
+        (None, None) => send_from_own_account(user, calls, duplicates).await?,
+    };
+
+    record_submission(user, response.transaction_hash, submitted, inclusion);
+
+    Ok(())
 }
 
-#[derive(Debug, Clone)]
-struct GooseUserState {
-    account: StarknetAccount,
-    nonce: FieldElement,
-    prev_tx: Vec<FieldElement>,
+/// Send `calls` from the account of the user with its next nonce, waiting for the other
+/// users of the account to submit theirs first
+///
+/// A counterfactual account is deployed by its first transaction, the deployment is
+/// submitted right before it without waiting for it to be accepted
+async fn send_from_own_account(
+    user: &mut GooseUser,
+    calls: Vec<Call>,
+    duplicates: Option<&DuplicateInjection>,
+) -> Result<InvokeTransactionResult, Box<TransactionError>> {
+    let GooseUserState { account, nonce, .. } = user
+        .get_session_data::<GooseUserState>()
+        .expect("Should be in a goose user with GooseUserState session data");
+    let (account, nonce) = (account.clone(), nonce.clone());
+
+    send_with_nonce(user, &account, &nonce, calls, duplicates).await
 }
 
-pub type RpcError = ProviderError<JsonRpcClientError<HttpTransportError>>;
+/// Send `calls` from the spender of the user like [`send_from_own_account`] does
+async fn send_from_spender(
+    user: &mut GooseUser,
+    calls: Vec<Call>,
+) -> Result<InvokeTransactionResult, Box<TransactionError>> {
+    let (account, nonce) = user
+        .get_session_data::<GooseUserState>()
+        .expect("Should be in a goose user with GooseUserState session data")
+        .spender
+        .clone()
+        .expect("Should be in a benchmark handing out spenders");
 
-impl GooseUserState {
-    pub async fn new(
-        account: StarknetAccount,
-        transactions_amount: usize,
-    ) -> Result<Self, RpcError> {
-        Ok(Self {
-            nonce: account.get_nonce().await?,
-            account,
-            prev_tx: Vec::with_capacity(transactions_amount),
-        })
-    }
+    send_with_nonce(user, &account, &nonce, calls, None).await
 }
 
-async fn setup(
-    accounts: Vec<StarknetAccount>,
-    transactions_amount: usize,
-) -> Result<TransactionFunction, RpcError> {
-    let queue = ArrayQueue::new(accounts.len());
-    for account in accounts {
-        queue
-            .push(GooseUserState::new(account, transactions_amount).await?)
-            .expect("Queue should have enough space for all accounts as it's length is from the accounts vec");
+async fn send_with_nonce(
+    user: &mut GooseUser,
+    account: &StarknetAccount,
+    nonce: &tokio::sync::Mutex<AccountNonce>,
+    calls: Vec<Call>,
+    duplicates: Option<&DuplicateInjection>,
+) -> Result<InvokeTransactionResult, Box<TransactionError>> {
+    let mut nonce = nonce.lock().await;
+    if let Some(deployment) = nonce.deployment.clone() {
+        let _: (DeployAccountTransactionResult, _) =
+            send_request(user, JsonRpcMethod::AddDeployAccountTransaction, deployment).await?;
+
+        nonce.nonce = FieldElement::ONE;
+        nonce.deployment = None;
     }
-    let queue = Arc::new(queue);
 
-    Ok(Arc::new(move |user| {
-        let queue = queue.clone();
-        user.set_session_data(
-            queue
-                .pop()
-                .expect("Not enough accounts were created for the amount of users"),
-        );
+    let response = send_transfer(user, calls, nonce.nonce, account, duplicates).await?;
 
-        Box::pin(async { Ok(()) })
-    }))
+    nonce.nonce += FieldElement::ONE;
+
+    Ok(response)
 }
 
-fn goose_user_wait_last_tx() -> TransactionFunction {
-    Arc::new(move |user| {
-        let tx = user
-            .get_session_data::<GooseUserState>()
-            .expect("Should be in a goose user with GooseUserState session data")
-            .prev_tx
-            .last()
-            .copied();
+/// Keep a transaction accepted by the node for the user to wait for and verify it
+fn record_submission(
+    user: &mut GooseUser,
+    tx_hash: FieldElement,
+    submitted: &SegQueue<SubmittedTransaction>,
+    inclusion: Option<&InclusionTracker>,
+) {
+    user.get_session_data_mut::<GooseUserState>()
+        .expect(
+            "Should be successful as we already asserted that the session data is a GooseUserState",
+        )
+        .prev_tx
+        .push(tx_hash);
+    submitted.push(SubmittedTransaction {
+        hash: tx_hash,
+        submitted_at: SystemTime::now(),
+    });
 
-        Box::pin(async move {
-            // If all transactions failed, we can skip this step
-            if let Some(tx) = tx {
-                wait_for_tx(user, tx).await?;
-            }
+    if let Some(inclusion) = inclusion {
+        inclusion.track(tx_hash);
+    }
+}
 
-            Ok(())
+/// Times every submitted transaction until its receipt is found, each one being polled
+/// from its own task so that the users keep sending while their transactions are pending
+///
+/// The receipts are polled every `run.confirmation_interval_ms`, which the times are precise to
+struct InclusionTracker {
+    rpc: Arc<StarknetRpc>,
+    tx_timeout: Duration,
+    check_interval: Duration,
+    max_retries: u32,
+    waits: SegQueue<tokio::task::JoinHandle<Option<Duration>>>,
+}
+
+impl InclusionTracker {
+    /// Tracker of a benchmark, `None` unless `report.inclusion_times` is set
+    fn new(shooter: &GatlingShooterSetup) -> Option<Arc<Self>> {
+        let config = shooter.config();
+
+        config.report.inclusion_times.then(|| {
+            Arc::new(Self {
+                rpc: shooter.rpc_client().clone(),
+                tx_timeout: Duration::from_secs(config.run.tx_timeout_secs),
+                check_interval: config.run.confirmation_interval(),
+                max_retries: config.rpc.max_retries,
+                waits: SegQueue::new(),
+            })
         })
-    })
+    }
+
+    fn track(&self, tx_hash: FieldElement) {
+        let rpc = self.rpc.clone();
+        let (tx_timeout, check_interval, max_retries) =
+            (self.tx_timeout, self.check_interval, self.max_retries);
+        let submitted_at = Instant::now();
+
+        self.waits.push(tokio::spawn(async move {
+            let result = utils::wait_for_tx(
+                &rpc,
+                tx_hash,
+                check_interval,
+                check_interval,
+                tx_timeout,
+                max_retries,
+            )
+            .await;
+
+            match result {
+                Ok(()) => Some(submitted_at.elapsed()),
+                // Verification reports the failed transactions, they are only left out here
+                Err(err) => {
+                    log::debug!("Could not time the inclusion of {tx_hash:#064x}: {err}");
+                    None
+                }
+            }
+        }));
+    }
+
+    /// Inclusion times of the accepted transactions once all of them are waited for,
+    /// empty without a tracker
+    async fn times(tracker: Option<Arc<Self>>) -> Vec<Duration> {
+        let Some(tracker) = tracker else {
+            return Vec::new();
+        };
+
+        let mut times = Vec::new();
+        for wait in drain_queue(&tracker.waits) {
+            if let Ok(Some(time)) = wait.await {
+                times.push(time);
+            }
+        }
+
+        times
+    }
 }
 
-// Hex: 0xdead
-// from_hex_be isn't const whereas from_mont is
-const VOID_ADDRESS: FieldElement = FieldElement::from_mont([
-    18446744073707727457,
-    18446744073709551615,
-    18446744073709551615,
-    576460752272412784,
-]);
+/// Accounts shared by all the users, every transfer is sent from one of them
+/// picked following their weights
+///
+/// The nonce of an account stays locked while a transaction of it is submitted,
+/// so the users picking the same account are serialized like on a real hot account
+struct WeightedAccounts {
+    accounts: Vec<(StarknetAccount, tokio::sync::Mutex<FieldElement>)>,
+    weights: WeightedIndex<f64>,
+    rng: std::sync::Mutex<StdRng>,
+    sent: Vec<AtomicU64>,
+}
 
-async fn transfer(user: &mut GooseUser, erc20_address: FieldElement) -> TransactionResult {
-    let GooseUserState { account, nonce, .. } = user
-        .get_session_data::<GooseUserState>()
-        .expect("Should be in a goose user with GooseUserState session data");
+impl WeightedAccounts {
+    async fn zipf(
+        accounts: &[StarknetAccount],
+        exponent: f64,
+        seed: u64,
+    ) -> color_eyre::Result<Self> {
+        let weights = (1..=accounts.len()).map(|rank| 1.0 / (rank as f64).powf(exponent));
+        let weights = WeightedIndex::new(weights)
+            .map_err(|err| eyre!("Invalid Zipf exponent {exponent}: {err}"))?;
+
+        let mut nonces = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            nonces.push((
+                account.clone(),
+                tokio::sync::Mutex::new(account.get_nonce().await?),
+            ));
+        }
 
-    let (amount_low, amount_high) = (felt!("1"), felt!("0"));
+        Ok(Self {
+            accounts: nonces,
+            weights,
+            rng: std::sync::Mutex::new(StdRng::seed_from_u64(seed)),
+            sent: accounts.iter().map(|_| AtomicU64::new(0)).collect(),
+        })
+    }
 
-    let call = Call {
-        to: erc20_address,
-        selector: selector!("transfer"),
-        calldata: vec![VOID_ADDRESS, amount_low, amount_high],
-    };
+    async fn send(
+        &self,
+        user: &mut GooseUser,
+        calls: Vec<Call>,
+        duplicates: Option<&DuplicateInjection>,
+    ) -> Result<InvokeTransactionResult, Box<TransactionError>> {
+        let index = self
+            .weights
+            .sample(&mut *self.rng.lock().expect("Rng lock is poisoned"));
+        let (account, nonce) = &self.accounts[index];
 
-    let response: InvokeTransactionResult = send_execution(
-        user,
-        vec![call],
-        *nonce,
-        &account.clone(),
-        JsonRpcMethod::AddInvokeTransaction,
-    )
-    .await?
-    .0;
+        let mut nonce = nonce.lock().await;
+        let response = send_transfer(user, calls, *nonce, account, duplicates).await?;
 
-    let GooseUserState { nonce, prev_tx, .. } =
-        user.get_session_data_mut::<GooseUserState>().expect(
-            "Should be successful as we already asserted that the session data is a GooseUserState",
-        );
+        *nonce += FieldElement::ONE;
+        self.sent[index].fetch_add(1, Ordering::Relaxed);
 
-    *nonce += FieldElement::ONE;
+        Ok(response)
+    }
 
-    prev_tx.push(response.transaction_hash);
+    /// Transactions sent from every account, from the most to the least picked
+    fn distribution(&self) -> Vec<AccountShare> {
+        let total: u64 = self
+            .sent
+            .iter()
+            .map(|sent| sent.load(Ordering::Relaxed))
+            .sum();
+
+        let mut distribution: Vec<AccountShare> = self
+            .accounts
+            .iter()
+            .zip(&self.sent)
+            .map(|((account, _), sent)| {
+                let transactions = sent.load(Ordering::Relaxed);
+
+                AccountShare {
+                    address: account.address(),
+                    transactions,
+                    share: transactions as f64 / total.max(1) as f64,
+                }
+            })
+            .collect();
 
-    Ok(())
+        distribution.sort_unstable_by_key(|account| Reverse(account.transactions));
+        distribution
+    }
 }
 
+/// Send `calls_per_tx` erc721 mints to the account of the user in a single transaction,
+/// or each to a new address with `recipients`
+#[allow(clippy::too_many_arguments)]
 async fn mint(
     user: &mut GooseUser,
     erc721_address: FieldElement,
+    calls_per_tx: u64,
     nonce: FieldElement,
-    from_account: &SingleOwnerAccount<Arc<JsonRpcClient<HttpTransport>>, LocalWallet>,
+    from_account: &StarknetAccount,
+    submitted: &SegQueue<SubmittedTransaction>,
+    inclusion: Option<&InclusionTracker>,
+    recipients: Option<&UniqueRecipients>,
 ) -> TransactionResult {
     let recipient = user
         .get_session_data::<GooseUserState>()
@@ -311,17 +2060,18 @@ async fn mint(
         .clone()
         .address();
 
-    let (token_id_low, token_id_high) = (get_rng(), felt!("0x0000"));
+    let calls = (0..calls_per_tx)
+        .map(|_| {
+            let recipient = recipients.map_or(recipient, UniqueRecipients::next_recipient);
 
-    let call = Call {
-        to: erc721_address,
-        selector: selector!("mint"),
-        calldata: vec![recipient, token_id_low, token_id_high],
-    };
+            erc721_mint_call(erc721_address, recipient)
+        })
+        .collect::<Vec<_>>();
+    let recorded = recording::is_recording().then(|| calls.clone());
 
     let response: InvokeTransactionResult = send_execution(
         user,
-        vec![call],
+        calls,
         nonce,
         from_account,
         JsonRpcMethod::AddInvokeTransaction,
@@ -329,17 +2079,18 @@ async fn mint(
     .await?
     .0;
 
-    user.get_session_data_mut::<GooseUserState>()
-        .expect(
-            "Should be successful as we already asserted that the session data is a GooseUserState",
-        )
-        .prev_tx
-        .push(response.transaction_hash);
+    if let Some(calls) = recorded {
+        recording::record(RecordedSender::Deployer, &calls);
+    }
+
+    record_submission(user, response.transaction_hash, submitted, inclusion);
 
     Ok(())
 }
 
-async fn verify_transactions(user: &mut GooseUser) -> TransactionResult {
+/// Check the receipts of the transactions of the user, counting reverted ones in `reverted`
+/// without failing so that the remaining transactions still get verified
+async fn verify_transactions(user: &mut GooseUser, reverted: &AtomicU64) -> TransactionResult {
     let transactions = mem::take(
         &mut user
             .get_session_data_mut::<GooseUserState>()
@@ -355,9 +2106,8 @@ async fn verify_transactions(user: &mut GooseUser) -> TransactionResult {
             MaybePendingTransactionReceipt::Receipt(receipt) => match receipt.execution_result() {
                 ExecutionResult::Succeeded => {}
                 ExecutionResult::Reverted { reason } => {
-                    let tag = format!("Transaction {tx:#064x} has been rejected/reverted");
-
-                    return user.set_failure(&tag, &mut metrics, None, Some(reason));
+                    log::debug!("Transaction {tx:#064x} has been reverted: {reason}");
+                    reverted.fetch_add(1, Ordering::Relaxed);
                 }
             },
             MaybePendingTransactionReceipt::PendingReceipt(pending) => {
@@ -373,20 +2123,28 @@ async fn verify_transactions(user: &mut GooseUser) -> TransactionResult {
     Ok(())
 }
 
-const WAIT_FOR_TX_TIMEOUT: Duration = Duration::from_secs(60);
-
 pub async fn wait_for_tx(
     user: &mut GooseUser,
     tx_hash: FieldElement,
+    timeout: Duration,
+    check_interval: Duration,
+    max_interval: Duration,
 ) -> Result<(), Box<TransactionError>> {
     let start = SystemTime::now();
+    let mut interval = check_interval;
+    let mut new_heads = user
+        .get_session_data::<GooseUserState>()
+        .and_then(|state| state.new_heads.as_ref())
+        .map(NewHeads::from_now);
 
     loop {
         let (receipt, mut metric) =
             raw_send_request(user, JsonRpcMethod::GetTransactionReceipt, tx_hash).await?;
+        let remaining = timeout.saturating_sub(start.elapsed().unwrap_or_default());
 
-        if start.elapsed().unwrap() >= WAIT_FOR_TX_TIMEOUT {
+        if start.elapsed().unwrap() >= timeout {
             let tag = format!("Timeout while waiting for transaction {tx_hash:#064x}");
+            log::warn!("{tag}");
             return user.set_failure(&tag, &mut metric, None, None);
         }
 
@@ -414,7 +2172,7 @@ pub async fn wait_for_tx(
                     return user.set_failure(&reverted_tag(), &mut metric, None, Some(reason));
                 }
                 log::debug!("Waiting for transaction {tx_hash:#064x} to be accepted");
-                tokio::time::sleep(CHECK_INTERVAL).await;
+                interval = next_check(&mut new_heads, interval, max_interval, remaining).await;
             }
             JsonRpcResponse::Error {
                 error:
@@ -425,10 +2183,10 @@ pub async fn wait_for_tx(
                 ..
             } => {
                 log::debug!("Waiting for transaction {tx_hash:#064x} to show up");
-                tokio::time::sleep(CHECK_INTERVAL).await;
+                interval = next_check(&mut new_heads, interval, max_interval, remaining).await;
             }
             JsonRpcResponse::Error {
-                error: JsonRpcError { code, message },
+                error: JsonRpcError { code, message, .. },
                 ..
             } => {
                 let tag = format!("Error Code {code} while waiting for tx {tx_hash:#064x}");
@@ -439,46 +2197,331 @@ pub async fn wait_for_tx(
     }
 }
 
+/// Wait for the next block before looking up a receipt again when subscribed to them,
+/// backing off from `interval` otherwise or once the subscription dropped
+async fn next_check(
+    new_heads: &mut Option<NewHeads>,
+    interval: Duration,
+    max_interval: Duration,
+    remaining: Duration,
+) -> Duration {
+    if let Some(heads) = new_heads {
+        if heads.next_block(remaining).await {
+            return interval;
+        }
+
+        log::warn!("Block subscription dropped, polling for the transactions instead");
+        *new_heads = None;
+    }
+
+    backoff(interval, max_interval).await
+}
+
 /// Sends a execution request via goose, returning the successful json rpc response
 pub async fn send_execution<T: DeserializeOwned>(
     user: &mut GooseUser,
     calls: Vec<Call>,
     nonce: FieldElement,
-    from_account: &SingleOwnerAccount<Arc<JsonRpcClient<HttpTransport>>, LocalWallet>,
+    from_account: &StarknetAccount,
     method: JsonRpcMethod,
 ) -> Result<(T, GooseRequestMetric), Box<TransactionError>> {
-    let calldata = from_account.encode_calls(&calls);
+    let param = sign_execution(calls, nonce, from_account)
+        .await
+        .map_err(|err| signing_failure(user, &err))?;
 
-    #[allow(dead_code)] // Removes warning for unused fields, we need them to properly transmute
-    struct FakeRawExecution {
-        calls: Vec<Call>,
-        nonce: FieldElement,
-        max_fee: FieldElement,
+    send_request(user, method, param).await
+}
+
+/// Send an erc20 transfer, resubmitting it as is afterwards when `duplicates` picks it
+async fn send_transfer(
+    user: &mut GooseUser,
+    calls: Vec<Call>,
+    nonce: FieldElement,
+    from_account: &StarknetAccount,
+    duplicates: Option<&DuplicateInjection>,
+) -> Result<InvokeTransactionResult, Box<TransactionError>> {
+    let recorded = recording::is_recording().then(|| calls.clone());
+    let param = sign_execution(calls, nonce, from_account)
+        .await
+        .map_err(|err| signing_failure(user, &err))?;
+
+    let (response, _): (InvokeTransactionResult, _) =
+        send_request(user, JsonRpcMethod::AddInvokeTransaction, param.clone()).await?;
+
+    if let Some(calls) = recorded {
+        recording::record(RecordedSender::Account, &calls);
     }
 
-    let raw_exec = FakeRawExecution {
-        calls,
-        nonce,
-        max_fee: MAX_FEE,
-    };
+    if let Some(duplicates) = duplicates {
+        duplicates
+            .maybe_resubmit(user, param, response.transaction_hash)
+            .await?;
+    }
 
-    // TODO: We cannot right now construct RawExecution directly and need to use this hack
-    // see https://github.com/xJonathanLEI/starknet-rs/issues/538
-    let raw_exec = unsafe { mem::transmute::<FakeRawExecution, RawExecution>(raw_exec) };
+    Ok(response)
+}
 
-    let param = starknet::core::types::BroadcastedInvokeTransaction {
-        sender_address: from_account.address(),
-        calldata,
-        max_fee: MAX_FEE,
-        signature: from_account
-            .sign_execution(&raw_exec)
+/// Sign with the signer of `from_account`, as an invoke of the version of `run.tx_version`
+pub async fn sign_execution(
+    calls: Vec<Call>,
+    nonce: FieldElement,
+    from_account: &StarknetAccount,
+) -> color_eyre::Result<BroadcastedInvokeTransaction> {
+    if let InvokeVersion::V3 {
+        resource_bounds,
+        tip,
+    } = from_account.invoke_version()
+    {
+        let calldata = from_account.encode_calls(&calls);
+        let transaction_hash = utils::invoke_v3_transaction_hash(
+            from_account.address(),
+            from_account.chain_id(),
+            nonce,
+            &calldata,
+            resource_bounds,
+            *tip,
+        );
+        let signature = from_account
+            .signer()
+            .sign_hash(&transaction_hash)
             .await
-            .expect("Raw Execution should be correctly constructed for signature"),
-        nonce,
-        is_query: false,
+            .map_err(|err| eyre!("Could not sign the transaction: {err}"))?;
+
+        return Ok(BroadcastedInvokeTransaction::V3(
+            BroadcastedInvokeTransactionV3 {
+                sender_address: from_account.address(),
+                calldata,
+                signature: vec![signature.r, signature.s],
+                nonce,
+                resource_bounds: resource_bounds.clone(),
+                tip: *tip,
+                paymaster_data: Vec::new(),
+                account_deployment_data: Vec::new(),
+                nonce_data_availability_mode: DataAvailabilityMode::L1,
+                fee_data_availability_mode: DataAvailabilityMode::L1,
+                is_query: false,
+            },
+        ));
+    }
+
+    from_account
+        .execute(calls)
+        .nonce(nonce)
+        .max_fee(MAX_FEE)
+        .prepared()
+        .map_err(|err| eyre!("Could not prepare the transaction: {err}"))?
+        .get_invoke_request(false)
+        .await
+        .map_err(|err| eyre!("Could not sign the transaction: {err}"))
+}
+
+/// Fail the transaction of a user that couldn't be signed, recording it as a failed
+/// request of its own as there is no request to the node to fail
+fn signing_failure(user: &mut GooseUser, err: &color_eyre::Report) -> Box<TransactionError> {
+    const TAG: &str = "Signing failed";
+
+    let mut metric = GooseRequestMetric {
+        elapsed: user.started.elapsed().as_millis() as u64,
+        scenario_index: 0,
+        scenario_name: String::new(),
+        transaction_index: String::new(),
+        transaction_name: String::new(),
+        raw: GooseRawRequest {
+            method: GooseMethod::Post,
+            url: user.base_url.to_string(),
+            headers: Vec::new(),
+            body: String::new(),
+        },
+        name: "Signing".to_owned(),
+        final_url: String::new(),
+        redirected: false,
+        response_time: 0,
+        status_code: 0,
+        success: false,
+        update: false,
+        user: user.weighted_users_index,
+        error: TAG.to_owned(),
+        coordinated_omission_elapsed: 0,
+        user_cadence: 0,
     };
 
-    send_request(user, method, param).await
+    // Already failed, so set_failure only logs it
+    if let Some(metrics_channel) = &user.metrics_channel {
+        let _ = metrics_channel.send(GooseMetric::Request(Box::new(metric.clone())));
+    }
+
+    user.set_failure(TAG, &mut metric, None, Some(&format!("{err:#}")))
+        .unwrap_err() // SAFETY: This always returns a error
+}
+
+/// Sends every transaction from the next of the accounts in turn, each one holding a key of
+/// its own, to stress signature verification with many distinct keys
+#[derive(Debug)]
+struct KeyRotation {
+    accounts: Vec<(StarknetAccount, tokio::sync::Mutex<FieldElement>)>,
+    sent: AtomicU64,
+}
+
+impl KeyRotation {
+    async fn new(accounts: &[StarknetAccount]) -> color_eyre::Result<Self> {
+        let mut nonces = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            nonces.push((
+                account.clone(),
+                tokio::sync::Mutex::new(account.get_nonce().await?),
+            ));
+        }
+
+        Ok(Self {
+            accounts: nonces,
+            sent: AtomicU64::new(0),
+        })
+    }
+
+    async fn send(
+        &self,
+        user: &mut GooseUser,
+        calls: Vec<Call>,
+        duplicates: Option<&DuplicateInjection>,
+    ) -> Result<InvokeTransactionResult, Box<TransactionError>> {
+        let index = self.sent.fetch_add(1, Ordering::Relaxed) as usize % self.accounts.len();
+        let (account, nonce) = &self.accounts[index];
+
+        let mut nonce = nonce.lock().await;
+        let response = send_transfer(user, calls, *nonce, account, duplicates).await?;
+
+        *nonce += FieldElement::ONE;
+
+        Ok(response)
+    }
+
+    /// Every account signs with its own key, so this is the amount of distinct keys used
+    fn used_keys(&self) -> u64 {
+        self.sent
+            .load(Ordering::Relaxed)
+            .min(self.accounts.len() as u64)
+    }
+}
+
+/// Derives a new address for every erc721 mint from the run seed, so that the mints
+/// spread their storage writes instead of all updating the balances of the same accounts
+#[derive(Debug)]
+struct UniqueRecipients {
+    seed: FieldElement,
+    derived: AtomicU64,
+}
+
+impl UniqueRecipients {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed: seed.into(),
+            derived: AtomicU64::new(0),
+        }
+    }
+
+    /// Address of a counterfactual contract, which is always a valid contract address
+    fn next_recipient(&self) -> FieldElement {
+        let index = self.derived.fetch_add(1, Ordering::Relaxed);
+
+        compute_contract_address(self.seed, FieldElement::ZERO, &[index.into()])
+    }
+}
+
+/// Nonces of the deployer handed out to its mints in the order goose runs them,
+/// as many as a benchmark running for a duration needs
+#[derive(Debug)]
+struct DeployerNonces {
+    first: FieldElement,
+    taken: AtomicU64,
+}
+
+impl DeployerNonces {
+    fn new(first: FieldElement) -> Self {
+        Self {
+            first,
+            taken: AtomicU64::new(0),
+        }
+    }
+
+    fn next(&self) -> FieldElement {
+        self.first + self.taken.fetch_add(1, Ordering::Relaxed).into()
+    }
+}
+
+/// Resubmits some of the transactions exactly as they were sent, so with the same hash,
+/// to benchmark how the node handles duplicates
+#[derive(Debug)]
+struct DuplicateInjection {
+    rate: f64,
+    rng: std::sync::Mutex<StdRng>,
+    accepted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl DuplicateInjection {
+    fn new(rate: f64, seed: u64) -> color_eyre::Result<Self> {
+        ensure!(
+            (0.0..=1.0).contains(&rate),
+            "Duplicate rate should be between 0 and 1, got {rate}"
+        );
+
+        Ok(Self {
+            rate,
+            rng: std::sync::Mutex::new(StdRng::seed_from_u64(seed)),
+            accepted: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        })
+    }
+
+    /// A rejected duplicate is the expected outcome, it doesn't fail the transaction
+    async fn maybe_resubmit(
+        &self,
+        user: &mut GooseUser,
+        param: BroadcastedInvokeTransaction,
+        transaction_hash: FieldElement,
+    ) -> Result<(), Box<TransactionError>> {
+        let resubmit = self
+            .rng
+            .lock()
+            .expect("Rng lock is poisoned")
+            .gen_bool(self.rate);
+
+        if !resubmit {
+            return Ok(());
+        }
+
+        let (response, _) = raw_send_request::<InvokeTransactionResult>(
+            user,
+            JsonRpcMethod::AddInvokeTransaction,
+            param,
+        )
+        .await?;
+
+        match response {
+            JsonRpcResponse::Success { result, .. } => {
+                if result.transaction_hash != transaction_hash {
+                    log::warn!(
+                        "Duplicate of {transaction_hash:#064x} was accepted as {:#064x}",
+                        result.transaction_hash
+                    );
+                }
+                self.accepted.fetch_add(1, Ordering::Relaxed);
+            }
+            JsonRpcResponse::Error { error, .. } => {
+                log::debug!("Duplicate of {transaction_hash:#064x} was rejected: {error}");
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn submissions(&self) -> DuplicateSubmissions {
+        DuplicateSubmissions {
+            accepted: self.accepted.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
 }
 
 /// Sends request via goose, returning the successful json rpc response
@@ -487,19 +2530,18 @@ pub async fn send_request<T: DeserializeOwned>(
     method: JsonRpcMethod,
     param: impl Serialize,
 ) -> Result<(T, GooseRequestMetric), Box<TransactionError>> {
-    let (body, mut metrics) = raw_send_request(user, method, param).await?;
+    let (body, mut metrics, exchange) = send_request_with_exchange(user, method, param).await?;
 
     match body {
         JsonRpcResponse::Success { result, .. } => Ok((result, metrics)),
         JsonRpcResponse::Error { error, .. } => {
-            // While this is not the actual body we cannot serialize `JsonRpcResponse`
-            // due to it not implementing `Serialize`, and if we were to get the text before
-            // serializing we couldn't map the serde_json error to `TransactionError`.
-            // It also provides the exact same info and this is just debug information
-            let error = error.to_string();
+            // The report breaks the failures down by the code in the tag
+            let tag = format!("RPC Response was Error {}", error.code);
+            let error = exchange.response.clone();
+            capture_failure(user, exchange);
 
             Err(user
-                .set_failure("RPC Response was Error", &mut metrics, None, Some(&error))
+                .set_failure(&tag, &mut metrics, None, Some(&error))
                 .unwrap_err()) // SAFETY: This always returns a error
         }
     }
@@ -511,6 +2553,18 @@ pub async fn raw_send_request<T: DeserializeOwned>(
     method: JsonRpcMethod,
     param: impl Serialize,
 ) -> Result<(JsonRpcResponse<T>, GooseRequestMetric), Box<TransactionError>> {
+    let (body, metrics, _) = send_request_with_exchange(user, method, param).await?;
+
+    Ok((body, metrics))
+}
+
+/// Sends request via goose, returning the deserialized response alongside
+/// the raw exchange in case the caller considers it a failure
+async fn send_request_with_exchange<T: DeserializeOwned>(
+    user: &mut GooseUser,
+    method: JsonRpcMethod,
+    param: impl Serialize,
+) -> Result<(JsonRpcResponse<T>, GooseRequestMetric, RawExchange), Box<TransactionError>> {
     // Copied from https://docs.rs/starknet-providers/0.9.0/src/starknet_providers/jsonrpc/transports/http.rs.html#21-27
     #[derive(Debug, Serialize)]
     struct JsonRpcRequest<T> {
@@ -528,13 +2582,104 @@ pub async fn raw_send_request<T: DeserializeOwned>(
     };
 
     let goose_response = user.post_json("/", &request).await?;
+    let mut metrics = goose_response.request;
 
-    let body = goose_response
+    let text = goose_response
         .response
         .map_err(TransactionError::Reqwest)?
-        .json::<JsonRpcResponse<T>>()
+        .text()
         .await
         .map_err(TransactionError::Reqwest)?;
 
-    Ok((body, goose_response.request))
+    let exchange = RawExchange {
+        request: serde_json::to_value(&request).unwrap_or_default(),
+        response: text,
+    };
+
+    match serde_json::from_str(&exchange.response) {
+        Ok(body) => Ok((body, metrics, exchange)),
+        Err(err) => {
+            let error = format!("{err}: {}", exchange.response);
+            capture_failure(user, exchange);
+
+            Err(user
+                .set_failure("RPC Response is invalid", &mut metrics, None, Some(&error))
+                .unwrap_err()) // SAFETY: This always returns a error
+        }
+    }
+}
+
+/// JSON-RPC request with the response body as received
+struct RawExchange {
+    request: serde_json::Value,
+    response: String,
+}
+
+fn capture_failure(user: &GooseUser, exchange: RawExchange) {
+    if let Some(state) = user.get_session_data::<GooseUserState>() {
+        state.failures.capture(exchange);
+    }
+}
+
+/// Keeps the raw exchanges of the first `limit` failed requests, with their signatures redacted
+#[derive(Debug)]
+struct FailureCapture {
+    limit: usize,
+    exchanges: std::sync::Mutex<Vec<RpcExchange>>,
+}
+
+impl FailureCapture {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            exchanges: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    fn capture(&self, exchange: RawExchange) {
+        let mut exchanges = self
+            .exchanges
+            .lock()
+            .expect("Failure capture lock is poisoned");
+
+        if exchanges.len() >= self.limit {
+            return;
+        }
+
+        let mut request = exchange.request;
+        // Bodies that are not JSON, like HTTP errors from a proxy, are kept as a string
+        let mut response = serde_json::from_str(&exchange.response)
+            .unwrap_or(serde_json::Value::String(exchange.response));
+
+        redact(&mut request);
+        redact(&mut response);
+
+        exchanges.push(RpcExchange { request, response });
+    }
+
+    fn exchanges(&self) -> Vec<RpcExchange> {
+        mem::take(
+            &mut self
+                .exchanges
+                .lock()
+                .expect("Failure capture lock is poisoned"),
+        )
+    }
+}
+
+/// Replace the signatures found in `value`, which could be replayed, by a placeholder
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if key == "signature" {
+                    *value = serde_json::Value::String("<redacted>".into());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
 }
@@ -0,0 +1,56 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use color_eyre::{eyre::Context, Result};
+
+/// Set by the first Ctrl-C, the benchmarks stop sending transactions once it is set
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set along with [`INTERRUPTED`] when the run went past `run.max_duration_secs`
+static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// Stop the benchmarks on the first Ctrl-C and exit on the second one
+///
+/// Goose installs its own handler with `ctrlc` when an attack starts, overwriting any handler
+/// installed before it and only cancelling the first attack of the process. Installing ours
+/// with `ctrlc` first makes goose keep it for every attack, which then end early on their own
+pub fn install() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            log::warn!("Caught another ctrl-c, exiting immediately");
+            std::process::exit(130);
+        }
+
+        log::warn!(
+            "Caught ctrl-c, stopping the benchmarks to write a partial report, \
+            press ctrl-c again to exit immediately"
+        );
+    })
+    .wrap_err("Could not install the ctrl-c handler")
+}
+
+/// Stop the benchmarks like a Ctrl-C does once `max_duration` has elapsed, unless the
+/// returned task is aborted before
+pub fn deadline(max_duration: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        tokio::time::sleep(max_duration).await;
+
+        TIMED_OUT.store(true, Ordering::Relaxed);
+        if !INTERRUPTED.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "The run went past {} seconds, stopping the benchmarks to write a partial report",
+                max_duration.as_secs()
+            );
+        }
+    })
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+pub fn is_timed_out() -> bool {
+    TIMED_OUT.load(Ordering::Relaxed)
+}
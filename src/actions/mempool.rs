@@ -0,0 +1,50 @@
+use std::{sync::Arc, time::Duration};
+
+use color_eyre::Result;
+use tokio::{sync::oneshot, task::JoinHandle, time::Instant};
+
+use crate::utils::{pending_tx_count, StarknetRpc};
+
+/// Counts the transactions of the pending block in the background until stopped,
+/// to see the pool of the node grow when it can't keep up with the load
+pub struct MempoolSampler {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<Vec<(f64, u64)>>,
+}
+
+impl MempoolSampler {
+    pub fn start(starknet_rpc: Arc<StarknetRpc>, interval: Duration) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+
+        let handle = tokio::spawn(async move {
+            let start = Instant::now();
+            let mut interval = tokio::time::interval(interval);
+            let mut samples = Vec::new();
+
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = interval.tick() => {}
+                }
+
+                let Some(pending) = pending_tx_count(&starknet_rpc).await else {
+                    continue;
+                };
+
+                samples.push((start.elapsed().as_secs_f64(), pending));
+            }
+
+            samples
+        });
+
+        Self { stop, handle }
+    }
+
+    /// Stop sampling and return the `(seconds since the start, pending transactions)` samples
+    pub async fn stop(self) -> Result<Vec<(f64, u64)>> {
+        // The sampler only stops by itself when it panicked, which `handle` reports
+        let _ = self.stop.send(());
+
+        Ok(self.handle.await?)
+    }
+}
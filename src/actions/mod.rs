@@ -1,101 +1,1262 @@
-use ::goose::metrics::GooseMetrics;
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use color_eyre::eyre::{ensure, eyre, Context};
 use futures::Future;
-use starknet::providers::{jsonrpc::HttpTransport, JsonRpcClient, Provider};
 
 use crate::{
-    config::GatlingConfig,
-    metrics::{BenchmarkReport, GlobalReport},
+    config::{GatlingConfig, ReportConfig, ReportFormat, TpsDefinition},
+    logging,
+    metrics::{
+        sent_transactions, BenchmarkReport, GlobalReport, Rounded, SubmittedTransaction,
+        REPORT_SCHEMA_VERSION,
+    },
+    utils::{block_number, get_blocks_with_txs_including_pending, memory_string, MemorySampler},
 };
 
-use self::shoot::GatlingShooterSetup;
+use self::{
+    checkpoint::{
+        Checkpoint, ACCOUNT_DEPLOYMENTS, ERC20_APPROVALS, ERC20_TRANSFERS, ERC721_MINTS, MIXED,
+        REPLAY,
+    },
+    events::{Event, EventEmitter},
+    goose::GooseBenchOutput,
+    mempool::MempoolSampler,
+    node_metrics::NodeMetricsSampler,
+    progress::Progress,
+    recording::RecordedInvoke,
+    shoot::GatlingShooterSetup,
+    stdout::ReportStdout,
+};
 
+mod checkpoint;
+mod compare;
+mod dry_run;
+mod events;
 mod goose;
+mod interrupt;
+mod mempool;
+mod node_metrics;
+mod profile;
+mod progress;
+mod recording;
 mod shoot;
+mod signer;
+mod stdout;
+
+/// Time the benchmarks get to stop gracefully once `run.max_duration_secs` has elapsed,
+/// before the run is cut short wherever it is
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Run the setup phase only and save the resulting environment to `state_path`
+pub async fn setup(config: GatlingConfig, state_path: &Path) -> color_eyre::Result<()> {
+    config.validate()?;
+    log_to_file(&config.report)?;
+    let events = EventEmitter::new(config.report.events.as_ref())?;
+
+    let result = async {
+        let mut shooter = GatlingShooterSetup::from_config(config).await?;
+        log::info!("Starting the setup");
+        shooter.setup().await?;
+        shooter.save_state(state_path)?;
+        log::info!(
+            "Setup complete, saved the state to {}",
+            state_path.display()
+        );
+
+        events.emit(Event::SetupComplete {
+            loaded_state: false,
+        });
+
+        Ok(())
+    }
+    .await;
+
+    events.emit_error(&result);
+    result
+}
+
+/// Print how the metrics of the report at `new_path` changed from the one at `old_path`,
+/// failing when any of them got worse by more than `threshold` percent
+pub fn compare(old_path: &Path, new_path: &Path, threshold: f64) -> color_eyre::Result<()> {
+    let deltas = compare::compare(old_path, new_path)?;
+
+    println!(
+        "{:<24} {:<28} {:>14} {:>14} {:>10}",
+        "Scenario", "Metric", "Old", "New", "Delta"
+    );
+
+    let mut regressions = 0;
+    for delta in &deltas {
+        if delta.is_regression(threshold) {
+            regressions += 1;
+            println!("{delta} REGRESSION");
+        } else {
+            println!("{delta}");
+        }
+    }
+
+    ensure!(
+        regressions == 0,
+        "{regressions} metrics regressed by more than {threshold}%"
+    );
+
+    Ok(())
+}
+
+/// Print the fees the run would cost without sending any transaction, the benchmarks being
+/// estimated against the environment saved by a previous setup at `state_path`
+///
+/// Without a state the setup is estimated instead, the benchmarks calling the contracts it deploys
+pub async fn dry_run(config: GatlingConfig, state_path: Option<&Path>) -> color_eyre::Result<()> {
+    config.validate()?;
+    let mut shooter = GatlingShooterSetup::from_config(config).await?;
+
+    let projections = match state_path {
+        Some(state_path) => {
+            shooter.load_state(state_path)?;
+            dry_run::estimate_fees(&shooter).await?
+        }
+        None => {
+            log::info!("No setup state given, estimating the setup only, run it to estimate the benchmarks");
+            dry_run::estimate_setup_fees(&shooter).await?
+        }
+    };
+
+    for projection in &projections {
+        println!("{projection}");
+
+        if projection.exceeds_max_fee() {
+            log::warn!(
+                "{} transactions cost more than the max fee they are sent with, the node would reject them",
+                projection.name
+            );
+        }
+    }
+
+    // The v3 invokes pay in fri and the rest in wei, each unit is totaled on its own
+    let mut total_fees = BTreeMap::from([("wei", 0)]);
+    for projection in &projections {
+        *total_fees.entry(projection.fees.fee_unit()).or_default() += projection.total_fee();
+    }
+    let total_fees: Vec<_> = total_fees
+        .into_iter()
+        .map(|(unit, total_fee)| format!("{total_fee} {unit}"))
+        .collect();
+    println!("Projected total: {}", total_fees.join(" and "));
+
+    Ok(())
+}
+
+/// Run the benchmarks, the setup phase is skipped when `state_path`
+/// points to the state saved by a previous setup
+///
+/// The progress is saved to `checkpoint_path` when provided, with `resume`
+/// the run continues from the progress it holds instead of starting over
+pub async fn shoot(
+    config: GatlingConfig,
+    state_path: Option<&Path>,
+    checkpoint_path: Option<&Path>,
+    resume: bool,
+) -> color_eyre::Result<()> {
+    start(config, state_path, checkpoint_path, resume, None).await
+}
 
-pub async fn shoot(config: GatlingConfig) -> color_eyre::Result<()> {
-    let run_erc20 = config.run.num_erc20_transfers != 0;
-    let run_erc721 = config.run.num_erc721_mints != 0;
-    let total_txs = config.run.num_erc20_transfers + config.run.num_erc721_mints;
-    let num_blocks = config.report.num_blocks;
+/// Send again the invokes recorded at `record_path` by a previous run with `report.record_file`,
+/// as a single `Replay` benchmark instead of the configured ones
+///
+/// The invokes are signed by the accounts of this run, set up or loaded from `state_path`,
+/// so only the contracts they call have to be the same
+pub async fn replay(
+    mut config: GatlingConfig,
+    record_path: &Path,
+    state_path: Option<&Path>,
+) -> color_eyre::Result<()> {
+    let invokes = recording::read(record_path)?;
+    ensure!(
+        !invokes.is_empty(),
+        "No invoke was recorded in {}",
+        record_path.display()
+    );
+    log::info!(
+        "Replaying the {} invokes recorded in {}",
+        invokes.len(),
+        record_path.display()
+    );
+
+    let run = &mut config.run;
+    run.num_erc20_transfers = 0;
+    run.duration_secs = None;
+    run.mixed = None;
+    run.num_erc721_mints = 0;
+    run.num_erc20_approvals = 0;
+    run.num_account_deployments = 0;
+    for custom_call in &mut run.custom_calls {
+        custom_call.count = 0;
+    }
+
+    start(config, state_path, None, false, Some(invokes)).await
+}
+
+/// Run the benchmarks like [`shoot`], then the `replay` ones if any
+async fn start(
+    config: GatlingConfig,
+    state_path: Option<&Path>,
+    checkpoint_path: Option<&Path>,
+    resume: bool,
+    replay: Option<Vec<RecordedInvoke>>,
+) -> color_eyre::Result<()> {
+    config.validate()?;
+    log_to_file(&config.report)?;
+    let mut report_stdout = report_stdout(&config.report)?;
+    let events = EventEmitter::new(config.report.events.as_ref())?;
+    interrupt::install()?;
+    let max_duration = config.run.max_duration_secs.map(Duration::from_secs);
+    let deadline = max_duration.map(interrupt::deadline);
+    let report_config = config.report.clone();
+    let partial_report = Mutex::new(None);
+
+    let running = run(
+        config,
+        state_path,
+        checkpoint_path,
+        resume,
+        &events,
+        &mut report_stdout,
+        replay,
+        &partial_report,
+    );
+    // The deadline stops the benchmarks gracefully, the timeout then cuts short what
+    // doesn't check it such as the setup, the block fetches and the confirmations
+    let result = match max_duration {
+        Some(max_duration) => {
+            let timed = tokio::time::timeout(max_duration + SHUTDOWN_GRACE_PERIOD, running).await;
+
+            match timed {
+                Ok(result) => result,
+                Err(_) => write_timed_out_report(
+                    partial_report.into_inner().unwrap_or_default(),
+                    &report_config,
+                    report_stdout,
+                ),
+            }
+        }
+        None => running.await,
+    };
+
+    if let Some(deadline) = deadline {
+        deadline.abort();
+    }
+
+    events.emit_error(&result);
+    result
+}
+
+/// Run the setup and the benchmarks then write the report, keeping `partial_report` up to
+/// date with the benchmarks that are over in case the run is cut short
+#[allow(clippy::too_many_arguments)]
+async fn run(
+    config: GatlingConfig,
+    state_path: Option<&Path>,
+    checkpoint_path: Option<&Path>,
+    resume: bool,
+    events: &EventEmitter,
+    report_stdout: &mut Option<ReportStdout>,
+    replay: Option<Vec<RecordedInvoke>>,
+    partial_report: &Mutex<Option<GlobalReport>>,
+) -> color_eyre::Result<()> {
+    let checkpoint = Arc::new(match checkpoint_path {
+        Some(checkpoint_path) if resume => Checkpoint::resume(checkpoint_path)?,
+        checkpoint_path => Checkpoint::new(checkpoint_path)?,
+    });
+
+    let erc20_transfers = remaining(&checkpoint, ERC20_TRANSFERS, config.run.num_erc20_transfers);
+    // A duration isn't resumed from the transactions of a previous run, only skipped once done
+    let duration = |scenario, workload| {
+        config
+            .run
+            .duration_of(scenario)
+            .filter(|_| !checkpoint.is_finished(workload))
+    };
+    let erc20_duration = duration("erc20", ERC20_TRANSFERS);
+    let erc721_duration = duration("erc721", ERC721_MINTS);
+    let mixed_duration = config
+        .run
+        .mixed
+        .as_ref()
+        .and_then(|_| duration("mixed", MIXED));
+    let erc721_mints = remaining(&checkpoint, ERC721_MINTS, config.run.num_erc721_mints);
+    let erc20_approvals = {
+        let amount = remaining(&checkpoint, ERC20_APPROVALS, config.run.num_erc20_approvals);
+
+        // A resumed run with less than an approval and its spending left has nothing to send
+        if amount < config.run.num_erc20_approvals && amount < 2 {
+            0
+        } else {
+            amount
+        }
+    };
+    let account_deployments = remaining(
+        &checkpoint,
+        ACCOUNT_DEPLOYMENTS,
+        config.run.num_account_deployments,
+    );
+    let custom_calls: Vec<u64> = config
+        .run
+        .custom_calls
+        .iter()
+        .enumerate()
+        .map(|(index, custom_call)| {
+            remaining(&checkpoint, &custom_call_workload(index), custom_call.count)
+        })
+        .collect();
+    let mixed = match &config.run.mixed {
+        Some(mixed) => {
+            let amount = remaining(&checkpoint, MIXED, mixed.num_transactions);
+
+            // A resumed mix with less than an iteration left has nothing to send
+            if amount < mixed.num_transactions && amount < mixed.iteration_transactions() {
+                0
+            } else {
+                amount
+            }
+        }
+        None => 0,
+    };
+    let total_txs = erc20_transfers
+        + erc721_mints
+        + erc20_approvals
+        + account_deployments
+        + custom_calls.iter().sum::<u64>()
+        + mixed
+        + replay.as_ref().map_or(0, |invokes| invokes.len() as u64);
+
+    events.emit(Event::RunStarted {
+        erc20_transfers,
+        erc721_mints,
+        erc20_approvals,
+        account_deployments,
+        custom_calls: custom_calls.iter().sum(),
+        mixed,
+        resumed: resume,
+    });
+
+    let memory = MemorySampler::start();
 
     let mut shooter = GatlingShooterSetup::from_config(config).await?;
-    shooter.setup().await?;
+    match state_path {
+        Some(state_path) => shooter.load_state(state_path)?,
+        None => {
+            log::info!("Starting the setup");
+            shooter.setup().await?;
+            log::info!("Setup complete");
+        }
+    }
+
+    events.emit(Event::SetupComplete {
+        loaded_state: state_path.is_some(),
+    });
 
     let mut global_report = GlobalReport {
+        schema_version: REPORT_SCHEMA_VERSION,
+        gatling_version: env!("CARGO_PKG_VERSION"),
+        incomplete: false,
+        timed_out: false,
         users: shooter.config().run.concurrency,
+        ramp_up_secs: shooter.config().run.ramp_up_secs,
+        counterfactual_accounts: !shooter.environment()?.account_deployments.is_empty(),
         all_bench_report: BenchmarkReport::new("".into(), total_txs as usize),
         benches: Vec::new(),
+        health_probes: Vec::new(),
+        node_metrics: Vec::new(),
+        setup: shooter.setup_timings(),
         extra: crate::utils::sysinfo_string(),
     };
+    snapshot(partial_report, &global_report);
+
+    let node_metrics = shooter
+        .config()
+        .report
+        .node_metrics
+        .as_ref()
+        .map(NodeMetricsSampler::start);
+
+    let warmup_duration = shooter.config().run.warmup_duration_secs;
+    if warmup_duration != 0 {
+        log::info!(
+            "Warming up with erc20 transfers for {warmup_duration} seconds, they are not measured"
+        );
+        goose::warmup(&shooter, Duration::from_secs(warmup_duration)).await?;
+        log::info!("Warmup is over, starting the measured benchmarks");
+    }
+
+    // Only the invokes of the measured benchmarks are recorded, not the warmup ones
+    if let Some(record_file) = &shooter.config().report.record_file {
+        recording::record_to(record_file)?;
+    }
+
+    let max_retries = shooter.config().rpc.max_retries;
+    let start_block = block_number(shooter.report_rpc_client(), max_retries).await?;
 
-    let start_block = shooter.rpc_client().block_number().await?;
+    let mut all_submitted = Vec::new();
+    let mut all_attempted = Vec::new();
 
-    if run_erc20 {
-        let report = make_report_over_bench(
-            goose::erc20(&shooter),
+    let mut healthy = true;
+    // Name of the last benchmark that ran, the node is probed before the next one
+    let mut previous_bench: Option<&str> = None;
+
+    if erc20_transfers == 0 && erc20_duration.is_none() {
+        log::info!("Skipping erc20 transfers")
+    } else if interrupt::is_interrupted() {
+        log::warn!("Interrupted, skipping erc20 transfers")
+    } else {
+        let (report, submitted, attempted) = make_report_over_bench(
+            goose::erc20(&shooter, erc20_transfers, &checkpoint),
             "Erc20 Transfers".into(),
-            shooter.rpc_client(),
-            num_blocks,
+            erc20_transfers,
+            &shooter,
+            events,
+            &checkpoint,
+            ERC20_TRANSFERS,
         )
         .await?;
+        finish_workload(&checkpoint, ERC20_TRANSFERS)?;
 
+        // The transfers of a duration are only counted once they are over
+        if erc20_duration.is_some() {
+            global_report.all_bench_report.amount += report.amount;
+        }
         global_report.benches.push(report);
-    } else {
-        log::info!("Skipping erc20 transfers")
+        snapshot(partial_report, &global_report);
+        all_submitted.extend(submitted);
+        all_attempted.extend(attempted);
+        previous_bench = Some("Erc20 Transfers");
     }
 
-    if run_erc721 {
-        let report = make_report_over_bench(
-            goose::erc721(&shooter),
+    if erc721_mints == 0 && erc721_duration.is_none() {
+        log::info!("Skipping erc721 mints")
+    } else if interrupt::is_interrupted() {
+        log::warn!("Interrupted, skipping erc721 mints")
+    } else if !probe_before(&shooter, &mut healthy, previous_bench, &mut global_report).await? {
+        log::error!("Node is unhealthy, skipping erc721 mints")
+    } else {
+        let (report, submitted, attempted) = make_report_over_bench(
+            goose::erc721(&shooter, erc721_mints, &checkpoint),
             "Erc721 Mints".into(),
-            shooter.rpc_client(),
-            num_blocks,
+            erc721_mints,
+            &shooter,
+            events,
+            &checkpoint,
+            ERC721_MINTS,
         )
         .await?;
+        finish_workload(&checkpoint, ERC721_MINTS)?;
 
+        // The mints of a duration are only counted once they are over
+        if erc721_duration.is_some() {
+            global_report.all_bench_report.amount += report.amount;
+        }
         global_report.benches.push(report);
+        snapshot(partial_report, &global_report);
+        all_submitted.extend(submitted);
+        all_attempted.extend(attempted);
+        previous_bench = Some("Erc721 Mints");
+    }
+
+    if erc20_approvals == 0 {
+        log::info!("Skipping erc20 approvals")
+    } else if interrupt::is_interrupted() {
+        log::warn!("Interrupted, skipping erc20 approvals")
+    } else if !probe_before(&shooter, &mut healthy, previous_bench, &mut global_report).await? {
+        log::error!("Node is unhealthy, skipping erc20 approvals")
     } else {
-        log::info!("Skipping erc721 mints")
+        let (report, submitted, attempted) = make_report_over_bench(
+            goose::erc20_approvals(&shooter, erc20_approvals, &checkpoint),
+            "Erc20 Approvals".into(),
+            erc20_approvals,
+            &shooter,
+            events,
+            &checkpoint,
+            ERC20_APPROVALS,
+        )
+        .await?;
+        finish_workload(&checkpoint, ERC20_APPROVALS)?;
+
+        global_report.benches.push(report);
+        snapshot(partial_report, &global_report);
+        all_submitted.extend(submitted);
+        all_attempted.extend(attempted);
+        previous_bench = Some("Erc20 Approvals");
     }
 
-    let end_block = shooter.rpc_client().block_number().await?;
+    if account_deployments == 0 {
+        log::info!("Skipping account deployments")
+    } else if interrupt::is_interrupted() {
+        log::warn!("Interrupted, skipping account deployments")
+    } else if !probe_before(&shooter, &mut healthy, previous_bench, &mut global_report).await? {
+        log::error!("Node is unhealthy, skipping account deployments")
+    } else {
+        let (report, submitted, attempted) = make_report_over_bench(
+            goose::account_deployments(&shooter, account_deployments, &checkpoint),
+            "Account Deployments".into(),
+            account_deployments,
+            &shooter,
+            events,
+            &checkpoint,
+            ACCOUNT_DEPLOYMENTS,
+        )
+        .await?;
+        finish_workload(&checkpoint, ACCOUNT_DEPLOYMENTS)?;
+
+        global_report.benches.push(report);
+        snapshot(partial_report, &global_report);
+        all_submitted.extend(submitted);
+        all_attempted.extend(attempted);
+        previous_bench = Some("Account Deployments");
+    }
+
+    for (index, (custom_call, amount)) in shooter
+        .config()
+        .run
+        .custom_calls
+        .iter()
+        .zip(custom_calls)
+        .enumerate()
+    {
+        let name = &custom_call.selector;
+
+        if amount == 0 {
+            log::info!("Skipping {name} calls");
+            continue;
+        }
+
+        if interrupt::is_interrupted() {
+            log::warn!("Interrupted, skipping {name} calls");
+            continue;
+        }
+
+        if !probe_before(&shooter, &mut healthy, previous_bench, &mut global_report).await? {
+            log::error!("Node is unhealthy, skipping {name} calls");
+            continue;
+        }
+
+        let workload = custom_call_workload(index);
+        let (report, submitted, attempted) = make_report_over_bench(
+            goose::custom_call(&shooter, custom_call, workload.clone(), amount, &checkpoint),
+            name.clone(),
+            amount,
+            &shooter,
+            events,
+            &checkpoint,
+            &workload,
+        )
+        .await?;
+        finish_workload(&checkpoint, &workload)?;
+
+        global_report.benches.push(report);
+        snapshot(partial_report, &global_report);
+        all_submitted.extend(submitted);
+        all_attempted.extend(attempted);
+        previous_bench = Some(name);
+    }
+
+    if mixed == 0 && mixed_duration.is_none() {
+        log::info!("Skipping the mixed workload")
+    } else if interrupt::is_interrupted() {
+        log::warn!("Interrupted, skipping the mixed workload")
+    } else if !probe_before(&shooter, &mut healthy, previous_bench, &mut global_report).await? {
+        log::error!("Node is unhealthy, skipping the mixed workload")
+    } else {
+        let (report, submitted, attempted) = make_report_over_bench(
+            goose::mixed(&shooter, mixed, &checkpoint),
+            "Mixed Workload".into(),
+            mixed,
+            &shooter,
+            events,
+            &checkpoint,
+            MIXED,
+        )
+        .await?;
+        finish_workload(&checkpoint, MIXED)?;
+
+        // The transactions of a duration are only counted once they are over
+        if mixed_duration.is_some() {
+            global_report.all_bench_report.amount += report.amount;
+        }
+        global_report.benches.push(report);
+        snapshot(partial_report, &global_report);
+        all_submitted.extend(submitted);
+        all_attempted.extend(attempted);
+        previous_bench = Some("Mixed Workload");
+    }
+
+    if let Some(invokes) = &replay {
+        if interrupt::is_interrupted() {
+            log::warn!("Interrupted, skipping the replay")
+        } else if !probe_before(&shooter, &mut healthy, previous_bench, &mut global_report).await? {
+            log::error!("Node is unhealthy, skipping the replay")
+        } else {
+            let amount = invokes.len() as u64;
+            let (report, submitted, attempted) = make_report_over_bench(
+                goose::replay(&shooter, invokes, &checkpoint),
+                "Replay".into(),
+                amount,
+                &shooter,
+                events,
+                &checkpoint,
+                REPLAY,
+            )
+            .await?;
+
+            global_report.benches.push(report);
+            snapshot(partial_report, &global_report);
+            all_submitted.extend(submitted);
+            all_attempted.extend(attempted);
+        }
+    }
+
+    let end_block = block_number(shooter.report_rpc_client(), max_retries).await?;
+
+    if let Some(node_metrics) = node_metrics {
+        global_report.node_metrics = node_metrics.stop().await?;
+    }
 
     global_report
         .all_bench_report
-        .with_block_range(shooter.rpc_client(), start_block, end_block)
+        .with_block_range(
+            shooter.report_rpc_client(),
+            max_retries,
+            start_block,
+            end_block,
+            &shooter.config().report,
+        )
         .await?;
+    global_report
+        .all_bench_report
+        .with_submission_rate(&all_submitted, &all_attempted);
+    global_report
+        .all_bench_report
+        .with_target_tps(shooter.config().run.target_tps);
 
-    let report_path = shooter
-        .config()
-        .report
-        .output_location
-        .with_extension("json");
+    let (peak_memory, final_memory) = memory.stop().await?;
+    global_report.extra = format!(
+        "{}\n{}",
+        global_report.extra,
+        memory_string(peak_memory, final_memory)
+    );
 
-    let writer = std::fs::File::create(report_path)?;
-    serde_json::to_writer_pretty(writer, &global_report)?;
+    global_report.incomplete = interrupt::is_interrupted();
+    global_report.timed_out = interrupt::is_timed_out();
+    let finished_at = SystemTime::now();
+    let report_path = write_report(
+        &global_report,
+        &shooter.config().report,
+        report_stdout.take(),
+        finished_at,
+    )?;
+
+    if let Some(history_file) = &shooter.config().report.history_file {
+        append_to_history(
+            &global_report,
+            history_file,
+            shooter.config().report.precision,
+            finished_at,
+        )?;
+    }
+
+    if let Some(influx_url) = &shooter.config().report.influx_url {
+        push_to_influx(
+            &global_report.to_influx(finished_at),
+            influx_url,
+            shooter.config().report.influx_token.as_deref(),
+        )
+        .await;
+    }
+
+    if let Some(pushgateway_url) = &shooter.config().report.pushgateway_url {
+        push_to_pushgateway(&global_report.to_prometheus(), pushgateway_url).await;
+    }
+
+    if let Some(post_hook) = &shooter.config().report.post_hook {
+        run_post_hook(post_hook, &report_path).await;
+    }
+
+    events.emit(Event::RunComplete {
+        report: &report_path,
+    });
+
+    ensure!(
+        !global_report.timed_out,
+        "The run went past run.max_duration_secs, wrote a partial report to {}",
+        report_path.display()
+    );
+    ensure!(
+        !global_report.incomplete,
+        "Interrupted the run, wrote a partial report to {}",
+        report_path.display()
+    );
+    ensure!(
+        healthy,
+        "Aborted the run as the node was unhealthy between benchmarks"
+    );
+
+    let run_config = &shooter.config().run;
+    if let Some(min_tps) = run_config.min_tps {
+        check_min_tps(
+            &global_report.all_bench_report,
+            min_tps,
+            run_config.min_tps_definition,
+        )?;
+    }
 
     Ok(())
 }
 
+/// Keep a copy of `report` to write in case the run is cut short
+fn snapshot(partial_report: &Mutex<Option<GlobalReport>>, report: &GlobalReport) {
+    if let Ok(mut partial_report) = partial_report.lock() {
+        *partial_report = Some(report.clone());
+    }
+}
+
+/// Write the report of the benchmarks that were over when the run was cut short
+/// by `run.max_duration_secs`, failing either way
+fn write_timed_out_report(
+    partial_report: Option<GlobalReport>,
+    config: &ReportConfig,
+    report_stdout: Option<ReportStdout>,
+) -> color_eyre::Result<()> {
+    log::error!(
+        "The run is still going {} seconds after run.max_duration_secs, cutting it short",
+        SHUTDOWN_GRACE_PERIOD.as_secs()
+    );
+
+    let Some(mut report) = partial_report else {
+        return Err(eyre!(
+            "The run went past run.max_duration_secs during the setup, no report was written"
+        ));
+    };
+    report.incomplete = true;
+    report.timed_out = true;
+
+    let report_path = write_report(&report, config, report_stdout, SystemTime::now())?;
+
+    Err(eyre!(
+        "The run went past run.max_duration_secs, wrote a partial report to {}",
+        report_path.display()
+    ))
+}
+
+/// Probe the health of the node after the benchmark `after` when `run.health_probe` is set,
+/// recording the probe in `report`
+async fn probe_health(
+    shooter: &GatlingShooterSetup,
+    after: &str,
+    report: &mut GlobalReport,
+) -> color_eyre::Result<bool> {
+    let Some(health_probe) = &shooter.config().run.health_probe else {
+        return Ok(true);
+    };
+
+    let probe = shooter
+        .health_probe(after.into(), Duration::from_secs(health_probe.timeout))
+        .await?;
+    let healthy = probe.is_healthy();
+    report.health_probes.push(probe);
+
+    Ok(healthy)
+}
+
+/// Whether the next benchmark should run, probing the node after `previous_bench` if any
+/// and keeping it unhealthy once a probe failed
+async fn probe_before(
+    shooter: &GatlingShooterSetup,
+    healthy: &mut bool,
+    previous_bench: Option<&str>,
+    report: &mut GlobalReport,
+) -> color_eyre::Result<bool> {
+    if let (true, Some(previous_bench)) = (*healthy, previous_bench) {
+        *healthy = probe_health(shooter, previous_bench, report).await?;
+    }
+
+    Ok(*healthy)
+}
+
+fn log_to_file(config: &ReportConfig) -> color_eyre::Result<()> {
+    match &config.log_file {
+        Some(log_file) => logging::log_to_file(log_file),
+        None => Ok(()),
+    }
+}
+
+/// Take stdout for the report when `report.output_location` is `-`, which only
+/// the JSON report can be written to
+fn report_stdout(config: &ReportConfig) -> color_eyre::Result<Option<ReportStdout>> {
+    if !stdout::is_stdout(&config.output_location) {
+        return Ok(None);
+    }
+
+    ensure!(
+        [ReportFormat::Csv, ReportFormat::Html, ReportFormat::Influx]
+            .into_iter()
+            .all(|format| !config.format.writes(format)),
+        "Only the json report can be written to stdout, set report.format to json"
+    );
+    ensure!(
+        !config.events.as_deref().is_some_and(stdout::is_stdout),
+        "report.output_location and report.events can't both be stdout"
+    );
+
+    ReportStdout::take().map(Some)
+}
+
+/// Write the report in the configured formats, returning the path of the main one,
+/// the JSON report when it is written
+///
+/// With `stdout` only the JSON report is written, to stdout
+fn write_report(
+    report: &GlobalReport,
+    config: &ReportConfig,
+    stdout: Option<ReportStdout>,
+    finished_at: SystemTime,
+) -> color_eyre::Result<PathBuf> {
+    let rounded = Rounded {
+        report,
+        precision: config.precision,
+    };
+
+    if let Some(mut stdout) = stdout {
+        serde_json::to_writer_pretty(&mut stdout, &rounded)?;
+        writeln!(stdout)?;
+
+        return Ok(stdout::STDOUT.into());
+    }
+
+    // Every format is written next to `output_location`, only its extension changes
+    if let Some(dir) = config.output_location.parent() {
+        std::fs::create_dir_all(dir)
+            .wrap_err_with(|| format!("Could not create the report directory {}", dir.display()))?;
+    }
+
+    let mut report_path = None;
+
+    if config.format.writes(ReportFormat::Json) {
+        let path = config.output_location.with_extension("json");
+        let writer = std::fs::File::create(&path)?;
+        serde_json::to_writer_pretty(writer, &rounded)?;
+
+        report_path.get_or_insert(path);
+    }
+
+    if config.format.writes(ReportFormat::Csv) {
+        let path = config.output_location.with_extension("csv");
+        std::fs::write(&path, report.to_csv())?;
+
+        report_path.get_or_insert(path);
+    }
+
+    if config.format.writes(ReportFormat::Html) {
+        let path = config.output_location.with_extension("html");
+        std::fs::write(&path, report.to_html())?;
+
+        report_path.get_or_insert(path);
+    }
+
+    if config.format.writes(ReportFormat::Influx) {
+        let path = config.output_location.with_extension("lp");
+        std::fs::write(&path, report.to_influx(finished_at))?;
+
+        report_path.get_or_insert(path);
+    }
+
+    report_path.ok_or(eyre!("report.format should have at least one format"))
+}
+
+/// Append the report to the JSON array of the past runs at `path`, with the Unix seconds
+/// it was `finished_at` as its `timestamp`, creating the array on the first run
+///
+/// The array is written to a temporary file renamed over `path`, so that a run stopped
+/// while writing can't lose the history
+fn append_to_history(
+    report: &GlobalReport,
+    path: &Path,
+    precision: Option<u32>,
+    finished_at: SystemTime,
+) -> color_eyre::Result<()> {
+    let mut history: Vec<serde_json::Value> = match std::fs::read(path) {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .wrap_err_with(|| format!("History file {} should be a JSON array", path.display()))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            return Err(err)
+                .wrap_err_with(|| format!("Could not read history file {}", path.display()))
+        }
+    };
+
+    let mut entry = serde_json::to_value(Rounded { report, precision })?;
+    entry["timestamp"] = finished_at
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or_default()
+        .into();
+    history.push(entry);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).wrap_err_with(|| {
+            format!("Could not create the history directory {}", dir.display())
+        })?;
+    }
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+
+    std::fs::write(&temporary, serde_json::to_vec_pretty(&history)?)
+        .wrap_err_with(|| format!("Could not write history file {}", temporary.display()))?;
+    std::fs::rename(&temporary, path)
+        .wrap_err_with(|| format!("Could not replace history file {}", path.display()))?;
+
+    log::info!(
+        "Appended the report to {}, which holds {} runs",
+        path.display(),
+        history.len()
+    );
+
+    Ok(())
+}
+
+/// Post the report as line protocol to an InfluxDB `write` endpoint
+///
+/// A failed push is logged but doesn't fail the run, like the post hook
+async fn push_to_influx(lines: &str, url: &str, token: Option<&str>) {
+    log::info!("Pushing the report to {url}");
+
+    let mut request = reqwest::Client::new().post(url).body(lines.to_owned());
+    if let Some(token) = token {
+        request = request.header(reqwest::header::AUTHORIZATION, format!("Token {token}"));
+    }
+
+    match request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+    {
+        Ok(_) => log::info!("Report pushed to {url}"),
+        Err(err) => log::warn!("Could not push the report to {url}: {err}"),
+    }
+}
+
+/// How many receipts to fetch at once for the fee metrics, `None` when they are disabled
+fn fee_batch_size(config: &ReportConfig) -> Option<usize> {
+    config.fee_metrics.then_some(config.receipt_batch_size)
+}
+
+/// Fail when the TPS measured over all benchmarks is below `min_tps`
+fn check_min_tps(
+    report: &BenchmarkReport,
+    min_tps: f64,
+    definition: TpsDefinition,
+) -> color_eyre::Result<()> {
+    let metric = match definition {
+        TpsDefinition::OnChain => "Average TPS",
+        TpsDefinition::Client => "Average Submission TPS",
+    };
+
+    let tps = report.metric(metric).ok_or(eyre!(
+        "Could not measure {metric} to check it against min_tps"
+    ))?;
+
+    ensure!(
+        tps >= min_tps,
+        "{metric} of {tps} transactions/second is below min_tps of {min_tps}"
+    );
+
+    log::info!("{metric} of {tps} transactions/second meets min_tps of {min_tps}");
+
+    Ok(())
+}
+
+/// Mark `workload` as finished in the checkpoint unless the run was interrupted during it,
+/// in which case resuming continues it from the transactions saved so far
+fn finish_workload(checkpoint: &Checkpoint, workload: &str) -> color_eyre::Result<()> {
+    if interrupt::is_interrupted() {
+        checkpoint.flush()
+    } else {
+        checkpoint.finish(workload)
+    }
+}
+
+/// Checkpoint workload of the custom call at `index` in `run.custom_calls`
+fn custom_call_workload(index: usize) -> String {
+    format!("custom_call_{index}")
+}
+
+/// Amount of transactions of `workload` left to do, finished workloads have none left
+fn remaining(checkpoint: &Checkpoint, workload: &str, amount: u64) -> u64 {
+    if checkpoint.is_finished(workload) {
+        return 0;
+    }
+
+    let completed = checkpoint.completed(workload);
+    if completed != 0 {
+        log::info!("Resuming {workload} after {completed} transactions");
+    }
+
+    amount.saturating_sub(completed)
+}
+
+/// Post the metrics as Prometheus text, replacing the ones of the same names pushed to `url` before
+///
+/// A failed push is logged but doesn't fail the run as the report is already written
+async fn push_to_pushgateway(text: &str, url: &str) {
+    log::info!("Pushing the metrics to {url}");
+
+    match reqwest::Client::new()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(text.to_owned())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+    {
+        Ok(_) => log::info!("Metrics pushed to {url}"),
+        Err(err) => log::warn!("Could not push the metrics to {url}: {err}"),
+    }
+}
+
+/// Run the post hook through the shell with the report path as its first positional argument,
+/// `{report}` is replaced by a quoted `"$1"` so that the path is never parsed by the shell
+///
+/// A failing hook is logged but doesn't fail the run as the report is already written
+async fn run_post_hook(post_hook: &str, report_path: &Path) {
+    let report_path = report_path.display().to_string();
+    let command = post_hook.replace("{report}", "\"$1\"");
+
+    log::info!("Running post hook `{command}` with `{report_path}`");
+
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .arg("gatling")
+        .arg(&report_path)
+        .status()
+        .await;
+
+    match status {
+        Ok(status) if status.success() => log::info!("Post hook exited with {status}"),
+        Ok(status) => log::warn!("Post hook failed, exited with {status}"),
+        Err(err) => log::warn!("Could not run post hook: {err}"),
+    }
+}
+
 async fn make_report_over_bench(
-    bench: impl Future<Output = color_eyre::Result<GooseMetrics>>,
+    bench: impl Future<Output = color_eyre::Result<GooseBenchOutput>>,
     name: String,
-    rpc_client: &JsonRpcClient<HttpTransport>,
-    num_blocks: u64,
-) -> color_eyre::Result<BenchmarkReport> {
-    let start_block = rpc_client.block_number().await?;
-    let goose_metrics = bench.await?;
-    let end_block = rpc_client.block_number().await?;
-
-    let mut report = BenchmarkReport::new(name, goose_metrics.scenarios[0].counter);
-    report
-        .with_block_range(rpc_client, start_block + 1, end_block)
-        .await?;
+    amount: u64,
+    shooter: &GatlingShooterSetup,
+    events: &EventEmitter,
+    checkpoint: &Arc<Checkpoint>,
+    workload: &str,
+) -> color_eyre::Result<(BenchmarkReport, Vec<SubmittedTransaction>, Vec<SystemTime>)> {
+    let rpc_client = shooter.report_rpc_client();
+    let report_config = &shooter.config().report;
+    let max_retries = shooter.config().rpc.max_retries;
 
-    if num_blocks != 0 {
-        report.with_last_x_blocks(rpc_client, num_blocks).await?;
+    events.emit(Event::BenchStarted {
+        name: &name,
+        amount,
+    });
+
+    logging::set_scenario(Some(&name));
+    let start_block = block_number(rpc_client, max_retries).await?;
+    log::info!("Starting {name} after block {start_block}");
+    let mempool = (report_config.mempool_interval_secs != 0).then(|| {
+        MempoolSampler::start(
+            shooter.rpc_client().clone(),
+            Duration::from_secs(report_config.mempool_interval_secs),
+        )
+    });
+    let progress = (report_config.progress_interval_secs != 0).then(|| {
+        Progress::start(
+            name.clone(),
+            amount,
+            Duration::from_secs(report_config.progress_interval_secs),
+            checkpoint.clone(),
+            workload.to_owned(),
+        )
+    });
+    let GooseBenchOutput {
+        metrics: goose_metrics,
+        submitted,
+        attempted,
+        finalizing,
+        verification,
+        reverted,
+        failed_requests,
+        duplicates,
+        signing_keys,
+        account_distribution,
+        requested_rates,
+        calls_per_tx,
+        inclusion_times,
+    } = bench.await?;
+    drop(progress);
+    let mempool_samples = match mempool {
+        Some(mempool) => mempool.stop().await?,
+        None => Vec::new(),
+    };
+
+    let cooldown_secs = shooter.config().run.cooldown_secs;
+    if cooldown_secs != 0 {
+        log::info!("Cooling down for at most {cooldown_secs} seconds");
+
+        let tx_hashes = submitted.iter().map(|transaction| transaction.hash);
+        if !shooter
+            .cooldown(tx_hashes, Duration::from_secs(cooldown_secs))
+            .await
+        {
+            log::warn!("Transactions were still pending after the cooldown, {name} may miss some");
+        }
+    } else if !interrupt::is_interrupted() {
+        // The users only waited for their last transaction, the others may still be pending
+        log::info!("Confirming the {} submitted transactions", submitted.len());
+
+        let tx_hashes = submitted.iter().map(|transaction| transaction.hash);
+        match shooter.wait_for_every_tx(tx_hashes).await {
+            Ok(errors) if errors.is_empty() => {}
+            Ok(errors) => log::warn!(
+                "{} of the submitted transactions could not be confirmed, the first with: {:#}",
+                errors.len(),
+                errors[0]
+            ),
+            Err(err) => log::warn!("Stopped confirming the submitted transactions: {err}"),
+        }
+    }
+
+    let end_block = block_number(rpc_client, max_retries).await?;
+    log::info!("{name} is over, measuring blocks {start_block} to {end_block}");
+    logging::set_scenario(None);
+
+    let blocks = if end_block > start_block || report_config.include_pending_block {
+        get_blocks_with_txs_including_pending(
+            rpc_client,
+            start_block + 1..=end_block,
+            max_retries,
+            report_config.max_concurrent_requests,
+            report_config.include_pending_block,
+        )
+        .await?
+    } else {
+        log::warn!("No block was produced during {name}, skipping its block metrics");
+        Vec::new()
+    };
+
+    // The iterations left when interrupted are counted by goose although they sent nothing
+    let amount = if interrupt::is_interrupted() {
+        attempted.len()
+    } else {
+        sent_transactions(&goose_metrics)?
+    };
+    let mut report = BenchmarkReport::new(name, amount);
+    report.with_blocks(&blocks);
+    report.mempool_samples = mempool_samples;
+    if let Some(breakdown) = report_config.uops_breakdown {
+        report.with_uops_breakdown(&blocks, breakdown);
+    }
+    report.with_inclusion_by_depth(&submitted, &blocks);
+
+    if let Some(batch_size) = fee_batch_size(report_config) {
+        report
+            .with_fees(
+                rpc_client,
+                max_retries,
+                &blocks,
+                batch_size,
+                &report_config.fee_buckets,
+            )
+            .await?;
     }
 
-    report.with_goose_metrics(&goose_metrics)?;
-    Ok(report)
+    if report_config.own_traffic_metrics {
+        report.with_own_traffic(&blocks, &shooter.benchmark_senders()?);
+    }
+
+    if report_config.fee_correlation {
+        report
+            .with_fee_correlation(
+                rpc_client,
+                max_retries,
+                &submitted,
+                &blocks,
+                report_config.receipt_batch_size,
+            )
+            .await?;
+    }
+
+    if report_config.dropped_sample_size != 0 {
+        report
+            .with_dropped_transactions(
+                rpc_client,
+                max_retries,
+                &submitted,
+                &blocks,
+                report_config.dropped_sample_size,
+                report_config.receipt_batch_size,
+            )
+            .await?;
+    }
+
+    if report_config.num_blocks != 0 {
+        report
+            .with_last_x_blocks(
+                rpc_client,
+                max_retries,
+                report_config.max_concurrent_requests,
+                report_config.num_blocks,
+                fee_batch_size(report_config),
+            )
+            .await?;
+    }
+
+    report.with_submission_rate(&submitted, &attempted);
+    report.with_target_tps(shooter.config().run.target_tps);
+    report.with_calls_per_tx(calls_per_tx);
+    report.with_inclusion_times(&inclusion_times);
+    report.with_load_profile(&requested_rates, &submitted);
+    report.with_phase_timings(finalizing, verification);
+    report.account_distribution = account_distribution;
+    report.failed_requests = failed_requests;
+
+    if let Some(duplicates) = duplicates {
+        report.with_duplicates(duplicates);
+    }
+
+    if let Some(signing_keys) = signing_keys {
+        report.with_signing_keys(signing_keys);
+    }
+    report.with_goose_metrics(&goose_metrics, reverted, &report_config.percentiles)?;
+
+    if let Some(rate) = report.metric("Confirmation Rate") {
+        if rate < report_config.min_confirmation_rate {
+            log::warn!(
+                "Only {:.1}% of the {} transactions of {} were confirmed, below report.min_confirmation_rate, its throughput is not to be trusted",
+                rate * 100.0,
+                report.amount,
+                report.name
+            );
+        }
+    }
+
+    events.emit(Event::BenchComplete {
+        name: &report.name,
+        transactions: report.amount,
+        average_tps: report.metric("Average TPS"),
+    });
+
+    Ok((report, submitted, attempted))
 }
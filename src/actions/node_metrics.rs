@@ -0,0 +1,115 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
+use color_eyre::Result;
+use tokio::{sync::oneshot, task::JoinHandle};
+
+use crate::{
+    config::NodeMetricsConfig,
+    metrics::{NodeMetricSample, NodeMetricSeries},
+};
+
+/// Scrapes the Prometheus endpoint of the node in the background
+/// until stopped, keeping only the configured series
+pub struct NodeMetricsSampler {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<BTreeMap<String, Vec<NodeMetricSample>>>,
+}
+
+impl NodeMetricsSampler {
+    pub fn start(config: &NodeMetricsConfig) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+        let config = config.clone();
+
+        let handle = tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            let start = SystemTime::now();
+            let mut interval = tokio::time::interval(Duration::from_secs(config.interval.max(1)));
+            let mut samples: BTreeMap<String, Vec<NodeMetricSample>> = BTreeMap::new();
+
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = interval.tick() => {}
+                }
+
+                let body = match scrape(&client, &config.url).await {
+                    Ok(body) => body,
+                    Err(err) => {
+                        log::warn!("Could not scrape node metrics from {}: {err:#}", config.url);
+                        continue;
+                    }
+                };
+
+                let time = start.elapsed().unwrap_or_default().as_secs_f64();
+
+                for (series, value) in parse_series(&body, &config.series) {
+                    samples
+                        .entry(series.to_owned())
+                        .or_default()
+                        .push(NodeMetricSample { time, value });
+                }
+            }
+
+            samples
+        });
+
+        Self { stop, handle }
+    }
+
+    /// Stop scraping and return the sampled series
+    pub async fn stop(self) -> Result<Vec<NodeMetricSeries>> {
+        // The sampler only stops by itself when it panicked, which `handle` reports
+        let _ = self.stop.send(());
+        let samples = self.handle.await?;
+
+        Ok(samples
+            .into_iter()
+            .map(|(name, samples)| NodeMetricSeries::new(name, samples))
+            .collect())
+    }
+}
+
+async fn scrape(client: &reqwest::Client, url: &str) -> Result<String> {
+    Ok(client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?)
+}
+
+/// Values of the series of the Prometheus text exposition format in `body`
+/// that are either selected by their metric name or by their full series
+fn parse_series<'a>(
+    body: &'a str,
+    selected: &'a [String],
+) -> impl Iterator<Item = (&'a str, f64)> + 'a {
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            // Labels can contain spaces, the series ends with the closing brace when it has some
+            let series_end = match line.find('{') {
+                Some(_) => line.rfind('}')? + 1,
+                None => line.find(char::is_whitespace)?,
+            };
+            let (series, rest) = line.split_at(series_end);
+            let name = series.split('{').next()?;
+
+            if !selected
+                .iter()
+                .any(|selected| selected == name || selected == series)
+            {
+                return None;
+            }
+
+            // A timestamp can follow the value
+            let value = rest.split_whitespace().next()?.parse().ok()?;
+
+            Some((series, value))
+        })
+}
@@ -0,0 +1,168 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::{Duration, SystemTime},
+};
+
+use color_eyre::{eyre::ensure, Result};
+
+use crate::{config::LoadSegment, metrics::RequestedRate};
+
+/// Paces transactions so that they are sent at the rate of a load profile,
+/// the profile starts with the first paced transaction
+///
+/// Every transaction gets the time at which it should be sent from the amount
+/// of transactions paced before it, so the users sharing the profile never
+/// have to coordinate beyond a counter
+pub struct LoadProfile {
+    segments: Vec<Segment>,
+    /// Rate kept once the segments are over
+    final_tps: f64,
+    paced: AtomicU64,
+    start: OnceLock<SystemTime>,
+}
+
+struct Segment {
+    /// Seconds from the start of the profile
+    offset: f64,
+    duration: f64,
+    from_tps: f64,
+    to_tps: f64,
+    /// Transactions sent by the previous segments
+    sent_before: f64,
+}
+
+impl Segment {
+    fn transactions(&self) -> f64 {
+        self.duration * (self.from_tps + self.to_tps) / 2.0
+    }
+
+    /// Seconds into the segment at which `sent` of its transactions are sent
+    fn time_of(&self, sent: f64) -> f64 {
+        let slope = (self.to_tps - self.from_tps) / self.duration;
+
+        if slope == 0.0 {
+            return sent / self.from_tps;
+        }
+
+        let discriminant = (self.from_tps.powi(2) + 2.0 * slope * sent).max(0.0);
+
+        (discriminant.sqrt() - self.from_tps) / slope
+    }
+}
+
+impl LoadProfile {
+    pub fn new(load_profile: &[LoadSegment]) -> Result<Self> {
+        ensure!(
+            load_profile.iter().all(|segment| segment.duration != 0
+                && segment.target_tps.is_finite()
+                && segment.target_tps >= 0.0),
+            "Load profile segments should last at least a second and target a non negative TPS"
+        );
+        ensure!(
+            load_profile
+                .last()
+                .is_some_and(|segment| segment.target_tps > 0.0),
+            "The last load profile segment should target more than 0 TPS"
+        );
+
+        let (mut offset, mut sent_before, mut previous_tps) = (0.0, 0.0, 0.0);
+        let mut segments = Vec::with_capacity(load_profile.len());
+
+        for segment in load_profile {
+            let segment = Segment {
+                offset,
+                duration: segment.duration as f64,
+                from_tps: if segment.ramp {
+                    previous_tps
+                } else {
+                    segment.target_tps
+                },
+                to_tps: segment.target_tps,
+                sent_before,
+            };
+
+            offset += segment.duration;
+            sent_before += segment.transactions();
+            previous_tps = segment.to_tps;
+            segments.push(segment);
+        }
+
+        Ok(Self {
+            segments,
+            final_tps: previous_tps,
+            paced: AtomicU64::new(0),
+            start: OnceLock::new(),
+        })
+    }
+
+    /// Profile holding `target_tps` from the start, it has no segments to report
+    pub fn constant(target_tps: u64) -> Result<Self> {
+        ensure!(target_tps != 0, "run.target_tps should be more than 0");
+
+        Ok(Self {
+            segments: Vec::new(),
+            final_tps: target_tps as f64,
+            paced: AtomicU64::new(0),
+            start: OnceLock::new(),
+        })
+    }
+
+    /// Wait until the next transaction should be sent
+    pub async fn wait(&self) {
+        let start = *self.start.get_or_init(SystemTime::now);
+        let sent = self.paced.fetch_add(1, Ordering::Relaxed) as f64;
+
+        let send_at = start + Duration::from_secs_f64(self.offset_of(sent));
+        let delay = send_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+
+        tokio::time::sleep(delay).await;
+    }
+
+    /// Seconds from the start of the profile at which `sent` transactions are sent,
+    /// past the end of the profile the target of the last segment is kept
+    fn offset_of(&self, sent: f64) -> f64 {
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| sent < segment.sent_before + segment.transactions());
+
+        match segment {
+            Some(segment) => segment.offset + segment.time_of(sent - segment.sent_before),
+            None => {
+                let (end, sent_before_end) = self
+                    .segments
+                    .last()
+                    .map(|last| {
+                        (
+                            last.offset + last.duration,
+                            last.sent_before + last.transactions(),
+                        )
+                    })
+                    .unwrap_or_default();
+
+                end + (sent - sent_before_end) / self.final_tps
+            }
+        }
+    }
+
+    /// Rates requested by the profile, empty when no transaction was paced
+    pub fn requested_rates(&self) -> Vec<RequestedRate> {
+        let Some(start) = self.start.get() else {
+            return Vec::new();
+        };
+
+        self.segments
+            .iter()
+            .map(|segment| RequestedRate {
+                start: *start + Duration::from_secs_f64(segment.offset),
+                duration: segment.duration,
+                tps: (segment.from_tps + segment.to_tps) / 2.0,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,53 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::task::JoinHandle;
+
+use super::checkpoint::Checkpoint;
+
+/// Logs how far a benchmark got every interval until dropped, so that long runs don't look hung
+pub struct Progress(JoinHandle<()>);
+
+impl Progress {
+    /// Log the transactions of `workload` accepted out of `amount`, with the TPS since the last log,
+    /// an `amount` of `0` being a benchmark bounded by its duration instead
+    pub fn start(
+        name: String,
+        amount: u64,
+        interval: Duration,
+        checkpoint: Arc<Checkpoint>,
+        workload: String,
+    ) -> Self {
+        Self(tokio::spawn(async move {
+            // A resumed workload already has the transactions of the previous runs
+            let resumed = checkpoint.completed(&workload);
+            let start = Instant::now();
+            let mut previous = 0;
+
+            loop {
+                tokio::time::sleep(interval).await;
+
+                let accepted = checkpoint.completed(&workload) - resumed;
+                let tps = (accepted - previous) as f64 / interval.as_secs_f64();
+                previous = accepted;
+
+                let accepted = match amount {
+                    0 => accepted.to_string(),
+                    amount => format!("{accepted}/{amount}"),
+                };
+                log::info!(
+                    "{name}: {accepted} transactions accepted, {tps:.1} TPS, {} seconds elapsed",
+                    start.elapsed().as_secs()
+                );
+            }
+        }))
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
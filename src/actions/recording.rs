@@ -0,0 +1,116 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use serde_derive::{Deserialize, Serialize};
+use starknet::{accounts::Call, core::types::FieldElement};
+
+/// File the submitted invokes are written to, only with `report.record_file`
+static RECORDING: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Account an invoke was sent from, the accounts themselves differ from one run to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedSender {
+    /// The benchmark account of the goose user that sent it
+    Account,
+    Deployer,
+}
+
+/// Invoke as it was submitted, without its nonce and signature so that it can be signed
+/// again by the accounts of another run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedInvoke {
+    pub sender: RecordedSender,
+    pub calls: Vec<RecordedCall>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub to: FieldElement,
+    pub selector: FieldElement,
+    pub calldata: Vec<FieldElement>,
+}
+
+impl RecordedInvoke {
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls
+            .iter()
+            .map(|call| Call {
+                to: call.to,
+                selector: call.selector,
+                calldata: call.calldata.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Write every invoke submitted from now on to `path`, one JSON object per line,
+/// replacing the invokes of a previous run
+pub fn record_to(path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .wrap_err_with(|| format!("Could not create record file {}", path.display()))?;
+
+    RECORDING
+        .set(Mutex::new(file))
+        .map_err(|_| eyre!("The record file can only be set once"))
+}
+
+pub fn is_recording() -> bool {
+    RECORDING.get().is_some()
+}
+
+/// Record an invoke accepted by the node, a failed write is only logged so that it doesn't
+/// fail the transaction
+pub fn record(sender: RecordedSender, calls: &[Call]) {
+    let Some(file) = RECORDING.get() else {
+        return;
+    };
+
+    let invoke = RecordedInvoke {
+        sender,
+        calls: calls
+            .iter()
+            .map(|call| RecordedCall {
+                to: call.to,
+                selector: call.selector,
+                calldata: call.calldata.clone(),
+            })
+            .collect(),
+    };
+
+    let result = serde_json::to_string(&invoke)
+        .map_err(std::io::Error::from)
+        .and_then(|line| match file.lock() {
+            Ok(mut file) => writeln!(file, "{line}"),
+            Err(_) => Err(std::io::Error::other("Record file lock is poisoned")),
+        });
+
+    if let Err(err) = result {
+        log::warn!("Could not record an invoke: {err}");
+    }
+}
+
+/// Invokes written to `path` by [`record`], in the order they were submitted
+pub fn read(path: &Path) -> Result<Vec<RecordedInvoke>> {
+    let file = File::open(path)
+        .wrap_err_with(|| format!("Could not open record file {}", path.display()))?;
+
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|(index, line)| {
+            serde_json::from_str(&line?).wrap_err_with(|| {
+                format!("Could not parse line {} of {}", index + 1, path.display())
+            })
+        })
+        .collect()
+}
@@ -1,46 +1,230 @@
-use crate::config::{ContractSourceConfig, GatlingConfig};
-use crate::utils::{compute_contract_address, wait_for_tx};
-use color_eyre::eyre::Context;
+use crate::config::{
+    ContractSourceConfig, ExistingErc20Config, GatlingConfig, RunConfig, TxVersion,
+};
+use crate::metrics::{HealthProbe, SetupTimings};
+use crate::utils::{
+    compute_contract_address, pending_tx_count, spec_version, supports_subscriptions,
+    wait_for_block_production, wait_for_every_tx, wait_for_tx, wait_for_tx_subscribed,
+    wait_for_txs, FailoverTransport, NewHeads, StarknetRpc,
+};
+use color_eyre::eyre::{ensure, Context};
 use color_eyre::{eyre::eyre, Result};
+use futures::StreamExt;
 
 use log::{debug, info, warn};
+use serde_derive::{Deserialize, Serialize};
 use starknet::core::types::contract::SierraClass;
 
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
+use async_trait::async_trait;
 use starknet::accounts::{
-    Account, AccountFactory, Call, ConnectedAccount, ExecutionEncoding, OpenZeppelinAccountFactory,
+    Account, AccountFactory, Call, ConnectedAccount, ExecutionEncoder, ExecutionEncoding,
+    OpenZeppelinAccountFactory, RawDeclaration, RawExecution, RawLegacyDeclaration,
     SingleOwnerAccount,
 };
 use starknet::contract::ContractFactory;
+use starknet::core::crypto::pedersen_hash;
 use starknet::core::types::{
-    contract::legacy::LegacyContractClass, BlockId, BlockTag, FieldElement, StarknetError,
+    contract::legacy::LegacyContractClass, BlockId, BlockTag, BroadcastedDeployAccountTransaction,
+    BroadcastedDeployAccountTransactionV1, ContractClass, FeeEstimate, FieldElement, FunctionCall,
+    ResourceBounds, ResourceBoundsMapping, StarknetError,
 };
+use starknet::core::utils::parse_cairo_short_string;
 use starknet::macros::{felt, selector};
 use starknet::providers::ProviderError;
-use starknet::providers::{jsonrpc::HttpTransport, JsonRpcClient, Provider};
-use starknet::providers::{MaybeUnknownErrorCode, StarknetErrorWithMessage};
-use starknet::signers::{LocalWallet, SigningKey};
+use starknet::providers::{JsonRpcClient, Provider};
+use starknet::signers::{LocalWallet, Signer, SigningKey};
+
+use super::signer::GatlingSigner;
 use std::str;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use url::Url;
 
 // Used to bypass validation
 pub static MAX_FEE: FieldElement = felt!("0x6efb28c75a0000");
-pub static CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// Amount of ERC20 each benchmark account is funded with
+const ERC20_FUNDING_AMOUNT: FieldElement = felt!("0xFFF");
+/// Amount of fee token each benchmark account is funded with
+const FEE_FUNDING_AMOUNT: FieldElement = felt!("0xFFFFFFFFFFFFFFFFFFFF");
+/// Transfers per multicall when funding many accounts at once
+pub const FUNDING_BATCH_SIZE: usize = 100;
+
+type OwnerAccount = SingleOwnerAccount<Arc<StarknetRpc>, GatlingSigner>;
+
+/// Version the invokes of the benchmarks are signed with, along with how they pay their fee
+#[derive(Debug, Clone)]
+pub enum InvokeVersion {
+    /// Paying in the fee token up to [`MAX_FEE`]
+    V1,
+    /// Paying in STRK within `resource_bounds`, plus `tip`
+    V3 {
+        resource_bounds: ResourceBoundsMapping,
+        tip: u64,
+    },
+}
+
+impl InvokeVersion {
+    pub fn from_config(run: &RunConfig) -> Self {
+        match run.tx_version {
+            TxVersion::V1 => Self::V1,
+            TxVersion::V3 => {
+                let bounds = run.resource_bounds.unwrap_or_default();
+
+                Self::V3 {
+                    resource_bounds: ResourceBoundsMapping {
+                        l1_gas: ResourceBounds {
+                            max_amount: bounds.max_amount,
+                            max_price_per_unit: bounds.max_price_per_unit.into(),
+                        },
+                        l2_gas: ResourceBounds {
+                            max_amount: 0,
+                            max_price_per_unit: 0,
+                        },
+                    },
+                    tip: run.tip.unwrap_or_default(),
+                }
+            }
+        }
+    }
+
+    /// Most a single invoke can pay, in wei for v1 and in fri for v3
+    pub fn max_fee(&self) -> u128 {
+        match self {
+            Self::V1 => u128::try_from(MAX_FEE).unwrap_or(u128::MAX),
+            Self::V3 {
+                resource_bounds, ..
+            } => u128::from(resource_bounds.l1_gas.max_amount)
+                .saturating_mul(resource_bounds.l1_gas.max_price_per_unit),
+        }
+    }
+
+    /// Unit of the fees paid by the invokes
+    pub fn fee_unit(&self) -> &'static str {
+        match self {
+            Self::V1 => "wei",
+            Self::V3 { .. } => "fri",
+        }
+    }
+}
 
-pub type StarknetAccount = SingleOwnerAccount<Arc<JsonRpcClient<HttpTransport>>, LocalWallet>;
+/// Account sending the transactions, which also signs the invokes of the benchmarks
+/// with the version of `run.tx_version`
+#[derive(Debug, Clone)]
+pub struct StarknetAccount {
+    account: OwnerAccount,
+    /// Also held by `account`, which doesn't give access to it, to sign the v3 invokes
+    signer: GatlingSigner,
+    invoke_version: InvokeVersion,
+}
+
+impl StarknetAccount {
+    pub fn new(
+        provider: Arc<StarknetRpc>,
+        signer: GatlingSigner,
+        address: FieldElement,
+        chain_id: FieldElement,
+        encoding: ExecutionEncoding,
+        invoke_version: InvokeVersion,
+    ) -> Self {
+        Self {
+            account: SingleOwnerAccount::new(provider, signer.clone(), address, chain_id, encoding),
+            signer,
+            invoke_version,
+        }
+    }
+
+    pub fn set_block_id(&mut self, block_id: BlockId) -> &Self {
+        self.account.set_block_id(block_id);
+        self
+    }
+
+    pub fn invoke_version(&self) -> &InvokeVersion {
+        &self.invoke_version
+    }
+
+    pub fn signer(&self) -> &GatlingSigner {
+        &self.signer
+    }
+}
+
+#[async_trait]
+impl Account for StarknetAccount {
+    type SignError = <OwnerAccount as Account>::SignError;
+
+    fn address(&self) -> FieldElement {
+        self.account.address()
+    }
+
+    fn chain_id(&self) -> FieldElement {
+        self.account.chain_id()
+    }
+
+    async fn sign_execution(
+        &self,
+        execution: &RawExecution,
+        query_only: bool,
+    ) -> std::result::Result<Vec<FieldElement>, Self::SignError> {
+        self.account.sign_execution(execution, query_only).await
+    }
+
+    async fn sign_declaration(
+        &self,
+        declaration: &RawDeclaration,
+        query_only: bool,
+    ) -> std::result::Result<Vec<FieldElement>, Self::SignError> {
+        self.account.sign_declaration(declaration, query_only).await
+    }
+
+    async fn sign_legacy_declaration(
+        &self,
+        legacy_declaration: &RawLegacyDeclaration,
+        query_only: bool,
+    ) -> std::result::Result<Vec<FieldElement>, Self::SignError> {
+        self.account
+            .sign_legacy_declaration(legacy_declaration, query_only)
+            .await
+    }
+}
+
+impl ExecutionEncoder for StarknetAccount {
+    fn encode_calls(&self, calls: &[Call]) -> Vec<FieldElement> {
+        self.account.encode_calls(calls)
+    }
+}
+
+impl ConnectedAccount for StarknetAccount {
+    type Provider = Arc<StarknetRpc>;
+
+    fn provider(&self) -> &Self::Provider {
+        self.account.provider()
+    }
+
+    fn block_id(&self) -> BlockId {
+        self.account.block_id()
+    }
+}
 
 pub struct GatlingShooterSetup {
     config: GatlingConfig,
-    starknet_rpc: Arc<JsonRpcClient<HttpTransport>>,
-    signer: LocalWallet,
+    starknet_rpc: Arc<StarknetRpc>,
+    /// Client of the node the report is measured on, the load node unless configured otherwise
+    report_rpc: Arc<StarknetRpc>,
+    signer: GatlingSigner,
+    /// Key of the accounts the setup creates, the deployer's own one unless it signs remotely
+    accounts_signing_key: SigningKey,
     account: StarknetAccount,
     nonces: HashMap<FieldElement, FieldElement>,
+    /// Accounts of `setup.accounts_file` with their signer, empty when the accounts are created
+    file_accounts: Vec<(FieldElement, LocalWallet)>,
+    /// Subscription transactions are waited for on, only with `rpc.use_websocket`
+    new_heads: Option<NewHeads>,
     environment: Option<GatlingEnvironment>, // Will be populated in setup phase
+    /// Time spent in each step of the setup, `None` when the environment was loaded instead
+    setup_timings: Option<SetupTimings>,
 }
 
 #[derive(Clone)]
@@ -48,16 +232,100 @@ pub struct GatlingEnvironment {
     pub erc20_address: FieldElement,
     pub erc721_address: FieldElement,
     pub accounts: Vec<StarknetAccount>,
+    /// Signed deployments of the accounts that are deployed on first use, by address
+    pub account_deployments: HashMap<FieldElement, BroadcastedDeployAccountTransaction>,
+    /// Signed deployments of the fresh accounts funded for the deploy account benchmark
+    pub benchmark_deployments: Vec<BroadcastedDeployAccountTransaction>,
+    /// Instance of `setup.declare_contract`, called by the custom calls without a contract address
+    pub custom_contract_address: Option<FieldElement>,
+    /// Keys of the created accounts holding one of their own, by address
+    pub account_keys: HashMap<FieldElement, FieldElement>,
+}
+
+/// Serializable form of a [`GatlingEnvironment`], persisted by the `setup` command
+/// so that later runs can skip the setup phase
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GatlingState {
+    pub erc20_address: FieldElement,
+    pub erc721_address: FieldElement,
+    pub legacy_accounts: bool,
+    pub accounts: Vec<AccountState>,
+    /// Key of the created accounts, only saved when the deployer signs remotely
+    /// as they are otherwise controlled by the key of the deployer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accounts_signing_key: Option<FieldElement>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub benchmark_deployments: Vec<BroadcastedDeployAccountTransaction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_contract_address: Option<FieldElement>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountState {
+    pub address: FieldElement,
+    /// Signed deployment for counterfactual accounts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployment: Option<BroadcastedDeployAccountTransaction>,
+    /// Key of an account created with one of its own, for `run.rotate_signing_keys`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<FieldElement>,
 }
 
 impl GatlingShooterSetup {
     pub async fn from_config(config: GatlingConfig) -> Result<Self> {
-        let starknet_rpc: Arc<JsonRpcClient<HttpTransport>> =
-            Arc::new(starknet_rpc_provider(Url::parse(&config.clone().rpc.url)?));
+        let urls = config
+            .rpc
+            .url
+            .all()
+            .iter()
+            .map(|url| Url::parse(url))
+            .collect::<Result<Vec<_>, _>>()?;
+        let primary_url = urls
+            .first()
+            .cloned()
+            .ok_or(eyre!("rpc.url should have at least one url"))?;
+        let starknet_rpc: Arc<StarknetRpc> = Arc::new(starknet_rpc_provider(urls));
+
+        if let Some(grace_period) = &config.rpc.startup_grace_period {
+            wait_for_block_production(&starknet_rpc, grace_period).await?;
+        }
 
-        let signer = LocalWallet::from(SigningKey::from_secret_scalar(config.deployer.signing_key));
+        if config.run.expected_chain_id.is_some() {
+            warn!("run.expected_chain_id is deprecated, set rpc.expected_chain_id instead");
+        }
+        if let Some(expected_chain_id) = config.expected_chain_id() {
+            let chain_id = starknet_rpc.chain_id().await?;
+
+            ensure!(
+                chain_id == expected_chain_id,
+                "Node is on chain {} but rpc.expected_chain_id is {}, aborting to avoid benchmarking the wrong network",
+                chain_id_string(chain_id),
+                chain_id_string(expected_chain_id)
+            );
+        }
 
-        let account = SingleOwnerAccount::new(
+        let report_rpc = match &config.report.verification_rpc_url {
+            Some(url) => Arc::new(starknet_rpc_provider([Url::parse(url)?])),
+            None => starknet_rpc.clone(),
+        };
+
+        ensure!(
+            config.setup.accounts_file.is_none() || !config.setup.counterfactual_accounts,
+            "setup.accounts_file lists deployed accounts, it can't be used with setup.counterfactual_accounts"
+        );
+        let file_accounts = match &config.setup.accounts_file {
+            Some(path) => read_accounts_file(path, config.setup.keystore_password.as_deref())?,
+            None => Vec::new(),
+        };
+
+        let signer = GatlingSigner::from_config(&config.deployer)?;
+        // The remote signer only signs for the deployer, the accounts get a key of their own
+        let accounts_signing_key = match config.deployer.signing_key {
+            Some(signing_key) => SigningKey::from_secret_scalar(signing_key),
+            None => SigningKey::from_random(),
+        };
+
+        let account = StarknetAccount::new(
             starknet_rpc.clone(),
             signer.clone(),
             config.deployer.address,
@@ -67,6 +335,7 @@ impl GatlingShooterSetup {
             } else {
                 ExecutionEncoding::New
             },
+            InvokeVersion::from_config(&config.run),
         );
 
         // Fails if nonce is null (which is the case for 1st startup)
@@ -75,16 +344,45 @@ impl GatlingShooterSetup {
         let mut nonces: HashMap<FieldElement, FieldElement> = HashMap::new();
         nonces.insert(config.deployer.address, cur_nonce);
 
+        let new_heads = if config.rpc.use_websocket {
+            match subscribe_new_heads(primary_url).await {
+                Ok(new_heads) => Some(new_heads),
+                Err(err) => {
+                    warn!(
+                        "Could not subscribe to new blocks, polling transactions instead: {err:#}"
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         Ok(Self {
             config,
             starknet_rpc,
+            report_rpc,
             signer,
+            accounts_signing_key,
             account,
             nonces,
+            file_accounts,
+            new_heads,
             environment: None,
+            setup_timings: None,
         })
     }
 
+    /// Subscription to the new blocks the transactions are waited for on, when subscribed
+    pub fn new_heads(&self) -> Option<&NewHeads> {
+        self.new_heads.as_ref()
+    }
+
+    /// Signer of the accounts the setup creates, holding their key locally
+    fn accounts_signer(&self) -> GatlingSigner {
+        GatlingSigner::Local(LocalWallet::from(self.accounts_signing_key.clone()))
+    }
+
     pub fn environment(&self) -> Result<&GatlingEnvironment> {
         self.environment.as_ref().ok_or(eyre!(
             "Environment is not yet populated, you should run the setup function first"
@@ -95,16 +393,332 @@ impl GatlingShooterSetup {
         &self.config
     }
 
-    pub fn rpc_client(&self) -> &Arc<JsonRpcClient<HttpTransport>> {
+    pub fn setup_timings(&self) -> Option<SetupTimings> {
+        self.setup_timings
+    }
+
+    /// Client of the node the load is sent to
+    pub fn rpc_client(&self) -> &Arc<StarknetRpc> {
         &self.starknet_rpc
     }
 
+    /// Client to use for the block numbers and blocks the report is computed from
+    pub fn report_rpc_client(&self) -> &Arc<StarknetRpc> {
+        &self.report_rpc
+    }
+
+    /// Wait for a transaction to be accepted, backing off from `run.confirmation_interval_ms`
+    /// up to `run.max_confirmation_interval_ms` between polls
+    ///
+    /// With a block subscription, see [`wait_for_tx_subscribed`]
+    async fn wait_for_tx(&self, tx_hash: FieldElement) -> Result<()> {
+        let timeout = Duration::from_secs(self.config.run.tx_timeout_secs);
+        let interval = self.config.run.confirmation_interval();
+        let max_retries = self.config.rpc.max_retries;
+
+        match &self.new_heads {
+            Some(new_heads) => {
+                wait_for_tx_subscribed(
+                    &self.starknet_rpc,
+                    tx_hash,
+                    new_heads,
+                    interval,
+                    self.config.run.max_confirmation_interval(),
+                    timeout,
+                    max_retries,
+                )
+                .await
+            }
+            None => {
+                wait_for_tx(
+                    &self.starknet_rpc,
+                    tx_hash,
+                    interval,
+                    self.config.run.max_confirmation_interval(),
+                    timeout,
+                    max_retries,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Wait for many transactions to be accepted at once, see [`Self::wait_for_tx`]
+    async fn wait_for_txs(&self, tx_hashes: impl IntoIterator<Item = FieldElement>) -> Result<()> {
+        let interval = self.config.run.confirmation_interval();
+
+        wait_for_txs(
+            &self.starknet_rpc,
+            tx_hashes.into_iter(),
+            self.new_heads.as_ref(),
+            interval,
+            self.config.run.max_confirmation_interval(),
+            Duration::from_secs(self.config.run.tx_timeout_secs),
+            self.config.rpc.max_retries,
+            self.config.report.max_concurrent_requests,
+        )
+        .await
+    }
+
+    /// Wait for many transactions at once without stopping at the failed ones,
+    /// returning the error of each of them, see [`Self::wait_for_txs`]
+    pub async fn wait_for_every_tx(
+        &self,
+        tx_hashes: impl IntoIterator<Item = FieldElement>,
+    ) -> Result<Vec<color_eyre::Report>> {
+        let interval = self.config.run.confirmation_interval();
+
+        wait_for_every_tx(
+            &self.starknet_rpc,
+            tx_hashes.into_iter(),
+            self.new_heads.as_ref(),
+            interval,
+            self.config.run.max_confirmation_interval(),
+            Duration::from_secs(self.config.run.tx_timeout_secs),
+            self.config.rpc.max_retries,
+            self.config.report.max_concurrent_requests,
+        )
+        .await
+    }
+
     pub fn deployer_account(&self) -> &StarknetAccount {
         &self.account
     }
 
+    /// Addresses sending the benchmark transactions, the accounts and the deployer minting ERC721s
+    pub fn benchmark_senders(&self) -> Result<HashSet<FieldElement>> {
+        let environment = self.environment()?;
+
+        Ok(environment
+            .accounts
+            .iter()
+            .map(|account| account.address())
+            .chain([self.account.address()])
+            .collect())
+    }
+
+    /// Check that the node still produces blocks, answers a read call and
+    /// drains its mempool within `timeout`, before running another benchmark
+    pub async fn health_probe(&self, after: String, timeout: Duration) -> Result<HealthProbe> {
+        let environment = self.environment()?;
+        let start = std::time::Instant::now();
+
+        let read_call = self
+            .starknet_rpc
+            .call(
+                FunctionCall {
+                    contract_address: environment.erc20_address,
+                    entry_point_selector: selector!("balanceOf"),
+                    calldata: vec![self.account.address()],
+                },
+                BlockId::Tag(BlockTag::Latest),
+            )
+            .await
+            .inspect_err(|err| warn!("Health probe read call failed: {err}"))
+            .is_ok();
+
+        let first_block = self.starknet_rpc.block_number().await?;
+        let (mut block_advancing, mut mempool_drained) = (false, Some(false));
+
+        while start.elapsed() < timeout {
+            if !block_advancing {
+                block_advancing = self.starknet_rpc.block_number().await? > first_block;
+            }
+
+            if mempool_drained == Some(false) {
+                mempool_drained = self.mempool_drained().await;
+            }
+
+            if block_advancing && mempool_drained != Some(false) {
+                break;
+            }
+
+            tokio::time::sleep(self.config.run.confirmation_interval()).await;
+        }
+
+        let probe = HealthProbe {
+            after,
+            block_advancing,
+            read_call,
+            mempool_drained,
+        };
+
+        info!("Health probe: {probe:?}");
+
+        Ok(probe)
+    }
+
+    /// Whether the pending block is empty, `None` when it can't be fetched
+    async fn mempool_drained(&self) -> Option<bool> {
+        pending_tx_count(&self.starknet_rpc)
+            .await
+            .map(|pending| pending == 0)
+    }
+
+    /// Wait for at most `timeout` for `tx_hashes` to be accepted and the pending block to
+    /// be closed, returning whether everything was included in a block in time
+    ///
+    /// The failed transactions are only counted, every other one is still waited for
+    pub async fn cooldown(
+        &self,
+        tx_hashes: impl IntoIterator<Item = FieldElement>,
+        timeout: Duration,
+    ) -> bool {
+        let start = std::time::Instant::now();
+
+        match tokio::time::timeout(timeout, self.wait_for_every_tx(tx_hashes)).await {
+            Ok(Ok(errors)) if errors.is_empty() => {}
+            // A reverted transaction is included all the same, the pending block is still waited for
+            Ok(Ok(errors)) => warn!(
+                "{} of the submitted transactions failed, the first with: {:#}",
+                errors.len(),
+                errors[0]
+            ),
+            Ok(Err(err)) => warn!("Stopped waiting for the submitted transactions: {err}"),
+            Err(_) => return false,
+        }
+
+        while start.elapsed() < timeout {
+            if self.mempool_drained().await != Some(false) {
+                return true;
+            }
+
+            tokio::time::sleep(self.config.run.confirmation_interval()).await;
+        }
+
+        false
+    }
+
+    /// Save the environment populated by the setup phase to a state file
+    pub fn save_state(&self, path: &Path) -> Result<()> {
+        let environment = self.environment()?;
+
+        let state = GatlingState {
+            erc20_address: environment.erc20_address,
+            erc721_address: environment.erc721_address,
+            legacy_accounts: matches!(
+                self.config.setup.account_contract,
+                ContractSourceConfig::V0(_)
+            ),
+            accounts: environment
+                .accounts
+                .iter()
+                .map(|account| AccountState {
+                    address: account.address(),
+                    deployment: environment
+                        .account_deployments
+                        .get(&account.address())
+                        .cloned(),
+                    signing_key: environment.account_keys.get(&account.address()).copied(),
+                })
+                .collect(),
+            accounts_signing_key: matches!(self.signer, GatlingSigner::Remote(_))
+                .then(|| self.accounts_signing_key.secret_scalar()),
+            benchmark_deployments: environment.benchmark_deployments.clone(),
+            custom_contract_address: environment.custom_contract_address,
+        };
+
+        let writer = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(writer, &state)?;
+
+        info!("Setup state saved to {}", path.display());
+
+        Ok(())
+    }
+
+    /// Populate the environment from a state file saved by a previous setup,
+    /// instead of running the setup phase
+    pub fn load_state(&mut self, path: &Path) -> Result<()> {
+        let file = std::fs::File::open(path)
+            .wrap_err_with(|| format!("Could not open state file {}", path.display()))?;
+        let state: GatlingState = serde_json::from_reader(file)
+            .wrap_err_with(|| format!("Could not parse state file {}", path.display()))?;
+
+        let execution_encoding = if state.legacy_accounts {
+            ExecutionEncoding::Legacy
+        } else {
+            ExecutionEncoding::New
+        };
+
+        match state.accounts_signing_key {
+            Some(signing_key) => {
+                self.accounts_signing_key = SigningKey::from_secret_scalar(signing_key)
+            }
+            None => ensure!(
+                !self.file_accounts.is_empty() || matches!(self.signer, GatlingSigner::Local(_)),
+                "State file {} doesn't hold the key of its accounts, run the setup again to create accounts with a key of their own",
+                path.display()
+            ),
+        }
+
+        let mut accounts = Vec::with_capacity(state.accounts.len());
+        let mut account_deployments = HashMap::new();
+        let mut account_keys = HashMap::new();
+
+        let file_signers: HashMap<FieldElement, LocalWallet> =
+            self.file_accounts.iter().cloned().collect();
+
+        for AccountState {
+            address,
+            deployment,
+            signing_key,
+        } in state.accounts
+        {
+            let signer = if let Some(signing_key) = signing_key {
+                account_keys.insert(address, signing_key);
+                GatlingSigner::Local(LocalWallet::from(SigningKey::from_secret_scalar(
+                    signing_key,
+                )))
+            } else if self.file_accounts.is_empty() {
+                self.accounts_signer()
+            } else {
+                file_signers
+                    .get(&address)
+                    .cloned()
+                    .map(GatlingSigner::Local)
+                    .ok_or(eyre!(
+                        "Account {address:#064x} of the state file is missing from setup.accounts_file"
+                    ))?
+            };
+
+            accounts.push(StarknetAccount::new(
+                self.starknet_rpc.clone(),
+                signer,
+                address,
+                self.config.setup.chain_id,
+                execution_encoding,
+                InvokeVersion::from_config(&self.config.run),
+            ));
+
+            if let Some(deployment) = deployment {
+                account_deployments.insert(address, deployment);
+            }
+        }
+
+        info!(
+            "Loaded setup state from {} with {} accounts",
+            path.display(),
+            accounts.len()
+        );
+
+        self.environment = Some(GatlingEnvironment {
+            erc20_address: state.erc20_address,
+            erc721_address: state.erc721_address,
+            accounts,
+            account_deployments,
+            benchmark_deployments: state.benchmark_deployments,
+            custom_contract_address: state.custom_contract_address,
+            account_keys,
+        });
+
+        Ok(())
+    }
+
     /// Setup the simulation.
     pub async fn setup(&mut self) -> Result<()> {
+        let setup_start = Instant::now();
+        let mut timings = SetupTimings::default();
+
         let chain_id = self.starknet_rpc.chain_id().await?.to_bytes_be();
         let block_number = self.starknet_rpc.block_number().await?;
         info!(
@@ -113,50 +727,128 @@ impl GatlingShooterSetup {
             block_number
         );
 
-        let setup_config = self.config.clone().setup;
+        self.check_fee_funding().await?;
 
-        let erc20_class_hash = self.declare_contract(&setup_config.erc20_contract).await?;
+        let setup_config = self.config.clone().setup;
 
-        let erc721_class_hash = self.declare_contract(&setup_config.erc721_contract).await?;
+        let step = Instant::now();
+        let erc721_class_hash = self
+            .contract_class(
+                setup_config.erc721_class_hash,
+                &setup_config.erc721_contract,
+            )
+            .await?;
 
         let account_class_hash = self
             .declare_contract(&setup_config.account_contract)
             .await?;
 
+        if setup_config.counterfactual_accounts || self.config.run.num_account_deployments != 0 {
+            self.check_counterfactual_support(account_class_hash)
+                .await?;
+        }
+        timings.declare += step.elapsed().as_secs_f64();
+
         let execution_encoding = match setup_config.account_contract {
             ContractSourceConfig::V0(_) => ExecutionEncoding::Legacy,
             ContractSourceConfig::V1(_) => ExecutionEncoding::New,
         };
 
-        let erc20_address = self.deploy_erc20(erc20_class_hash).await?;
+        let (erc20_address, erc20_funder) = match &setup_config.existing_erc20 {
+            Some(existing_erc20) => self.use_existing_erc20(existing_erc20).await?,
+            None => {
+                let step = Instant::now();
+                let erc20_class_hash = self
+                    .contract_class(setup_config.erc20_class_hash, &setup_config.erc20_contract)
+                    .await?;
+                timings.declare += step.elapsed().as_secs_f64();
+
+                let step = Instant::now();
+                let erc20_address = self.deploy_erc20(erc20_class_hash).await?;
+                timings.deploy += step.elapsed().as_secs_f64();
+
+                (erc20_address, self.account.clone())
+            }
+        };
+
+        let step = Instant::now();
         let erc721_address = self.deploy_erc721(erc721_class_hash).await?;
+        timings.deploy += step.elapsed().as_secs_f64();
+
+        let custom_contract_address = match &setup_config.declare_contract {
+            Some(declare_contract) => {
+                let step = Instant::now();
+                let source = ContractSourceConfig::V1(declare_contract.source.clone());
+                let class_hash = self.declare_contract(&source).await?;
+                timings.declare += step.elapsed().as_secs_f64();
+
+                let step = Instant::now();
+                let address = self
+                    .deploy_contract(
+                        "Custom",
+                        class_hash,
+                        declare_contract.constructor_calldata.clone(),
+                    )
+                    .await?;
+                timings.deploy += step.elapsed().as_secs_f64();
+
+                Some(address)
+            }
+            None => None,
+        };
+
+        let step = Instant::now();
 
-        let accounts = self
-            .create_accounts(
+        let (accounts, account_deployments, account_keys) = if self.file_accounts.is_empty() {
+            self.create_accounts(
                 account_class_hash,
-                self.config.run.concurrency as usize,
+                self.config.run.num_accounts() as usize,
                 execution_encoding,
                 erc20_address,
+                erc20_funder,
+            )
+            .await?
+        } else {
+            let fund_erc20 = setup_config.existing_erc20.is_none();
+            let accounts = self
+                .use_file_accounts(execution_encoding, erc20_address, erc20_funder, fund_erc20)
+                .await?;
+
+            (accounts, HashMap::new(), HashMap::new())
+        };
+
+        let benchmark_deployments = self
+            .create_benchmark_deployments(
+                account_class_hash,
+                self.config.run.num_account_deployments as usize,
             )
             .await?;
+        timings.fund = step.elapsed().as_secs_f64();
 
         let environment = GatlingEnvironment {
             erc20_address,
             erc721_address,
             accounts,
+            account_deployments,
+            benchmark_deployments,
+            custom_contract_address,
+            account_keys,
         };
 
         self.environment = Some(environment);
 
+        timings.total = setup_start.elapsed().as_secs_f64();
+        info!("Setup timings: {timings:?}");
+        self.setup_timings = Some(timings);
+
         Ok(())
     }
 
-    async fn transfer(
+    /// Send `calls` from `account` with its next nonce, returning the transaction hash
+    async fn execute(
         &mut self,
-        contract_address: FieldElement,
         account: StarknetAccount,
-        recipient: FieldElement,
-        amount: FieldElement,
+        calls: Vec<Call>,
     ) -> Result<FieldElement> {
         let from_address = account.address();
         let nonce = match self.nonces.get(&from_address) {
@@ -165,20 +857,12 @@ impl GatlingShooterSetup {
         };
 
         debug!(
-            "Transferring {amount} of {contract_address:#064x} from address {from_address:#064x} to address {recipient:#064x} with nonce={}",
-            nonce,
+            "Executing {} calls from address {from_address:#064x} with nonce={nonce}",
+            calls.len()
         );
 
-        let (amount_low, amount_high) = (amount, felt!("0"));
-
-        let call = Call {
-            to: contract_address,
-            selector: selector!("transfer"),
-            calldata: vec![recipient, amount_low, amount_high],
-        };
-
         let result = account
-            .execute(vec![call])
+            .execute(calls)
             .max_fee(MAX_FEE)
             .nonce(nonce)
             .send()
@@ -189,63 +873,200 @@ impl GatlingShooterSetup {
         Ok(result.transaction_hash)
     }
 
-    async fn deploy_erc721(&mut self, class_hash: FieldElement) -> Result<FieldElement> {
-        let contract_factory = ContractFactory::new(class_hash, self.account.clone());
-        let from_address = self.account.address();
-        let nonce = match self.nonces.get(&from_address) {
-            Some(nonce) => *nonce,
-            None => self.account.get_nonce().await?,
-        };
+    /// Use an ERC20 contract that is already deployed, checking it exists and that
+    /// the whale holds enough tokens to fund all the benchmark accounts
+    ///
+    /// # Returns
+    ///
+    /// The address of the contract and the whale account funding the benchmark accounts.
+    async fn use_existing_erc20(
+        &self,
+        existing_erc20: &ExistingErc20Config,
+    ) -> Result<(FieldElement, StarknetAccount)> {
+        let ExistingErc20Config { address, whale } = existing_erc20;
+
+        let class_hash = self
+            .starknet_rpc
+            .get_class_hash_at(BlockId::Tag(BlockTag::Pending), address)
+            .await
+            .wrap_err_with(|| format!("No ERC20 contract deployed at address {address:#064x}"))?;
 
-        let name = selector!("TestNFT");
-        let symbol = selector!("TNFT");
-        let recipient = self.account.address();
+        info!("Using existing ERC20 contract at address {address:#064x} with class hash {class_hash:#064x}");
 
-        let constructor_args = vec![name, symbol, recipient];
-        let unique = false;
+        let balance = self
+            .erc20_balance(*address, whale.address)
+            .await
+            .wrap_err_with(|| {
+                format!(
+                    "Could not get the ERC20 balance of whale {:#064x}",
+                    whale.address
+                )
+            })?;
 
-        let address =
-            compute_contract_address(self.config.deployer.salt, class_hash, &constructor_args);
+        let required = ERC20_FUNDING_AMOUNT * FieldElement::from(self.config.run.num_accounts());
 
-        if let Ok(contract_class_hash) = self
-            .starknet_rpc
-            .get_class_hash_at(BlockId::Tag(BlockTag::Pending), address)
-            .await
-        {
-            if contract_class_hash == class_hash {
-                warn!("ERC721 contract already deployed at address {address:#064x}");
-                return Ok(address);
+        ensure!(
+            balance.covers(required),
+            "Whale {:#064x} holds {balance} tokens of ERC20 {address:#064x}, {required} are needed to fund the accounts",
+            whale.address
+        );
+
+        let mut whale_account = StarknetAccount::new(
+            self.starknet_rpc.clone(),
+            GatlingSigner::Local(LocalWallet::from(SigningKey::from_secret_scalar(
+                whale.signing_key,
+            ))),
+            whale.address,
+            self.config.setup.chain_id,
+            if whale.legacy_account {
+                ExecutionEncoding::Legacy
             } else {
-                return Err(eyre!("ERC721 contract {address:#064x} already deployed with a different class hash {contract_class_hash:#064x}, expected {class_hash:#064x}"));
-            }
+                ExecutionEncoding::New
+            },
+            InvokeVersion::from_config(&self.config.run),
+        );
+        whale_account.set_block_id(BlockId::Tag(BlockTag::Pending));
+
+        Ok((*address, whale_account))
+    }
+
+    /// Fail before anything is sent when the deployer can't fund the accounts created by
+    /// the setup with the fee token, the fees of the setup itself are left out
+    ///
+    /// Checked by [`Self::setup`] rather than [`Self::from_config`], which also builds the
+    /// shooter of a dry run and of a run loading a setup state, whose deployer doesn't fund
+    /// anything and may well have spent its balance on the setup already
+    async fn check_fee_funding(&self) -> Result<()> {
+        let accounts = if self.file_accounts.is_empty() {
+            self.config.run.num_accounts()
+        } else {
+            0
+        };
+        let deployments = self.config.run.num_account_deployments;
+
+        let required = FEE_FUNDING_AMOUNT * FieldElement::from(accounts)
+            + MAX_FEE * FieldElement::from(deployments);
+        if required != FieldElement::ZERO {
+            let fee_token_address = self.config.setup.fee_token_address;
+            let balance = self
+                .erc20_balance(fee_token_address, self.account.address())
+                .await
+                .wrap_err_with(|| {
+                    format!(
+                        "Could not get the fee token balance of deployer {:#064x}",
+                        self.account.address()
+                    )
+                })?;
+
+            ensure!(
+                balance.covers(required),
+                "Deployer {:#064x} holds {balance} of fee token {fee_token_address:#064x}, \
+                {required} are needed to fund {accounts} accounts and {deployments} account deployments",
+                self.account.address()
+            );
         }
 
-        let deploy = contract_factory.deploy(constructor_args, self.config.deployer.salt, unique);
+        if let Some(strk_address) = self.strk_funding_address() {
+            // The deployer sends the erc721 mints, as v3 invokes too
+            let mints = self.config.run.num_erc721_mints
+                + self.config.run.mixed.as_ref().map_or(0, |mixed| {
+                    let weights = mixed.reduced_weights();
+                    let total: u64 = weights.iter().map(|(_, weight)| weight).sum();
+                    let mints: u64 = weights
+                        .iter()
+                        .filter(|(scenario, _)| *scenario == "erc721")
+                        .map(|(_, weight)| weight)
+                        .sum();
+
+                    mixed.num_transactions * mints / total.max(1)
+                });
+            let required = FEE_FUNDING_AMOUNT * FieldElement::from(accounts)
+                + FieldElement::from(self.account.invoke_version().max_fee())
+                    * FieldElement::from(mints);
+            let balance = self
+                .erc20_balance(strk_address, self.account.address())
+                .await
+                .wrap_err_with(|| {
+                    format!(
+                        "Could not get the STRK balance of deployer {:#064x}",
+                        self.account.address()
+                    )
+                })?;
+
+            ensure!(
+                balance.covers(required),
+                "Deployer {:#064x} holds {balance} of STRK {strk_address:#064x}, \
+                {required} are needed to fund {accounts} accounts for their v3 invokes \
+                and to pay for {mints} erc721 mints",
+                self.account.address()
+            );
+        }
 
-        info!("Deploying ERC721 with nonce={}, address={address}", nonce);
+        Ok(())
+    }
 
-        let result = deploy.nonce(nonce).max_fee(MAX_FEE).send().await?;
-        wait_for_tx(&self.starknet_rpc, result.transaction_hash, CHECK_INTERVAL).await?;
+    /// STRK the created accounts are also funded with to pay for their v3 invokes,
+    /// `None` when the benchmarks send v1 invokes
+    fn strk_funding_address(&self) -> Option<FieldElement> {
+        match self.config.run.tx_version {
+            TxVersion::V1 => None,
+            TxVersion::V3 => self.config.setup.strk_fee_token_address,
+        }
+    }
 
-        self.nonces.insert(from_address, nonce + FieldElement::ONE);
+    /// Balance of `holder` in the ERC20 contract at `address`, as of the pending block
+    async fn erc20_balance(
+        &self,
+        address: FieldElement,
+        holder: FieldElement,
+    ) -> Result<Erc20Balance> {
+        let balance = self
+            .starknet_rpc
+            .call(
+                FunctionCall {
+                    contract_address: address,
+                    entry_point_selector: selector!("balanceOf"),
+                    calldata: vec![holder],
+                },
+                BlockId::Tag(BlockTag::Pending),
+            )
+            .await?;
 
-        debug!(
-            "Deploy ERC721 transaction accepted {:#064x}",
-            result.transaction_hash
-        );
+        let [low, high] = balance.as_slice() else {
+            return Err(eyre!(
+                "Unexpected `balanceOf` result from ERC20 contract {address:#064x}: {balance:?}"
+            ));
+        };
 
-        info!("ERC721 contract deployed at address {:#064x}", address);
-        Ok(address)
+        Ok(Erc20Balance {
+            low: *low,
+            high: *high,
+        })
+    }
+
+    async fn deploy_erc721(&mut self, class_hash: FieldElement) -> Result<FieldElement> {
+        let constructor_args = self.erc721_constructor_args();
+
+        self.deploy_contract("ERC721", class_hash, constructor_args)
+            .await
+    }
+
+    pub fn erc721_constructor_args(&self) -> Vec<FieldElement> {
+        let name = selector!("TestNFT");
+        let symbol = selector!("TNFT");
+        let recipient = self.account.address();
+
+        vec![name, symbol, recipient]
     }
 
     async fn deploy_erc20(&mut self, class_hash: FieldElement) -> Result<FieldElement> {
-        let contract_factory = ContractFactory::new(class_hash, self.account.clone());
-        let from_address = self.account.address();
-        let nonce = match self.nonces.get(&from_address) {
-            Some(nonce) => *nonce,
-            None => self.account.get_nonce().await?,
-        };
+        let constructor_args = self.erc20_constructor_args();
+
+        self.deploy_contract("ERC20", class_hash, constructor_args)
+            .await
+    }
 
+    pub fn erc20_constructor_args(&self) -> Vec<FieldElement> {
         let name = selector!("TestToken");
         let symbol = selector!("TT");
         let decimals = felt!("128");
@@ -253,14 +1074,31 @@ impl GatlingShooterSetup {
             (felt!("0xFFFFFFFFF"), felt!("0xFFFFFFFFF"));
         let recipient = self.account.address();
 
-        let constructor_args = vec![
+        vec![
             name,
             symbol,
             decimals,
             initial_supply_low,
             initial_supply_high,
             recipient,
-        ];
+        ]
+    }
+
+    /// Deploy an instance of `class_hash` from the deployer, the address being derived from
+    /// `deployer.salt` an instance deployed by a previous setup is reused
+    async fn deploy_contract(
+        &mut self,
+        name: &str,
+        class_hash: FieldElement,
+        constructor_args: Vec<FieldElement>,
+    ) -> Result<FieldElement> {
+        let contract_factory = ContractFactory::new(class_hash, self.account.clone());
+        let from_address = self.account.address();
+        let nonce = match self.nonces.get(&from_address) {
+            Some(nonce) => *nonce,
+            None => self.account.get_nonce().await?,
+        };
+
         let unique = false;
 
         let address =
@@ -272,133 +1110,663 @@ impl GatlingShooterSetup {
             .await
         {
             if contract_class_hash == class_hash {
-                warn!("ERC20 contract already deployed at address {address:#064x}");
+                warn!("{name} contract already deployed at address {address:#064x}");
                 return Ok(address);
             } else {
-                return Err(eyre!("ERC20 contract {address:#064x} already deployed with a different class hash {contract_class_hash:#064x}, expected {class_hash:#064x}"));
+                return Err(eyre!("{name} contract {address:#064x} already deployed with a different class hash {contract_class_hash:#064x}, expected {class_hash:#064x}"));
             }
         }
 
         let deploy = contract_factory.deploy(constructor_args, self.config.deployer.salt, unique);
 
-        info!(
-            "Deploying ERC20 contract with nonce={}, address={:#064x}",
-            nonce, address
-        );
+        info!("Deploying {name} contract with nonce={nonce}, address={address:#064x}");
 
         let result = deploy.nonce(nonce).max_fee(MAX_FEE).send().await?;
-        wait_for_tx(&self.starknet_rpc, result.transaction_hash, CHECK_INTERVAL).await?;
+        self.wait_for_tx(result.transaction_hash).await?;
 
         self.nonces.insert(from_address, nonce + FieldElement::ONE);
 
         debug!(
-            "Deploy ERC20 transaction accepted {:#064x}",
+            "Deploy {name} transaction accepted {:#064x}",
             result.transaction_hash
         );
 
-        info!("ERC20 contract deployed at address {:#064x}", address);
+        info!("{name} contract deployed at address {address:#064x}");
         Ok(address)
     }
 
     /// Create accounts.
     ///
+    /// An account that can't be funded or deployed is tried again up to `setup.account_retries`
+    /// times, then left out, the setup only failing with less than `setup.min_accounts`.
+    ///
     /// # Arguments
     ///
     /// * `class_hash` - The class hash of the account contract.
     /// * `num_accounts` - The number of accounts to create.
     /// * `execution_encoding` - Execution encoding to use, `Legacy` for Cairo Zero and `New` for Cairo
     /// * `erc20_address` - The address of the ERC20 contract to use for funding the accounts.
+    /// * `erc20_funder` - The account holding the ERC20 tokens to fund the accounts with.
     ///
     /// # Returns
     ///
-    /// A vector of the created accounts.
+    /// A vector of the created accounts, alongside the signed deployments
+    /// of the ones left to be deployed on first use and the keys of the ones holding their own.
+    #[allow(clippy::extra_unused_lifetimes)]
     async fn create_accounts<'a>(
         &mut self,
         class_hash: FieldElement,
         num_accounts: usize,
         execution_encoding: ExecutionEncoding,
         erc20_address: FieldElement,
-    ) -> Result<Vec<StarknetAccount>> {
+        erc20_funder: StarknetAccount,
+    ) -> Result<(
+        Vec<StarknetAccount>,
+        HashMap<FieldElement, BroadcastedDeployAccountTransaction>,
+        HashMap<FieldElement, FieldElement>,
+    )> {
         info!("Creating {} accounts", num_accounts);
 
-        let mut deployed_accounts: Vec<StarknetAccount> = Vec::with_capacity(num_accounts);
+        let retries = self.config.setup.account_retries;
+        let mut created = BTreeMap::new();
+        let mut remaining: Vec<usize> = (0..num_accounts).collect();
 
-        for i in 0..num_accounts {
-            self.account.set_block_id(BlockId::Tag(BlockTag::Pending));
+        for attempt in 0..=retries {
+            if attempt != 0 {
+                warn!(
+                    "Could not create {} accounts, retrying ({attempt}/{retries})",
+                    remaining.len()
+                );
 
-            let fee_token_address = self.config.setup.fee_token_address;
+                // A rejected transaction leaves the nonce unused, so it is fetched again
+                self.nonces.remove(&erc20_funder.address());
+                self.nonces.remove(&self.account.address());
+            }
 
-            // TODO: Check if OpenZepplinAccountFactory could be used with other type of accounts ? or should we require users to use OpenZepplinAccountFactory ?
-            let signer = self.signer.clone();
-            let provider = self.starknet_rpc.clone();
-            let account_factory = OpenZeppelinAccountFactory::new(
-                class_hash,
-                self.config.setup.chain_id,
-                &signer,
-                &provider,
-            )
-            .await?;
+            remaining = self
+                .create_account_batch(
+                    &remaining,
+                    class_hash,
+                    execution_encoding,
+                    erc20_address,
+                    &erc20_funder,
+                    &mut created,
+                )
+                .await?;
+
+            if remaining.is_empty() {
+                break;
+            }
+        }
+
+        if !remaining.is_empty() {
+            warn!(
+                "Could not create accounts {remaining:?} after {retries} retries, leaving them out"
+            );
+        }
+
+        let mut deployed_accounts: Vec<StarknetAccount> = Vec::with_capacity(created.len());
+        let mut account_deployments = HashMap::new();
+        let mut account_keys = HashMap::new();
+
+        for (i, (account, deployment)) in created {
+            if let Some(deployment) = deployment {
+                account_deployments.insert(account.address(), deployment);
+            }
+            if let Some(signing_key) = self.created_account_key(i) {
+                account_keys.insert(account.address(), signing_key.secret_scalar());
+            }
+            deployed_accounts.push(account);
+        }
+
+        let min_accounts = self
+            .config
+            .setup
+            .min_accounts
+            .map_or(num_accounts, |min_accounts| min_accounts as usize);
+
+        info!(
+            "Created {} of {num_accounts} accounts",
+            deployed_accounts.len()
+        );
+
+        ensure!(
+            deployed_accounts.len() >= min_accounts,
+            "Only {} of {num_accounts} accounts could be created, {min_accounts} are needed, lower setup.min_accounts to run with less",
+            deployed_accounts.len()
+        );
 
+        Ok((deployed_accounts, account_deployments, account_keys))
+    }
+
+    /// Key of its own of the account created at `index`, only with `run.rotate_signing_keys`
+    /// so that the transfers rotating through the accounts are signed with distinct keys
+    ///
+    /// It is derived from the key of the accounts so that a setup run again finds the
+    /// accounts it already deployed
+    fn created_account_key(&self, index: usize) -> Option<SigningKey> {
+        self.config.run.rotate_signing_keys.then(|| {
+            SigningKey::from_secret_scalar(pedersen_hash(
+                &self.accounts_signing_key.secret_scalar(),
+                &FieldElement::from(index),
+            ))
+        })
+    }
+
+    /// Signer of the account created at `index`
+    fn created_account_signer(&self, index: usize) -> GatlingSigner {
+        match self.created_account_key(index) {
+            Some(signing_key) => GatlingSigner::Local(LocalWallet::from(signing_key)),
+            None => self.accounts_signer(),
+        }
+    }
+
+    /// Factory deploying the account created at `index` with its signer
+    async fn created_account_factory(
+        &self,
+        class_hash: FieldElement,
+        index: usize,
+    ) -> Result<OpenZeppelinAccountFactory<GatlingSigner, Arc<StarknetRpc>>> {
+        Ok(OpenZeppelinAccountFactory::new(
+            class_hash,
+            self.config.setup.chain_id,
+            self.created_account_signer(index),
+            self.starknet_rpc.clone(),
+        )
+        .await?)
+    }
+
+    /// Amount of accounts in `setup.accounts_file`, which the setup uses instead of creating any
+    pub fn file_accounts_count(&self) -> usize {
+        self.file_accounts.len()
+    }
+
+    /// Whether every account signs with a key of its own, as the accounts of
+    /// `setup.accounts_file` and the accounts created with `run.rotate_signing_keys` do
+    pub fn accounts_have_own_keys(&self) -> bool {
+        !self.file_accounts.is_empty()
+            || self.environment.as_ref().is_some_and(|environment| {
+                environment.account_keys.len() == environment.accounts.len()
+            })
+    }
+
+    /// Fund and deploy the accounts at `indices`, adding them to `created` by index along
+    /// with their signed deployment when they are deployed on first use, and returning
+    /// the indices of the accounts that failed
+    ///
+    /// The funding transfers are sent as multicalls of [`FUNDING_BATCH_SIZE`] transfers, one
+    /// after the other so that the nonces of the funders follow each other, and are then all
+    /// waited for at once, as are the deployments
+    async fn create_account_batch(
+        &mut self,
+        indices: &[usize],
+        class_hash: FieldElement,
+        execution_encoding: ExecutionEncoding,
+        erc20_address: FieldElement,
+        erc20_funder: &StarknetAccount,
+        created: &mut BTreeMap<
+            usize,
+            (StarknetAccount, Option<BroadcastedDeployAccountTransaction>),
+        >,
+    ) -> Result<Vec<usize>> {
+        self.account.set_block_id(BlockId::Tag(BlockTag::Pending));
+
+        let fee_token_address = self.config.setup.fee_token_address;
+        let max_concurrent = self.config.report.max_concurrent_requests.max(1);
+
+        let mut failed = Vec::new();
+        let mut to_fund = Vec::new();
+
+        for &i in indices {
             let salt = self.config.deployer.salt + FieldElement::from(i);
+            // TODO: Check if OpenZepplinAccountFactory could be used with other type of accounts ? or should we require users to use OpenZepplinAccountFactory ?
+            let address = self
+                .created_account_factory(class_hash, i)
+                .await?
+                .deploy(salt)
+                .address();
 
-            let deploy = account_factory.deploy(salt).max_fee(MAX_FEE);
-            let address = deploy.address();
-            info!("Deploying account {i} with salt {salt} at address {address:#064x}");
+            let account = StarknetAccount::new(
+                self.starknet_rpc.clone(),
+                self.created_account_signer(i),
+                address,
+                self.config.setup.chain_id,
+                execution_encoding,
+                InvokeVersion::from_config(&self.config.run),
+            );
 
-            if let Ok(account_class_hash) = self
+            // Also catches a deployment of a previous attempt that was accepted after timing out
+            match self
                 .starknet_rpc
                 .get_class_hash_at(BlockId::Tag(BlockTag::Pending), address)
                 .await
             {
-                if account_class_hash == class_hash {
+                Ok(account_class_hash) if account_class_hash == class_hash => {
                     warn!("Account {i} already deployed at address {address:#064x}");
-                    let account = SingleOwnerAccount::new(
-                        self.starknet_rpc.clone(),
-                        signer.clone(),
-                        address,
-                        self.config.setup.chain_id,
-                        execution_encoding,
+                    created.insert(i, (account, None));
+                }
+                Ok(account_class_hash) => {
+                    warn!("Account {i} already deployed at address {address:#064x} with a different class hash {account_class_hash:#064x}, expected {class_hash:#064x}");
+                    failed.push(i);
+                }
+                Err(_) => to_fund.push((i, salt, account)),
+            }
+        }
+
+        let mut funding = Vec::new();
+
+        for batch in to_fund.chunks(FUNDING_BATCH_SIZE) {
+            let (first, last) = (batch[0].0, batch[batch.len() - 1].0);
+            info!("Funding accounts {first} to {last}");
+
+            let erc20_calls = batch
+                .iter()
+                .map(|(_, _, account)| {
+                    transfer_call(erc20_address, account.address(), ERC20_FUNDING_AMOUNT)
+                })
+                .collect();
+            let fee_calls = batch
+                .iter()
+                .flat_map(|(_, _, account)| {
+                    let address = account.address();
+
+                    std::iter::once(fee_token_address)
+                        .chain(self.strk_funding_address())
+                        .map(move |token| transfer_call(token, address, FEE_FUNDING_AMOUNT))
+                })
+                .collect();
+
+            let sent = async {
+                let erc20_tx_hash = self.execute(erc20_funder.clone(), erc20_calls).await?;
+                let fee_tx_hash = self.execute(self.account.clone(), fee_calls).await?;
+
+                Ok::<_, color_eyre::Report>([erc20_tx_hash, fee_tx_hash])
+            }
+            .await;
+
+            match sent {
+                Ok(tx_hashes) => funding.push((batch, tx_hashes)),
+                Err(err) => {
+                    warn!("Could not fund accounts {first} to {last}: {err:#}");
+                    failed.extend(batch.iter().map(|(i, _, _)| *i));
+                }
+            }
+        }
+
+        let shooter = &*self;
+        let funded: Vec<_> = futures::stream::iter(funding)
+            .map(|(batch, tx_hashes)| async move { (batch, shooter.wait_for_txs(tx_hashes).await) })
+            .buffer_unordered(max_concurrent)
+            .collect()
+            .await;
+
+        let mut to_deploy = Vec::new();
+
+        for (batch, result) in funded {
+            match result {
+                Ok(()) => to_deploy.extend(batch.iter().cloned()),
+                Err(err) => {
+                    let (first, last) = (batch[0].0, batch[batch.len() - 1].0);
+                    warn!("Funding of accounts {first} to {last} failed: {err:#}");
+                    failed.extend(batch.iter().map(|(i, _, _)| *i));
+                }
+            }
+        }
+
+        if self.config.setup.counterfactual_accounts {
+            for (i, salt, account) in to_deploy {
+                let account_factory = self.created_account_factory(class_hash, i).await?;
+                let deployment = self
+                    .sign_account_deployment(
+                        &account_factory,
+                        &self.created_account_signer(i),
+                        salt,
+                    )
+                    .await?;
+
+                info!(
+                    "Account {i} funded at address {:#064x}, it will be deployed on first use",
+                    account.address()
+                );
+                created.insert(i, (account, Some(deployment)));
+            }
+
+            failed.sort_unstable();
+            return Ok(failed);
+        }
+
+        let mut deployments = Vec::new();
+
+        // Every account deploys itself, the deployments don't depend on each other
+        for (i, salt, account) in to_deploy {
+            info!(
+                "Deploying account {i} with salt {salt} at address {:#064x}",
+                account.address()
+            );
+
+            let account_factory = self.created_account_factory(class_hash, i).await?;
+            match account_factory.deploy(salt).max_fee(MAX_FEE).send().await {
+                Ok(result) => deployments.push((i, account, result.transaction_hash)),
+                Err(err) => {
+                    warn!("Could not deploy account {i}: {err}");
+                    failed.push(i);
+                }
+            }
+        }
+
+        let shooter = &*self;
+        let deployed =
+            futures::stream::iter(deployments)
+                .map(|(i, account, tx_hash)| async move {
+                    (i, account, shooter.wait_for_tx(tx_hash).await)
+                })
+                .buffer_unordered(max_concurrent)
+                .collect::<Vec<_>>()
+                .await;
+
+        for (i, account, result) in deployed {
+            match result {
+                Ok(()) => {
+                    info!(
+                        "Account {i} deployed at address {:#064x}",
+                        account.address()
                     );
-                    deployed_accounts.push(account);
-                    continue;
-                } else {
-                    return Err(eyre!("Account {i} already deployed at address {address:#064x} with a different class hash {account_class_hash:#064x}, expected {class_hash:#064x}"));
+                    created.insert(i, (account, None));
+                }
+                Err(err) => {
+                    warn!("Deployment of account {i} failed: {err:#}");
+                    failed.push(i);
                 }
             }
+        }
 
-            info!("Funding account {i} at address {address:#064x}");
-            let tx_hash = self
-                .transfer(erc20_address, self.account.clone(), address, felt!("0xFFF"))
-                .await?;
-            wait_for_tx(&self.starknet_rpc, tx_hash, CHECK_INTERVAL).await?;
-            let tx_hash = self
-                .transfer(
-                    fee_token_address,
-                    self.account.clone(),
-                    address,
-                    felt!("0xFFFFFFFFFFFFFFFFFFFF"),
+        failed.sort_unstable();
+        Ok(failed)
+    }
+
+    /// Benchmark with the accounts of `setup.accounts_file`, which are already deployed and
+    /// hold the fee token, only an `erc20_address` deployed by this setup is sent to them
+    async fn use_file_accounts(
+        &mut self,
+        execution_encoding: ExecutionEncoding,
+        erc20_address: FieldElement,
+        erc20_funder: StarknetAccount,
+        fund_erc20: bool,
+    ) -> Result<Vec<StarknetAccount>> {
+        info!(
+            "Using the {} accounts of the accounts file",
+            self.file_accounts.len()
+        );
+
+        let accounts: Vec<StarknetAccount> = self
+            .file_accounts
+            .iter()
+            .map(|(address, signer)| {
+                StarknetAccount::new(
+                    self.starknet_rpc.clone(),
+                    GatlingSigner::Local(signer.clone()),
+                    *address,
+                    self.config.setup.chain_id,
+                    execution_encoding,
+                    InvokeVersion::from_config(&self.config.run),
                 )
-                .await?;
-            wait_for_tx(&self.starknet_rpc, tx_hash, CHECK_INTERVAL).await?;
+            })
+            .collect();
+
+        if fund_erc20 {
+            let mut tx_hashes = Vec::new();
+
+            for batch in accounts.chunks(FUNDING_BATCH_SIZE) {
+                let calls = batch
+                    .iter()
+                    .map(|account| {
+                        transfer_call(erc20_address, account.address(), ERC20_FUNDING_AMOUNT)
+                    })
+                    .collect();
+                tx_hashes.push(self.execute(erc20_funder.clone(), calls).await?);
+            }
 
-            let result = deploy.send().await?;
+            self.wait_for_txs(tx_hashes).await?;
+        }
 
-            let account = SingleOwnerAccount::new(
-                self.starknet_rpc.clone(),
-                signer.clone(),
-                result.contract_address,
+        Ok(accounts)
+    }
+
+    /// Generate `amount` fresh accounts, each with its own key, and fund their precomputed
+    /// addresses with the fee of their deployment, which is signed for the deploy account
+    /// benchmark to send
+    async fn create_benchmark_deployments(
+        &mut self,
+        class_hash: FieldElement,
+        amount: usize,
+    ) -> Result<Vec<BroadcastedDeployAccountTransaction>> {
+        if amount == 0 {
+            return Ok(Vec::new());
+        }
+
+        info!("Creating {amount} accounts to deploy during the benchmark");
+
+        let salt = self.config.deployer.salt;
+        let mut deployments = Vec::with_capacity(amount);
+        let mut addresses = Vec::with_capacity(amount);
+
+        for _ in 0..amount {
+            let signer = GatlingSigner::Local(LocalWallet::from(SigningKey::from_random()));
+            let public_key = signer.get_public_key().await?.scalar();
+
+            let account_factory = OpenZeppelinAccountFactory::new(
+                class_hash,
                 self.config.setup.chain_id,
-                execution_encoding,
+                &signer,
+                &self.starknet_rpc,
+            )
+            .await?;
+
+            addresses.push(compute_contract_address(salt, class_hash, &[public_key]));
+            deployments.push(
+                self.sign_account_deployment(&account_factory, &signer, salt)
+                    .await?,
             );
+        }
 
-            deployed_accounts.push(account);
+        // The deployment is validated against the balance of the account, which has to cover its max fee
+        let fee_token_address = self.config.setup.fee_token_address;
+        let mut tx_hashes = Vec::new();
 
-            wait_for_tx(&self.starknet_rpc, result.transaction_hash, CHECK_INTERVAL).await?;
+        for (i, batch) in addresses.chunks(FUNDING_BATCH_SIZE).enumerate() {
+            info!(
+                "Funding accounts {} to {} of the deploy account benchmark",
+                i * FUNDING_BATCH_SIZE,
+                i * FUNDING_BATCH_SIZE + batch.len() - 1
+            );
 
-            info!("Account {i} deployed at address {address:#064x}");
+            let calls = batch
+                .iter()
+                .map(|address| transfer_call(fee_token_address, *address, MAX_FEE))
+                .collect();
+            tx_hashes.push(self.execute(self.account.clone(), calls).await?);
         }
 
-        Ok(deployed_accounts)
+        self.wait_for_txs(tx_hashes).await?;
+
+        Ok(deployments)
+    }
+
+    /// Sign the deployment of an account without sending it, so it can be sent
+    /// by the goose user that will use the account
+    async fn sign_account_deployment(
+        &self,
+        account_factory: &impl AccountFactory,
+        signer: &GatlingSigner,
+        salt: FieldElement,
+    ) -> Result<BroadcastedDeployAccountTransaction> {
+        let prepared = account_factory
+            .deploy(salt)
+            .max_fee(MAX_FEE)
+            .nonce(FieldElement::ZERO)
+            .prepared()?;
+
+        let signature = signer.sign_hash(&prepared.transaction_hash()).await?;
+
+        Ok(BroadcastedDeployAccountTransaction::V1(
+            BroadcastedDeployAccountTransactionV1 {
+                max_fee: MAX_FEE,
+                signature: vec![signature.r, signature.s],
+                nonce: FieldElement::ZERO,
+                contract_address_salt: salt,
+                constructor_calldata: account_factory.calldata(),
+                class_hash: account_factory.class_hash(),
+                is_query: false,
+            },
+        ))
+    }
+
+    /// Class hash of `source` along with the fee of its declaration by the deployer,
+    /// which is `None` when the class is already declared
+    pub async fn estimate_declaration(
+        &self,
+        source: &ContractSourceConfig,
+    ) -> Result<(FieldElement, Option<u64>)> {
+        let (class_hash, estimate) = match source {
+            ContractSourceConfig::V0(path) => {
+                let file = std::fs::File::open(path)?;
+                let contract_artifact: LegacyContractClass = serde_json::from_reader(file)?;
+                let class_hash = contract_artifact.class_hash()?;
+
+                if self.check_already_declared(class_hash).await? {
+                    return Ok((class_hash, None));
+                }
+
+                let estimate = self
+                    .account
+                    .declare_legacy(Arc::new(contract_artifact))
+                    .estimate_fee()
+                    .await?;
+
+                (class_hash, estimate)
+            }
+            ContractSourceConfig::V1(config) => {
+                let file = std::fs::File::open(&config.path)?;
+                let contract_artifact: SierraClass = serde_json::from_reader(file)?;
+                let class_hash = contract_artifact.class_hash()?;
+
+                if self.check_already_declared(class_hash).await? {
+                    return Ok((class_hash, None));
+                }
+
+                let estimate = self
+                    .account
+                    .declare(
+                        Arc::new(contract_artifact.flatten()?),
+                        config.get_casm_hash()?,
+                    )
+                    .estimate_fee()
+                    .await?;
+
+                (class_hash, estimate)
+            }
+        };
+
+        Ok((class_hash, Some(overall_fee(&estimate)?)))
+    }
+
+    /// Fee of deploying an instance of `class_hash` like [`Self::deploy_contract`] does,
+    /// `None` when a previous setup already deployed it
+    pub async fn estimate_contract_deployment(
+        &self,
+        class_hash: FieldElement,
+        constructor_args: Vec<FieldElement>,
+    ) -> Result<Option<u64>> {
+        let address =
+            compute_contract_address(self.config.deployer.salt, class_hash, &constructor_args);
+
+        if self
+            .starknet_rpc
+            .get_class_hash_at(BlockId::Tag(BlockTag::Pending), address)
+            .await
+            .is_ok()
+        {
+            return Ok(None);
+        }
+
+        let estimate = ContractFactory::new(class_hash, self.account.clone())
+            .deploy(constructor_args, self.config.deployer.salt, false)
+            .estimate_fee()
+            .await?;
+
+        Ok(Some(overall_fee(&estimate)?))
+    }
+
+    /// Fee of a funding multicall of [`FUNDING_BATCH_SIZE`] fee token transfers from the deployer,
+    /// along with as many STRK transfers when the benchmarks send v3 invokes
+    pub async fn estimate_funding_batch(&self) -> Result<u64> {
+        let fee_token_address = self.config.setup.fee_token_address;
+        let strk_address = self.strk_funding_address();
+        let calls = (0..FUNDING_BATCH_SIZE)
+            .flat_map(|i| {
+                let recipient = FieldElement::from(i as u64 + 1);
+
+                std::iter::once(fee_token_address)
+                    .chain(strk_address)
+                    .map(move |token| transfer_call(token, recipient, FEE_FUNDING_AMOUNT))
+            })
+            .collect();
+
+        let estimate = self.account.execute(calls).estimate_fee().await?;
+
+        overall_fee(&estimate)
+    }
+
+    /// Fee of the deployment of the first account the setup creates,
+    /// `None` when a previous setup already deployed it
+    pub async fn estimate_account_deployment(
+        &self,
+        class_hash: FieldElement,
+    ) -> Result<Option<u64>> {
+        let salt = self.config.deployer.salt;
+        let account_factory = self.created_account_factory(class_hash, 0).await?;
+        let deployment = account_factory.deploy(salt);
+
+        if self
+            .starknet_rpc
+            .get_class_hash_at(BlockId::Tag(BlockTag::Pending), deployment.address())
+            .await
+            .is_ok()
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(overall_fee(&deployment.estimate_fee().await?)?))
+    }
+
+    /// Check that an account class validates its own deployment, which is
+    /// required for it to be deployed counterfactually
+    async fn check_counterfactual_support(&self, class_hash: FieldElement) -> Result<()> {
+        let validate_deploy = selector!("__validate_deploy__");
+
+        let supported = match self
+            .starknet_rpc
+            .get_class(BlockId::Tag(BlockTag::Pending), class_hash)
+            .await?
+        {
+            ContractClass::Sierra(class) => class
+                .entry_points_by_type
+                .external
+                .iter()
+                .any(|entry_point| entry_point.selector == validate_deploy),
+            ContractClass::Legacy(class) => class
+                .entry_points_by_type
+                .external
+                .iter()
+                .any(|entry_point| entry_point.selector == validate_deploy),
+        };
+
+        ensure!(
+            supported,
+            "Account class {class_hash:#064x} has no `__validate_deploy__` entry point and can't be deployed counterfactually"
+        );
+
+        Ok(())
     }
 
     async fn check_already_declared(&self, class_hash: FieldElement) -> Result<bool> {
@@ -411,14 +1779,12 @@ impl GatlingShooterSetup {
                 warn!("Contract already declared at {class_hash:#064x}");
                 Ok(true)
             }
-            Err(ProviderError::StarknetError(StarknetErrorWithMessage {
-                code: MaybeUnknownErrorCode::Known(StarknetError::ClassHashNotFound),
-                ..
-            })) => Ok(false),
+            Err(ProviderError::StarknetError(StarknetError::ClassHashNotFound)) => Ok(false),
             Err(err) => Err(eyre!(err)),
         }
     }
 
+    #[allow(clippy::extra_unused_lifetimes)]
     async fn declare_contract_legacy<'a>(
         &mut self,
         contract_path: impl AsRef<Path>,
@@ -451,7 +1817,7 @@ impl GatlingShooterSetup {
             .await
             .wrap_err("Could not declare contract")?;
 
-        wait_for_tx(&self.starknet_rpc, tx_resp.transaction_hash, CHECK_INTERVAL).await?;
+        self.wait_for_tx(tx_resp.transaction_hash).await?;
 
         self.nonces.insert(from_address, nonce + FieldElement::ONE);
 
@@ -463,6 +1829,7 @@ impl GatlingShooterSetup {
         Ok(tx_resp.class_hash)
     }
 
+    #[allow(clippy::extra_unused_lifetimes)]
     async fn declare_contract_v1<'a>(
         &mut self,
         contract_path: impl AsRef<Path>,
@@ -504,7 +1871,7 @@ impl GatlingShooterSetup {
             .await
             .wrap_err("Could not declare contract")?;
 
-        wait_for_tx(&self.starknet_rpc, tx_resp.transaction_hash, CHECK_INTERVAL).await?;
+        self.wait_for_tx(tx_resp.transaction_hash).await?;
 
         info!(
             "Contract declared successfully at {:#064x}",
@@ -516,6 +1883,22 @@ impl GatlingShooterSetup {
         Ok(tx_resp.class_hash)
     }
 
+    /// Class hash of a contract, `class_hash` when it is already declared
+    /// or the class of `source` once declared
+    async fn contract_class(
+        &mut self,
+        class_hash: Option<FieldElement>,
+        source: &ContractSourceConfig,
+    ) -> Result<FieldElement> {
+        match class_hash {
+            Some(class_hash) => {
+                info!("Using the already declared class {class_hash:#064x}");
+                Ok(class_hash)
+            }
+            None => self.declare_contract(source).await,
+        }
+    }
+
     async fn declare_contract(
         &mut self,
         contract_source: &crate::config::ContractSourceConfig,
@@ -530,11 +1913,147 @@ impl GatlingShooterSetup {
     }
 }
 
-/// Create a StarkNet RPC provider from a URL.
+/// Entry of `setup.accounts_file`, holding either the private key of the account
+/// or the path of its keystore, relative to the accounts file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountsFileEntry {
+    address: FieldElement,
+    private_key: Option<FieldElement>,
+    keystore: Option<PathBuf>,
+}
+
+/// Read the accounts of `setup.accounts_file` along with their signer
+fn read_accounts_file(
+    path: &Path,
+    keystore_password: Option<&str>,
+) -> Result<Vec<(FieldElement, LocalWallet)>> {
+    let file = std::fs::File::open(path)
+        .wrap_err_with(|| format!("Could not open accounts file {}", path.display()))?;
+    let entries: Vec<AccountsFileEntry> = serde_json::from_reader(file)
+        .wrap_err_with(|| format!("Could not parse accounts file {}", path.display()))?;
+
+    ensure!(
+        !entries.is_empty(),
+        "Accounts file {} should list at least one account",
+        path.display()
+    );
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let address = entry.address;
+            let signing_key = match (entry.private_key, entry.keystore) {
+                (Some(private_key), None) => SigningKey::from_secret_scalar(private_key),
+                (None, Some(keystore)) => {
+                    let keystore = path.parent().unwrap_or(Path::new("")).join(keystore);
+                    let password = keystore_password.ok_or(eyre!(
+                        "setup.keystore_password is needed to decrypt keystore {}",
+                        keystore.display()
+                    ))?;
+
+                    SigningKey::from_keystore(&keystore, password).wrap_err_with(|| {
+                        format!("Could not decrypt keystore {}", keystore.display())
+                    })?
+                }
+                _ => {
+                    return Err(eyre!(
+                        "Account {address:#064x} of accounts file {} should have either a private_key or a keystore",
+                        path.display()
+                    ))
+                }
+            };
+
+            Ok((address, LocalWallet::from(signing_key)))
+        })
+        .collect()
+}
+
+/// Call transferring `amount` of the ERC20 at `contract_address` to `recipient`
+fn transfer_call(
+    contract_address: FieldElement,
+    recipient: FieldElement,
+    amount: FieldElement,
+) -> Call {
+    let (amount_low, amount_high) = (amount, felt!("0"));
+
+    Call {
+        to: contract_address,
+        selector: selector!("transfer"),
+        calldata: vec![recipient, amount_low, amount_high],
+    }
+}
+
+/// Fee of an estimate, which the node gives as a felt
+pub fn overall_fee(estimate: &FeeEstimate) -> Result<u64> {
+    u64::try_from(estimate.overall_fee).map_err(|_| {
+        eyre!(
+            "Estimated fee {} doesn't fit in 64 bits",
+            estimate.overall_fee
+        )
+    })
+}
+
+/// `u256` balance returned by `balanceOf`, split in its `low` and `high` 128 bits
+struct Erc20Balance {
+    low: FieldElement,
+    high: FieldElement,
+}
+
+impl Erc20Balance {
+    /// Whether the balance is at least `amount`, which is below 2^128
+    fn covers(&self, amount: FieldElement) -> bool {
+        self.high != FieldElement::ZERO || self.low >= amount
+    }
+}
+
+impl std::fmt::Display for Erc20Balance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.high == FieldElement::ZERO {
+            write!(f, "{}", self.low)
+        } else {
+            write!(f, "{} * 2^128 + {}", self.high, self.low)
+        }
+    }
+}
+
+/// Chain id as its ASCII name along with its hex felt, or only the hex felt when it isn't a name
+fn chain_id_string(chain_id: FieldElement) -> String {
+    match parse_cairo_short_string(&chain_id) {
+        Ok(name) if !name.is_empty() => format!("{name} ({chain_id:#x})"),
+        _ => format!("{chain_id:#x}"),
+    }
+}
+
+/// WebSocket endpoint of the node at `url`, served on the same address
+fn websocket_url(mut url: Url) -> Result<Url> {
+    let scheme = match url.scheme() {
+        "https" => "wss",
+        _ => "ws",
+    };
+    url.set_scheme(scheme)
+        .map_err(|()| eyre!("Could not derive a WebSocket url from {url}"))?;
+
+    Ok(url)
+}
+
+/// Subscribe to the new blocks of the node at `url` over WebSocket, once checked that
+/// the node implements a version of the spec with subscriptions
+async fn subscribe_new_heads(url: Url) -> Result<NewHeads> {
+    let spec_version = spec_version(&url).await?;
+    ensure!(
+        supports_subscriptions(&spec_version),
+        "the node implements version {spec_version} of the JSON-RPC spec, subscriptions need 0.8 or above"
+    );
+
+    NewHeads::subscribe(&websocket_url(url)?).await
+}
+
+/// Create a StarkNet RPC provider from URLs.
 /// # Arguments
-/// * `rpc` - The URL of the StarkNet RPC provider.
+/// * `urls` - The URLs of the StarkNet RPC providers, failed over in order.
 /// # Returns
 /// A StarkNet RPC provider.
-fn starknet_rpc_provider(rpc: Url) -> JsonRpcClient<HttpTransport> {
-    JsonRpcClient::new(HttpTransport::new(rpc))
+fn starknet_rpc_provider(urls: impl IntoIterator<Item = Url>) -> StarknetRpc {
+    JsonRpcClient::new(FailoverTransport::new(urls))
 }
@@ -0,0 +1,118 @@
+use std::{error::Error, fmt, time::Duration};
+
+use async_trait::async_trait;
+use color_eyre::{eyre::bail, Result};
+use serde_derive::{Deserialize, Serialize};
+use starknet::{
+    core::{crypto::Signature, types::FieldElement},
+    signers::{local_wallet, Infallible, LocalWallet, Signer, SigningKey, VerifyingKey},
+};
+use url::Url;
+
+use crate::config::{DeployerConfig, RemoteSignerConfig};
+
+/// Longest the remote signer has to answer a signing request
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Signer of the deployer, either holding the key or forwarding the hashes
+/// to sign to a remote signing service
+#[derive(Debug, Clone)]
+pub enum GatlingSigner {
+    Local(LocalWallet),
+    Remote(RemoteSigner),
+}
+
+impl GatlingSigner {
+    pub fn from_config(deployer: &DeployerConfig) -> Result<Self> {
+        match (&deployer.remote_signer, deployer.signing_key) {
+            (Some(remote_signer), None) => Ok(Self::Remote(RemoteSigner::new(remote_signer)?)),
+            (None, Some(signing_key)) => Ok(Self::Local(LocalWallet::from(
+                SigningKey::from_secret_scalar(signing_key),
+            ))),
+            _ => bail!("deployer should have either a signing_key or a remote_signer"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SignError {
+    Local(local_wallet::SignError),
+    Remote(reqwest::Error),
+}
+
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local(err) => write!(f, "{err}"),
+            Self::Remote(err) => write!(f, "Remote signer failed: {err}"),
+        }
+    }
+}
+
+impl Error for SignError {}
+
+#[async_trait]
+impl Signer for GatlingSigner {
+    type GetPublicKeyError = Infallible;
+    type SignError = SignError;
+
+    async fn get_public_key(&self) -> Result<VerifyingKey, Self::GetPublicKeyError> {
+        match self {
+            Self::Local(wallet) => wallet.get_public_key().await,
+            Self::Remote(remote) => Ok(VerifyingKey::from_scalar(remote.public_key)),
+        }
+    }
+
+    async fn sign_hash(&self, hash: &FieldElement) -> Result<Signature, Self::SignError> {
+        match self {
+            Self::Local(wallet) => wallet.sign_hash(hash).await.map_err(SignError::Local),
+            Self::Remote(remote) => remote.sign_hash(hash).await.map_err(SignError::Remote),
+        }
+    }
+}
+
+/// Signs by posting `{"hash": "0x..."}` to a signing service, which answers with
+/// `{"r": "0x...", "s": "0x..."}`, the key never leaving the service
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    client: reqwest::Client,
+    url: Url,
+    public_key: FieldElement,
+}
+
+#[derive(Serialize)]
+struct SignRequest<'a> {
+    hash: &'a FieldElement,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    r: FieldElement,
+    s: FieldElement,
+}
+
+impl RemoteSigner {
+    fn new(config: &RemoteSignerConfig) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(REMOTE_SIGNER_TIMEOUT)
+                .build()?,
+            url: Url::parse(&config.url)?,
+            public_key: config.public_key,
+        })
+    }
+
+    async fn sign_hash(&self, hash: &FieldElement) -> Result<Signature, reqwest::Error> {
+        let SignResponse { r, s } = self
+            .client
+            .post(self.url.clone())
+            .json(&SignRequest { hash })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(Signature { r, s })
+    }
+}
@@ -0,0 +1,46 @@
+use std::{fs::File, io::Write, path::Path};
+
+use color_eyre::Result;
+
+/// `report.output_location` of a report written to stdout
+pub const STDOUT: &str = "-";
+
+pub fn is_stdout(path: &Path) -> bool {
+    path.as_os_str() == STDOUT
+}
+
+/// Stdout of the process put aside for the report
+///
+/// The logs already go to stderr, but goose prints to stdout while attacking, so stdout is
+/// pointed at stderr for the rest of the run and only the report is written to the original one
+pub struct ReportStdout(File);
+
+impl ReportStdout {
+    #[cfg(unix)]
+    pub fn take() -> Result<Self> {
+        use color_eyre::eyre::Context;
+
+        std::io::stdout().flush()?;
+
+        let stdout = nix::unistd::dup(std::io::stdout()).wrap_err("Could not duplicate stdout")?;
+        nix::unistd::dup2_stdout(std::io::stderr())
+            .wrap_err("Could not redirect stdout to stderr")?;
+
+        Ok(Self(File::from(stdout)))
+    }
+
+    #[cfg(not(unix))]
+    pub fn take() -> Result<Self> {
+        color_eyre::eyre::bail!("Writing the report to stdout is only supported on unix")
+    }
+}
+
+impl Write for ReportStdout {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
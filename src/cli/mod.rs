@@ -1,6 +1,8 @@
 //! Defines the CLI commands.
 
 // Imports
+use std::path::PathBuf;
+
 use clap::{Args, Parser, Subcommand};
 
 const VERSION_STRING: &str = env!("CARGO_PKG_VERSION");
@@ -26,7 +28,53 @@ pub struct Cli {
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// Trigger a load test.
-    Shoot {},
+    Shoot {
+        /// State file saved by `setup`, skips the setup phase when provided.
+        #[clap(long)]
+        state: Option<PathBuf>,
+        /// File where to save the progress of the run.
+        #[clap(long)]
+        checkpoint: Option<PathBuf>,
+        /// Resume an interrupted run from its checkpoint, using the same setup state.
+        #[clap(long, requires_all = ["checkpoint", "state"])]
+        resume: bool,
+        /// Only print the fees the run would cost, estimated without sending any transaction; the setup without `--state`, the benchmarks with it.
+        #[clap(long, conflicts_with_all = ["checkpoint", "resume"])]
+        dry_run: bool,
+        /// Only run these scenarios, by name (erc20, erc721, approvals, deployments, mixed or the selector of a custom call), instead of all the configured ones.
+        #[clap(long, value_delimiter = ',')]
+        only: Vec<String>,
+        /// Leave out these scenarios, named like for `--only`.
+        #[clap(long, value_delimiter = ',')]
+        skip: Vec<String>,
+        /// Don't log the progress of the benchmarks, for CI logs.
+        #[clap(long)]
+        quiet: bool,
+    },
+    /// Run the setup phase only and save the resulting state.
+    Setup {
+        /// Path to the file where to save the setup state.
+        #[clap(long)]
+        state: PathBuf,
+    },
+    /// Send again the invokes recorded by a previous run with `report.record_file`.
+    Replay {
+        /// File the invokes were recorded to.
+        file: PathBuf,
+        /// State file saved by `setup`, skips the setup phase when provided.
+        #[clap(long)]
+        state: Option<PathBuf>,
+    },
+    /// Compare the JSON reports of two runs, failing when the new one regressed.
+    Compare {
+        /// Report of the run to compare against.
+        old: PathBuf,
+        /// Report of the run to compare.
+        new: PathBuf,
+        /// Percentage a metric can get worse by before it is a regression.
+        #[clap(long, default_value_t = 5.0)]
+        threshold: f64,
+    },
 }
 
 #[derive(Debug, Args)]
@@ -1,16 +1,21 @@
 //! General configuration
 
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{bail, ensure, Result};
 use config::{builder::DefaultState, Config, ConfigBuilder, File};
 
 use serde::de::Error as DeError;
 use serde::Deserialize;
+use starknet::accounts::Call;
 use starknet::core::{
     types::{contract::CompiledClass, FieldElement},
-    utils::{cairo_short_string_to_felt, CairoShortStringToFeltError},
+    utils::{cairo_short_string_to_felt, get_selector_from_name, CairoShortStringToFeltError},
 };
+use url::Url;
 
 /// Configuration for the application.
 #[derive(Debug, Deserialize, Clone)]
@@ -30,15 +35,72 @@ pub struct GatlingConfig {
 #[derive(Debug, Deserialize, Clone)]
 #[allow(unused)]
 pub struct RpcConfig {
-    pub url: String,
+    /// Nodes to send the requests to, failing over to the next one when a node is unreachable
+    pub url: RpcUrls,
+    /// Wait for the node to produce blocks before doing anything else
+    pub startup_grace_period: Option<StartupGracePeriod>,
+    /// Times a request fetching blocks, receipts or the block number is retried
+    /// after a transient error
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Wait for transactions on a subscription to the new blocks of the WebSocket endpoint
+    /// of the first url instead of polling them
+    #[serde(default)]
+    pub use_websocket: bool,
+    /// Abort at startup when the node reports another chain id, either as a hex felt or its ASCII name
+    #[serde(default, deserialize_with = "chain_id_deserializer")]
+    pub expected_chain_id: Option<FieldElement>,
 }
 
 impl Default for RpcConfig {
     fn default() -> Self {
         Self {
-            url: "http://localhost:9944".to_string(),
+            url: RpcUrls::Single("http://localhost:9944".to_string()),
+            startup_grace_period: None,
+            max_retries: default_max_retries(),
+            use_websocket: false,
+            expected_chain_id: None,
+        }
+    }
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+/// Either a single RPC url or a list of them
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RpcUrls {
+    Single(String),
+    List(Vec<String>),
+}
+
+impl RpcUrls {
+    pub fn all(&self) -> &[String] {
+        match self {
+            Self::Single(url) => std::slice::from_ref(url),
+            Self::List(urls) => urls,
         }
     }
+
+    /// The url tried first, the one the goose users send their requests to
+    pub fn primary(&self) -> Option<&str> {
+        self.all().first().map(String::as_str)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct StartupGracePeriod {
+    /// Seconds to wait for at most
+    #[serde(default = "default_startup_timeout")]
+    pub timeout: u64,
+    /// Block number to wait for instead of only waiting for the block number to advance
+    pub target_block: Option<u64>,
+}
+
+fn default_startup_timeout() -> u64 {
+    120
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -89,29 +151,595 @@ pub struct SetupConfig {
     pub erc721_contract: ContractSourceConfig,
     pub account_contract: ContractSourceConfig,
     pub fee_token_address: FieldElement,
+    /// Contract address of STRK, the fee token of the v3 invokes, the accounts are also
+    /// funded with it when `run.tx_version` is `v3`
+    pub strk_fee_token_address: Option<FieldElement>,
     #[serde(deserialize_with = "from_str_deserializer")]
     pub chain_id: FieldElement,
+    /// Only fund the accounts during setup and have each goose user deploy
+    /// its account on first use
+    #[serde(default)]
+    pub counterfactual_accounts: bool,
+    /// How many more times to fund and deploy an account after its transactions failed
+    #[serde(default = "default_account_retries")]
+    pub account_retries: u32,
+    /// Least amount of accounts to go on with when some could not be created, all of them by default
+    pub min_accounts: Option<u64>,
+    /// Already declared class to deploy the ERC20 from instead of declaring `erc20_contract`
+    pub erc20_class_hash: Option<FieldElement>,
+    /// Already declared class to deploy the ERC721 from instead of declaring `erc721_contract`
+    pub erc721_class_hash: Option<FieldElement>,
+    /// Contract declared and deployed during setup, for the custom calls without a contract address
+    pub declare_contract: Option<DeclareContractConfig>,
+    /// Already deployed ERC20 contract to benchmark instead of deploying `erc20_contract`,
+    /// meant for chains forked from a live network
+    pub existing_erc20: Option<ExistingErc20Config>,
+    /// JSON list of already deployed and funded accounts to benchmark with instead of creating them
+    pub accounts_file: Option<PathBuf>,
+    /// Password of the keystores listed in `accounts_file`
+    pub keystore_password: Option<String>,
+}
+
+fn default_account_retries() -> u32 {
+    2
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeclareContractConfig {
+    #[serde(flatten)]
+    pub source: ContractSourceConfigV1,
+    #[serde(default)]
+    pub constructor_calldata: Vec<FieldElement>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExistingErc20Config {
+    pub address: FieldElement,
+    /// Account holding enough of the token to fund the benchmark accounts
+    pub whale: WhaleConfig,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WhaleConfig {
+    pub address: FieldElement,
+    /// Signing key of the whale, any key works on forks running without validation
+    pub signing_key: FieldElement,
+    #[serde(default)]
+    pub legacy_account: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct DeployerConfig {
     pub salt: FieldElement,
     pub address: FieldElement,
-    pub signing_key: FieldElement,
+    pub signing_key: Option<FieldElement>,
+    /// Service signing for the deployer instead of `signing_key`, such as in front of an HSM
+    pub remote_signer: Option<RemoteSignerConfig>,
     pub legacy_account: bool,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct RemoteSignerConfig {
+    pub url: String,
+    /// Public key of the signing key held by the service
+    pub public_key: FieldElement,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct RunConfig {
     pub num_erc20_transfers: u64,
     pub num_erc721_mints: u64,
+    /// Transfers or mints packed into every transaction of the erc20 and erc721 benchmarks
+    #[serde(default = "default_calls_per_tx")]
+    pub calls_per_tx: u64,
+    /// Amount of erc20 approval and `transferFrom` transactions, every approval is followed
+    /// by a `transferFrom` from another account spending it
+    #[serde(default)]
+    pub num_erc20_approvals: u64,
+    /// Amount of deployments of fresh accounts, funded during setup
+    #[serde(default)]
+    pub num_account_deployments: u64,
+    /// Invocations of arbitrary contracts, each benchmarked on its own after the other workloads
+    #[serde(default)]
+    pub custom_calls: Vec<CustomCall>,
+    /// Workloads sent together in a single benchmark after the custom calls
+    pub mixed: Option<MixedWorkload>,
     pub concurrency: u64,
+    /// Benchmark accounts created and funded during setup, `concurrency` when missing
+    pub accounts: Option<u64>,
+    /// Let users share the accounts when there are fewer accounts than users,
+    /// the transactions of an account are then submitted one at a time
+    #[serde(default)]
+    pub share_accounts: bool,
+    /// Seconds over which the users are started linearly, `0` to start them all at once
+    #[serde(default)]
+    pub ramp_up_secs: u64,
+    /// Seconds to wait for at most for a transaction to be accepted
+    #[serde(default = "default_tx_timeout_secs")]
+    pub tx_timeout_secs: u64,
+    /// Milliseconds between the first polls for a transaction to be accepted, the polling
+    /// backs off from there
+    #[serde(default = "default_confirmation_interval_ms")]
+    pub confirmation_interval_ms: u64,
+    /// Longest milliseconds between two polls for a transaction to be accepted, which the
+    /// polling backs off to from `confirmation_interval_ms`
+    #[serde(default = "default_max_confirmation_interval_ms")]
+    pub max_confirmation_interval_ms: u64,
+    /// Seconds to wait for at most after a benchmark for its transactions to be included,
+    /// before measuring its blocks, `0` to measure them right away
+    #[serde(default)]
+    pub cooldown_secs: u64,
+    /// Seconds to send unmeasured erc20 transfers for before the benchmarks, `0` to skip the warmup
+    #[serde(default)]
+    pub warmup_duration_secs: u64,
+    /// Seconds after which the run is stopped like by a Ctrl-C, writing a partial report
+    pub max_duration_secs: Option<u64>,
+    /// Seconds to send the erc20 transfers, the erc721 mints and the mixed workload for, each,
+    /// instead of a count of them, exclusive with their counts
+    pub duration_secs: Option<u64>,
+    /// Workloads left out of `duration_secs` by [`RunConfig::select_scenarios`]
+    #[serde(skip)]
+    pub skipped_durations: Vec<&'static str>,
+    /// Fail the run, once the report is written, when the measured TPS is below this
+    pub min_tps: Option<f64>,
+    /// Which TPS `min_tps` is checked against
+    #[serde(default)]
+    pub min_tps_definition: TpsDefinition,
+    /// Segments of target TPS the transactions are paced by, sent as fast as possible when empty
+    #[serde(default)]
+    pub load_profile: Vec<LoadSegment>,
+    /// Steady TPS the transactions are paced at, instead of a load profile
+    pub target_tps: Option<u64>,
+    /// How the erc20 transfers are spread over the accounts
+    #[serde(default)]
+    pub account_selection: AccountSelection,
+    /// Share of the erc20 transfers resubmitted as is right after being accepted
+    #[serde(default)]
+    pub duplicate_rate: f64,
+    /// Deprecated alias of `rpc.expected_chain_id`
+    #[serde(default, deserialize_with = "chain_id_deserializer")]
+    pub expected_chain_id: Option<FieldElement>,
+    /// Check that the node is healthy between benchmarks, aborting the run when it isn't
+    pub health_probe: Option<HealthProbeConfig>,
+    /// Send every erc20 transfer from the next account in turn, the created accounts
+    /// then get a key of their own
+    #[serde(default)]
+    pub rotate_signing_keys: bool,
+    /// Mint every erc721 token to a new address instead of the account of the user
+    #[serde(default)]
+    pub erc721_unique_recipients: bool,
+    /// Version of the invoke transactions sent by the benchmarks
+    #[serde(default)]
+    pub tx_version: TxVersion,
+    /// Resource bounds of the v3 invokes, only with a `v3` `tx_version`
+    pub resource_bounds: Option<ResourceBoundsConfig>,
+    /// Tip of the v3 invokes, only with a `v3` `tx_version`
+    pub tip: Option<u64>,
+    /// Seed of the random choices made during the run, picked at random when missing
+    pub seed: Option<u64>,
+}
+
+/// Names of the built-in scenarios to select on the command line,
+/// the custom calls are selected by their selector
+pub const SCENARIOS: [&str; 5] = ["erc20", "erc721", "approvals", "deployments", "mixed"];
+
+impl RunConfig {
+    pub fn num_accounts(&self) -> u64 {
+        self.accounts.unwrap_or(self.concurrency)
+    }
+
+    /// Seconds to send the transactions of `scenario` for, `None` when they are counted
+    pub fn duration_of(&self, scenario: &str) -> Option<u64> {
+        self.duration_secs
+            .filter(|_| !self.skipped_durations.contains(&scenario))
+    }
+
+    pub fn confirmation_interval(&self) -> Duration {
+        Duration::from_millis(self.confirmation_interval_ms)
+    }
+
+    pub fn max_confirmation_interval(&self) -> Duration {
+        Duration::from_millis(self.max_confirmation_interval_ms)
+    }
+
+    /// Run only the scenarios in `only`, all of them when it is empty, leaving out the ones
+    /// in `skip` by setting their amount to `0`, and their duration or mix to `None`
+    pub fn select_scenarios(&mut self, only: &[String], skip: &[String]) -> Result<()> {
+        let known: Vec<String> = SCENARIOS
+            .iter()
+            .map(|&scenario| scenario.to_owned())
+            .chain(self.custom_calls.iter().map(|call| call.selector.clone()))
+            .collect();
+
+        for name in only.iter().chain(skip) {
+            ensure!(
+                known.contains(name),
+                "Unknown scenario `{name}`, expected one of {}",
+                known.join(", ")
+            );
+        }
+
+        let selected = |name: &str| {
+            (only.is_empty() || only.iter().any(|only| only == name))
+                && !skip.iter().any(|skip| skip == name)
+        };
+
+        for (scenario, amount) in SCENARIOS.into_iter().zip([
+            &mut self.num_erc20_transfers,
+            &mut self.num_erc721_mints,
+            &mut self.num_erc20_approvals,
+            &mut self.num_account_deployments,
+        ]) {
+            if !selected(scenario) {
+                *amount = 0;
+            }
+        }
+        for scenario in [SCENARIOS[0], SCENARIOS[1]] {
+            if !selected(scenario) {
+                self.skipped_durations.push(scenario);
+            }
+        }
+        if !selected(SCENARIOS[4]) {
+            self.mixed = None;
+        }
+        for custom_call in &mut self.custom_calls {
+            if !selected(&custom_call.selector) {
+                custom_call.count = 0;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Invocation of a contract benchmarked `count` times, sent from the benchmark accounts
+#[derive(Debug, Deserialize, Clone)]
+pub struct CustomCall {
+    /// Contract to call, the instance of `setup.declare_contract` when missing
+    pub contract_address: Option<FieldElement>,
+    /// Name of the entry point, or its selector as a hex felt
+    pub selector: String,
+    #[serde(default)]
+    pub calldata: Vec<FieldElement>,
+    pub count: u64,
+}
+
+impl CustomCall {
+    pub fn selector(&self) -> Result<FieldElement> {
+        if self.selector.starts_with("0x") {
+            return Ok(FieldElement::from_hex_be(&self.selector)?);
+        }
+
+        Ok(get_selector_from_name(&self.selector)?)
+    }
+
+    /// Call of the entry point, on `declared_contract` when there is no `contract_address`
+    pub fn call(&self, declared_contract: Option<FieldElement>) -> Result<Call> {
+        let Some(to) = self.contract_address.or(declared_contract) else {
+            bail!(
+                "Custom call {} has no contract_address and no contract was deployed from setup.declare_contract",
+                self.selector
+            );
+        };
+
+        Ok(Call {
+            to,
+            selector: self.selector()?,
+            calldata: self.calldata.clone(),
+        })
+    }
+}
+
+/// Workloads sent by the same users in a single benchmark, every iteration of a user sending
+/// each of them as many times as its weight
+#[derive(Debug, Deserialize, Clone)]
+pub struct MixedWorkload {
+    /// Transactions of the benchmark, over all of its workloads
+    pub num_transactions: u64,
+    pub weights: Vec<WorkloadWeight>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WorkloadWeight {
+    /// `erc20`, `erc721` or the selector of one of the custom calls
+    pub scenario: String,
+    pub weight: u64,
+}
+
+impl MixedWorkload {
+    /// Weights reduced by their greatest common divisor, so that an iteration is as short as
+    /// the proportions allow
+    pub fn reduced_weights(&self) -> Vec<(&str, u64)> {
+        let divisor = self
+            .weights
+            .iter()
+            .fold(0, |divisor, weight| gcd(divisor, weight.weight))
+            .max(1);
+
+        self.weights
+            .iter()
+            .map(|weight| (weight.scenario.as_str(), weight.weight / divisor))
+            .collect()
+    }
+
+    /// Transactions sent by a user at every iteration
+    pub fn iteration_transactions(&self) -> u64 {
+        self.reduced_weights()
+            .iter()
+            .map(|(_, weight)| weight)
+            .sum()
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        b => gcd(b, a % b),
+    }
+}
+
+fn default_calls_per_tx() -> u64 {
+    1
+}
+
+fn default_tx_timeout_secs() -> u64 {
+    60
+}
+
+fn default_confirmation_interval_ms() -> u64 {
+    500
+}
+
+fn default_max_confirmation_interval_ms() -> u64 {
+    5000
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct HealthProbeConfig {
+    /// Seconds to wait for at most for a block to be produced and the mempool to be drained
+    #[serde(default = "default_health_probe_timeout")]
+    pub timeout: u64,
+}
+
+fn default_health_probe_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountSelection {
+    /// Every user sends from its own account
+    #[default]
+    Uniform,
+    /// The accounts are shared by all the users and every transfer is sent from
+    /// an account picked following a Zipf distribution, the n-th account
+    /// being picked with a weight of `1 / n^exponent`
+    Zipf { exponent: f64 },
+}
+
+/// Part of a load profile, holding `target_tps` for `duration` seconds
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct LoadSegment {
+    pub duration: u64,
+    pub target_tps: f64,
+    /// Go linearly from the target of the previous segment to `target_tps`
+    /// instead of switching to it at once
+    #[serde(default)]
+    pub ramp: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TxVersion {
+    /// Invokes paying their fee in the fee token, up to a max fee
+    #[default]
+    V1,
+    /// Invokes paying their fee in STRK, within resource bounds
+    V3,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct ResourceBoundsConfig {
+    /// Most L1 gas a v3 invoke can use
+    #[serde(default = "default_max_l1_gas")]
+    pub max_amount: u64,
+    /// Highest price a v3 invoke pays for a unit of L1 gas, in fri
+    #[serde(default = "default_max_l1_gas_price")]
+    pub max_price_per_unit: u64,
+}
+
+impl Default for ResourceBoundsConfig {
+    fn default() -> Self {
+        Self {
+            max_amount: default_max_l1_gas(),
+            max_price_per_unit: default_max_l1_gas_price(),
+        }
+    }
+}
+
+fn default_max_l1_gas() -> u64 {
+    100_000
+}
+
+fn default_max_l1_gas_price() -> u64 {
+    100_000_000_000_000
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TpsDefinition {
+    /// Transactions included per second, from the blocks produced during the run
+    #[default]
+    OnChain,
+    /// Transactions accepted by the node per second, as seen by the client
+    Client,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum UopsBreakdown {
+    /// By the address of the invoked contract
+    Contract,
+    /// By the selector of the invoked entrypoint
+    Selector,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ReportConfig {
     pub num_blocks: u64,
     pub output_location: PathBuf,
+    /// Files to write the report to, next to `output_location`
+    #[serde(default)]
+    pub format: ReportFormats,
+    /// Node to compute the report from instead of `rpc.url`, such as a single node behind a load balancer
+    pub verification_rpc_url: Option<String>,
+    /// How many of the submitted transactions missing from the measured blocks
+    /// to look up in order to detect the ones dropped by the node
+    #[serde(default = "default_dropped_sample_size")]
+    pub dropped_sample_size: usize,
+    /// How many transaction receipts to fetch concurrently for the report
+    #[serde(default = "default_receipt_batch_size")]
+    pub receipt_batch_size: usize,
+    /// How many blocks to fetch, or transactions to wait for, concurrently
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Also measure the transactions already in the pending block once a benchmark is over
+    #[serde(default)]
+    pub include_pending_block: bool,
+    /// Percentiles of the submission and verification times to report, from 0 to 100
+    #[serde(default = "default_percentiles")]
+    pub percentiles: Vec<f64>,
+    /// Also compute throughput over the transactions sent by the benchmark accounts only
+    #[serde(default)]
+    pub own_traffic_metrics: bool,
+    /// Correlate the fee paid by the transactions with their time-to-inclusion
+    #[serde(default)]
+    pub fee_correlation: bool,
+    /// Report the total and average fee paid by the transactions of the measured blocks
+    #[serde(default)]
+    pub fee_metrics: bool,
+    /// Fees in wei the fee histogram is bucketed by, with `fee_metrics`
+    #[serde(default)]
+    pub fee_buckets: Vec<u64>,
+    /// Also break the UOPS down by the target of the first call of every invoke
+    pub uops_breakdown: Option<UopsBreakdown>,
+    /// Share of the transactions of a benchmark that should be confirmed, warning about it otherwise
+    #[serde(default = "default_min_confirmation_rate")]
+    pub min_confirmation_rate: f64,
+    /// Time every transaction from its submission to its receipt being found,
+    /// polling the receipts of the in-flight transactions meanwhile
+    #[serde(default)]
+    pub inclusion_times: bool,
+    /// InfluxDB `write` endpoint to post the report to as line protocol
+    pub influx_url: Option<String>,
+    /// Token sent to `influx_url`, for InfluxDB 2 and above
+    pub influx_token: Option<String>,
+    /// Shell command run once the report is written, `{report}` is replaced by the report path
+    pub post_hook: Option<String>,
+    /// Prometheus pushgateway endpoint to post the main metrics to, labeled by scenario
+    pub pushgateway_url: Option<String>,
+    /// How many of the failed requests to keep the raw JSON-RPC exchange of
+    #[serde(default)]
+    pub capture_failures: usize,
+    /// Prometheus endpoint of the node to sample during the run
+    pub node_metrics: Option<NodeMetricsConfig>,
+    /// Decimal places to round the floating point numbers of the JSON report to
+    pub precision: Option<u32>,
+    /// File to append the events of the run to as NDJSON, `-` for stdout
+    pub events: Option<PathBuf>,
+    /// File to also append the logs to as JSON lines
+    pub log_file: Option<PathBuf>,
+    /// JSON array of the reports of the past runs to append the report of this run to
+    pub history_file: Option<PathBuf>,
+    /// File to write the invokes of the benchmarks to as JSON lines, for `replay` to send them again
+    pub record_file: Option<PathBuf>,
+    /// Seconds between two counts of the transactions of the pending block, `0` to never count them
+    #[serde(default)]
+    pub mempool_interval_secs: u64,
+    /// Seconds between two logs of the progress of a benchmark, `0` to never log it
+    #[serde(default = "default_progress_interval_secs")]
+    pub progress_interval_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    Json,
+    /// A row per benchmark and a column per metric
+    Csv,
+    /// Both `json` and `csv`
+    Both,
+    /// A self-contained page meant to be shared
+    Html,
+    /// InfluxDB line protocol, a point per benchmark
+    Influx,
+}
+
+/// Either a single report format or a list of them
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ReportFormats {
+    Single(ReportFormat),
+    List(Vec<ReportFormat>),
+}
+
+impl Default for ReportFormats {
+    fn default() -> Self {
+        Self::Single(ReportFormat::Json)
+    }
+}
+
+impl ReportFormats {
+    pub fn writes(&self, format: ReportFormat) -> bool {
+        let formats = match self {
+            Self::Single(format) => std::slice::from_ref(format),
+            Self::List(formats) => formats,
+        };
+
+        formats.iter().any(|written| {
+            *written == format
+                || *written == ReportFormat::Both
+                    && matches!(format, ReportFormat::Json | ReportFormat::Csv)
+        })
+    }
+}
+
+fn default_dropped_sample_size() -> usize {
+    100
+}
+
+fn default_receipt_batch_size() -> usize {
+    20
+}
+
+fn default_max_concurrent_requests() -> usize {
+    50
+}
+
+fn default_percentiles() -> Vec<f64> {
+    vec![50.0, 90.0]
+}
+
+fn default_min_confirmation_rate() -> f64 {
+    0.9
+}
+
+fn default_progress_interval_secs() -> u64 {
+    5
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NodeMetricsConfig {
+    pub url: String,
+    /// Seconds between two scrapes
+    #[serde(default = "default_node_metrics_interval")]
+    pub interval: u64,
+    /// Series to keep, either a metric name or a series with its labels
+    pub series: Vec<String>,
+}
+
+fn default_node_metrics_interval() -> u64 {
+    5
 }
 
 impl GatlingConfig {
@@ -133,6 +761,291 @@ impl GatlingConfig {
             .try_deserialize()
             .map_err(|e| e.into())
     }
+
+    /// Chain id the node should report, from `rpc.expected_chain_id` or its deprecated
+    /// `run.expected_chain_id` alias
+    pub fn expected_chain_id(&self) -> Option<FieldElement> {
+        self.rpc.expected_chain_id.or(self.run.expected_chain_id)
+    }
+
+    /// Check the invariants of the configuration before anything is sent to the node,
+    /// failing with every problem found instead of only the first one
+    ///
+    /// The directory of `report.output_location` is only checked to be creatable,
+    /// so that the report can't fail to be written once the run is over
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        let run = &self.run;
+        let runs_workloads = run.num_erc20_transfers != 0
+            || run.num_erc721_mints != 0
+            || run.num_erc20_approvals != 0
+            || run.num_account_deployments != 0
+            || run
+                .custom_calls
+                .iter()
+                .any(|custom_call| custom_call.count != 0)
+            || run.duration_secs.is_some()
+            || run
+                .mixed
+                .as_ref()
+                .is_some_and(|mixed| mixed.num_transactions != 0)
+            || run.warmup_duration_secs != 0;
+
+        if runs_workloads && run.concurrency == 0 {
+            problems.push("run.concurrency should be at least 1 to send transactions".to_owned());
+        }
+
+        // Every measured block of the run holds at least one of its transactions, a run bounded
+        // by a duration sends as many as it can instead
+        if run.duration_secs.is_none() && run.warmup_duration_secs == 0 {
+            let num_transactions = run.num_erc20_transfers
+                + run.num_erc721_mints
+                + run.num_erc20_approvals
+                + run.num_account_deployments
+                + run
+                    .custom_calls
+                    .iter()
+                    .map(|custom_call| custom_call.count)
+                    .sum::<u64>()
+                + run.mixed.as_ref().map_or(0, |mixed| mixed.num_transactions);
+
+            if self.report.num_blocks > num_transactions {
+                problems.push(format!(
+                    "report.num_blocks is {} but the run only sends {num_transactions} transactions, \
+                    which can't produce that many blocks",
+                    self.report.num_blocks
+                ));
+            }
+        }
+
+        if run.max_duration_secs == Some(0) {
+            problems.push("run.max_duration_secs should be at least 1".to_owned());
+        }
+
+        match run.duration_secs {
+            Some(0) => problems.push("run.duration_secs should be at least 1".to_owned()),
+            Some(_) => {
+                let counts = [
+                    ("run.num_erc20_transfers", run.num_erc20_transfers),
+                    ("run.num_erc721_mints", run.num_erc721_mints),
+                    (
+                        "run.mixed.num_transactions",
+                        run.mixed.as_ref().map_or(0, |mixed| mixed.num_transactions),
+                    ),
+                ];
+
+                for (name, count) in counts {
+                    if count != 0 {
+                        problems.push(format!(
+                            "run.duration_secs and {name} can't be both set, \
+                            set {name} to 0 to run its transactions for a duration"
+                        ));
+                    }
+                }
+            }
+            None => {}
+        }
+
+        match run.tx_version {
+            TxVersion::V1 if run.resource_bounds.is_some() => problems
+                .push("run.resource_bounds is only used with a v3 run.tx_version".to_owned()),
+            TxVersion::V1 if run.tip.is_some() => {
+                problems.push("run.tip is only used with a v3 run.tx_version".to_owned())
+            }
+            TxVersion::V3 if self.setup.strk_fee_token_address.is_none() => problems.push(
+                "setup.strk_fee_token_address is needed to pay the fees of a v3 run.tx_version"
+                    .to_owned(),
+            ),
+            _ => {}
+        }
+
+        if let Some(mixed) = &run.mixed {
+            if mixed.weights.is_empty() {
+                problems.push("run.mixed.weights should list at least one scenario".to_owned());
+            }
+
+            for (index, weight) in mixed.weights.iter().enumerate() {
+                let scenario = &weight.scenario;
+                let known = ["erc20", "erc721"].contains(&scenario.as_str())
+                    || run
+                        .custom_calls
+                        .iter()
+                        .any(|custom_call| &custom_call.selector == scenario);
+
+                if !known {
+                    problems.push(format!(
+                        "run.mixed.weights has an unknown scenario `{scenario}`, \
+                        expected erc20, erc721 or the selector of a custom call"
+                    ));
+                }
+                if weight.weight == 0 {
+                    problems.push(format!(
+                        "run.mixed.weights of `{scenario}` should be at least 1"
+                    ));
+                }
+                if mixed.weights[..index]
+                    .iter()
+                    .any(|previous| &previous.scenario == scenario)
+                {
+                    problems.push(format!(
+                        "run.mixed.weights lists `{scenario}` more than once"
+                    ));
+                }
+            }
+        }
+
+        if let (Some(expected), Some(alias)) = (self.rpc.expected_chain_id, run.expected_chain_id) {
+            if expected != alias {
+                problems.push(
+                    "rpc.expected_chain_id and its deprecated run.expected_chain_id alias \
+                    are set to different chain ids"
+                        .to_owned(),
+                );
+            }
+        }
+
+        if run.confirmation_interval_ms == 0 {
+            problems.push("run.confirmation_interval_ms should be at least 1".to_owned());
+        }
+        if run.max_confirmation_interval_ms < run.confirmation_interval_ms {
+            problems.push(format!(
+                "run.max_confirmation_interval_ms is {} but should be at least the {} of run.confirmation_interval_ms",
+                run.max_confirmation_interval_ms, run.confirmation_interval_ms
+            ));
+        }
+        // The accounts of setup.accounts_file are only known once the file is read
+        if self.setup.accounts_file.is_none() && run.concurrency != 0 {
+            match run.num_accounts() {
+                0 => problems.push(format!(
+                    "run.accounts is 0, {} users need at least one account",
+                    run.concurrency
+                )),
+                accounts if accounts < run.concurrency && !run.share_accounts => {
+                    problems.push(format!(
+                    "run.accounts is {accounts} for {} users, raise it or set run.share_accounts",
+                    run.concurrency
+                ))
+                }
+                _ => {}
+            }
+        }
+        if let Some(min_accounts) = self.setup.min_accounts {
+            if min_accounts > run.num_accounts() {
+                problems.push(format!(
+                    "setup.min_accounts is {min_accounts}, more than the {} run.accounts",
+                    run.num_accounts()
+                ));
+            }
+        }
+        for custom_call in &run.custom_calls {
+            if custom_call.contract_address.is_none() && self.setup.declare_contract.is_none() {
+                problems.push(format!(
+                    "Custom call {} needs a contract_address or setup.declare_contract",
+                    custom_call.selector
+                ));
+            }
+        }
+        if run.calls_per_tx == 0 {
+            problems.push("run.calls_per_tx should be at least 1".to_owned());
+        }
+
+        if self.deployer.signing_key.is_some() == self.deployer.remote_signer.is_some() {
+            problems
+                .push("deployer should have either a signing_key or a remote_signer".to_owned());
+        }
+
+        if self.rpc.url.all().is_empty() {
+            problems.push("rpc.url should have at least one url".to_owned());
+        }
+        let urls = self
+            .rpc
+            .url
+            .all()
+            .iter()
+            .map(|url| ("rpc.url", url))
+            .chain(
+                self.report
+                    .verification_rpc_url
+                    .iter()
+                    .map(|url| ("report.verification_rpc_url", url)),
+            )
+            .chain(
+                self.report
+                    .node_metrics
+                    .iter()
+                    .map(|node_metrics| ("report.node_metrics.url", &node_metrics.url)),
+            )
+            .chain(
+                self.report
+                    .influx_url
+                    .iter()
+                    .map(|url| ("report.influx_url", url)),
+            )
+            .chain(
+                self.report
+                    .pushgateway_url
+                    .iter()
+                    .map(|url| ("report.pushgateway_url", url)),
+            )
+            .chain(
+                self.deployer
+                    .remote_signer
+                    .iter()
+                    .map(|remote_signer| ("deployer.remote_signer.url", &remote_signer.url)),
+            );
+        for (key, url) in urls {
+            if let Err(err) = Url::parse(url) {
+                problems.push(format!("{key} `{url}` is not a valid url: {err}"));
+            }
+        }
+
+        if !self
+            .report
+            .percentiles
+            .iter()
+            .all(|percentile| (0.0..=100.0).contains(percentile))
+        {
+            problems.push("report.percentiles should all be between 0 and 100".to_owned());
+        }
+        if !(0.0..=1.0).contains(&self.report.min_confirmation_rate) {
+            problems.push("report.min_confirmation_rate should be between 0 and 1".to_owned());
+        }
+        let report_dir = self
+            .report
+            .output_location
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty());
+        if let Some(report_dir) = report_dir {
+            let existing = report_dir
+                .ancestors()
+                .find(|dir| !dir.as_os_str().is_empty() && dir.exists())
+                .unwrap_or(Path::new("."));
+            let creatable = std::fs::metadata(existing)
+                .map(|metadata| metadata.is_dir() && !metadata.permissions().readonly())
+                .unwrap_or(false);
+            if !creatable {
+                problems.push(format!(
+                    "report.output_location is in {} which can't be created, {} isn't a writable directory",
+                    report_dir.display(),
+                    existing.display()
+                ));
+            }
+        }
+
+        if !problems.is_empty() {
+            bail!(
+                "Invalid configuration:\n{}",
+                problems
+                    .iter()
+                    .map(|problem| format!("  - {problem}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
+        Ok(())
+    }
 }
 
 fn base_config_builder() -> ConfigBuilder<DefaultState> {
@@ -156,3 +1069,63 @@ where
         CairoShortStringToFeltError::StringTooLong => D::Error::custom("string too long"),
     })
 }
+
+fn chain_id_deserializer<'de, D>(deserializer: D) -> Result<Option<FieldElement>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let Some(chain_id) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    if chain_id.starts_with("0x") {
+        return FieldElement::from_hex_be(&chain_id)
+            .map(Some)
+            .map_err(D::Error::custom);
+    }
+
+    from_str_deserializer(serde::de::value::StrDeserializer::<D::Error>::new(
+        &chain_id,
+    ))
+    .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML_CONFIG: &str = "config/default.yaml";
+    const TOML_CONFIG: &str = "config/default.toml";
+
+    /// The configurations have no `PartialEq`, their debug output stands in for it
+    fn load(path: &str) -> String {
+        format!("{:?}", GatlingConfig::from_file(path).unwrap())
+    }
+
+    #[test]
+    fn yaml_and_toml_configs_are_the_same() {
+        assert_eq!(load(YAML_CONFIG), load(TOML_CONFIG));
+    }
+
+    #[test]
+    fn json_config_is_the_same_as_yaml() {
+        let values: serde_json::Value = Config::builder()
+            .add_source(File::with_name(YAML_CONFIG))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let json_path = std::env::temp_dir().join(format!("gatling-{}.json", std::process::id()));
+        std::fs::write(&json_path, values.to_string()).unwrap();
+
+        let json = load(json_path.to_str().unwrap());
+        std::fs::remove_file(&json_path).unwrap();
+
+        assert_eq!(json, load(YAML_CONFIG));
+    }
+
+    #[test]
+    fn config_without_extension_is_looked_up() {
+        assert_eq!(load("config/default"), load(YAML_CONFIG));
+    }
+}
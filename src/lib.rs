@@ -12,6 +12,9 @@ pub mod actions;
 /// Generators.
 pub mod generators;
 
+/// Logging.
+pub mod logging;
+
 /// Metrics.
 pub mod metrics;
 
@@ -0,0 +1,114 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use log::{Level, Log, Metadata, Record};
+use serde_json::json;
+
+/// Least severe level written to the log file, whatever `RUST_LOG` lets through to stderr
+const FILE_LEVEL: Level = Level::Info;
+
+/// File the JSON log lines are appended to, only with `report.log_file`
+static LOG_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Benchmark running while the lines are logged, written along with them
+static SCENARIO: Mutex<Option<String>> = Mutex::new(None);
+
+/// Logs to stderr as filtered by `RUST_LOG`, and to the log file once there is one
+struct Logger {
+    stderr: env_logger::Logger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata) || LOG_FILE.get().is_some() && metadata.level() <= FILE_LEVEL
+    }
+
+    fn log(&self, record: &Record) {
+        if self.stderr.matches(record) {
+            self.stderr.log(record);
+        }
+
+        let Some(file) = LOG_FILE.get() else {
+            return;
+        };
+
+        if record.level() > FILE_LEVEL {
+            return;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs_f64())
+            .unwrap_or_default();
+        let scenario = SCENARIO
+            .lock()
+            .map(|scenario| scenario.clone())
+            .unwrap_or_default();
+
+        let mut line = json!({
+            "timestamp": timestamp,
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        });
+        if let Some(scenario) = scenario {
+            line["scenario"] = scenario.into();
+        }
+
+        // A failed write can't be logged without recursing, it is dropped instead
+        if let Ok(mut file) = file.lock() {
+            let _ = writeln!(file, "{line}");
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+
+        if let Some(Ok(mut file)) = LOG_FILE.get().map(Mutex::lock) {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Install the logger, which logs to stderr until [`log_to_file`] is called
+pub fn init() {
+    let stderr = env_logger::Builder::from_default_env().build();
+    let max_level = stderr.filter();
+
+    log::set_boxed_logger(Box::new(Logger { stderr }))
+        .expect("Logger should only be installed once");
+    log::set_max_level(max_level);
+}
+
+/// Also append every line logged from now on to `path` as JSON, with its timestamp
+/// in Unix seconds, level, target, message and the benchmark running if any
+pub fn log_to_file(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .wrap_err_with(|| format!("Could not open log file {}", path.display()))?;
+
+    LOG_FILE
+        .set(Mutex::new(file))
+        .map_err(|_| eyre!("The log file can only be set once"))?;
+    log::set_max_level(log::max_level().max(FILE_LEVEL.to_level_filter()));
+
+    Ok(())
+}
+
+/// Set the benchmark the next lines are logged under, `None` once it is over
+pub fn set_scenario(scenario: Option<&str>) {
+    if let Ok(mut current) = SCENARIO.lock() {
+        *current = scenario.map(str::to_owned);
+    }
+}
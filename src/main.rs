@@ -12,7 +12,7 @@ use gatling::{
 #[tokio::main(flavor = "multi_thread", worker_threads = 10)]
 async fn main() -> Result<()> {
     // Initialize the logger.
-    env_logger::init();
+    gatling::logging::init();
 
     // Initialize the error handler.
     color_eyre::install()?;
@@ -25,16 +25,59 @@ async fn main() -> Result<()> {
     // Parse the command line arguments.
     let cli = Cli::parse();
 
+    // Comparing reports needs no configuration.
+    if let Command::Compare {
+        old,
+        new,
+        threshold,
+    } = &cli.command
+    {
+        return actions::compare(old, new, *threshold);
+    }
+
     // Retrieve the application configuration.
-    let cfg = match cli.global_opts.config_path {
+    let mut cfg = match cli.global_opts.config_path {
         Some(path) => GatlingConfig::from_file(&path)?,
         None => GatlingConfig::new()?,
     };
 
+    // The scenarios selected on the command line override the ones of the configuration.
+    if let Command::Shoot {
+        only, skip, quiet, ..
+    } = &cli.command
+    {
+        cfg.run.select_scenarios(only, skip)?;
+
+        if *quiet {
+            cfg.report.progress_interval_secs = 0;
+        }
+    }
+
     // Execute the command.
     match cli.command {
-        Command::Shoot { .. } => {
-            actions::shoot(cfg).await?;
+        Command::Shoot {
+            state,
+            dry_run: true,
+            ..
+        } => {
+            actions::dry_run(cfg, state.as_deref()).await?;
+        }
+        Command::Shoot {
+            state,
+            checkpoint,
+            resume,
+            ..
+        } => {
+            actions::shoot(cfg, state.as_deref(), checkpoint.as_deref(), resume).await?;
+        }
+        Command::Replay { file, state } => {
+            actions::replay(cfg, &file, state.as_deref()).await?;
+        }
+        Command::Setup { state } => {
+            actions::setup(cfg, &state).await?;
+        }
+        Command::Compare { .. } => {
+            unreachable!("Reports are compared before loading the configuration")
         }
     }
 
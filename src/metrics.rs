@@ -1,4 +1,10 @@
-use crate::utils::get_num_tx_per_block;
+use crate::{
+    config::{ReportConfig, UopsBreakdown},
+    utils::{
+        block_number, get_blocks_with_txs, get_blocks_with_txs_including_pending, get_receipts,
+        ReceiptLookup, StarknetRpc,
+    },
+};
 
 use color_eyre::{
     eyre::{bail, eyre},
@@ -6,20 +12,356 @@ use color_eyre::{
 };
 
 use goose::metrics::{GooseMetrics, TransactionMetricAggregate};
+use rand::seq::SliceRandom;
+use serde::{ser::Error as _, Serializer};
 use serde_derive::Serialize;
-use starknet::providers::{jsonrpc::HttpTransport, JsonRpcClient, Provider};
-use std::fmt;
+use serde_json::Value;
+use starknet::core::types::{
+    BlockWithTxs, DeclareTransaction, DeployAccountTransaction, FieldElement, InvokeTransaction,
+    PriceUnit, Transaction, TransactionReceipt,
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 pub const BLOCK_TIME: u64 = 6;
 
+/// Version of the shape of the serialized [`GlobalReport`], bumped whenever a field
+/// of it or of the reports it holds is renamed, removed or changes meaning
+///
+/// Adding fields or metrics is compatible and keeps the version, such as the
+/// confirmations, fee histogram, timeout, error breakdown and workloads added since 1
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Gauges of the Prometheus exposition, along with the metric each of them is read from
+const PROMETHEUS_GAUGES: [(&str, &str); 6] = [
+    ("gatling_tps", "Average TPS"),
+    ("gatling_uops", "Average UOPS"),
+    ("gatling_submission_tps", "Average Submission TPS"),
+    ("gatling_average_latency_ms", "Average Submission Time"),
+    ("gatling_revert_rate", "Revert Rate"),
+    ("gatling_confirmation_rate", "Confirmation Rate"),
+];
+
 #[derive(Clone, Debug, Serialize)]
 pub struct GlobalReport {
+    /// [`REPORT_SCHEMA_VERSION`] of the report, so that consumers can check it before parsing
+    pub schema_version: u32,
+    /// Version of gatling that wrote the report
+    pub gatling_version: &'static str,
+    /// Whether the run was interrupted by Ctrl-C or `run.max_duration_secs`, the interrupted
+    /// benchmark is partial and the ones after it are missing
+    pub incomplete: bool,
+    /// Whether the run was interrupted by `run.max_duration_secs`
+    pub timed_out: bool,
     pub users: u64,
+    /// Seconds the users were started over, `0` when they were started all at once
+    pub ramp_up_secs: u64,
+    /// Whether accounts were deployed by their first transaction rather than during setup
+    pub counterfactual_accounts: bool,
     pub all_bench_report: BenchmarkReport,
     pub benches: Vec<BenchmarkReport>,
+    /// Health of the node checked between benchmarks
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub health_probes: Vec<HealthProbe>,
+    /// Series sampled from the Prometheus endpoint of the node during the run
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub node_metrics: Vec<NodeMetricSeries>,
+    /// Time the setup took, missing when the environment was loaded from a state file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setup: Option<SetupTimings>,
     pub extra: String,
 }
 
+/// Seconds spent in each step of the setup, contracts whose class is already declared
+/// or which already exist only take the time to check for them
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SetupTimings {
+    /// Declaring the classes and checking the account class for counterfactual deployments
+    pub declare: f64,
+    /// Deploying the erc20, erc721 and custom contracts
+    pub deploy: f64,
+    /// Creating, funding and deploying the accounts, and the fresh accounts of the deployments
+    pub fund: f64,
+    /// Whole setup, including the steps in between
+    pub total: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HealthProbe {
+    /// Name of the benchmark the probe ran after
+    pub after: String,
+    pub block_advancing: bool,
+    pub read_call: bool,
+    /// `None` when the node doesn't expose its pending block
+    pub mempool_drained: Option<bool>,
+}
+
+impl GlobalReport {
+    /// Flat CSV with a row per benchmark and one for all of them, and a column per metric,
+    /// left empty for the benchmarks without it
+    pub fn to_csv(&self) -> String {
+        let reports: Vec<&BenchmarkReport> = self
+            .benches
+            .iter()
+            .chain([&self.all_bench_report])
+            .collect();
+
+        let mut columns: Vec<&str> = Vec::new();
+        for metric in reports.iter().flat_map(|report| &report.metrics) {
+            if !columns.contains(&metric.name) {
+                columns.push(metric.name);
+            }
+        }
+
+        let header: Vec<String> = ["Scenario", "Amount", "First Block", "Last Block"]
+            .iter()
+            .chain(&columns)
+            .map(|column| csv_field(column))
+            .collect();
+
+        let mut csv = header.join(",");
+        csv.push('\n');
+
+        for report in reports {
+            csv.push_str(&report.to_csv_row(&columns));
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    /// Self-contained HTML page, with inline styles only, holding a table of metrics
+    /// per benchmark along with the system the run was done on
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Gatling Report</title>
+<style>
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+th { background: #f0f0f0; }
+td.value { text-align: right; font-variant-numeric: tabular-nums; }
+pre { background: #f6f6f6; padding: 1em; }
+</style>
+</head>
+<body>
+<h1>Gatling Report</h1>
+"#,
+        );
+
+        if self.timed_out {
+            html.push_str("<p><strong>Incomplete</strong>, the run timed out</p>\n");
+        } else if self.incomplete {
+            html.push_str("<p><strong>Incomplete</strong>, the run was interrupted</p>\n");
+        }
+
+        html.push_str(&format!(
+            "<p>{} users, {} accounts</p>\n",
+            self.users,
+            if self.counterfactual_accounts {
+                "counterfactual"
+            } else {
+                "deployed"
+            }
+        ));
+
+        if let Some(SetupTimings {
+            declare,
+            deploy,
+            fund,
+            total,
+        }) = self.setup
+        {
+            html.push_str(&format!(
+                "<p>Setup in {total:.2} seconds: declare {declare:.2}, deploy {deploy:.2}, fund {fund:.2}</p>\n"
+            ));
+        }
+
+        for report in self.benches.iter().chain([&self.all_bench_report]) {
+            html.push_str(&report.to_html_table());
+        }
+
+        html.push_str(&format!(
+            "<h2>System</h2>\n<pre>{}</pre>\n</body>\n</html>\n",
+            html_escape(&self.extra)
+        ));
+
+        html
+    }
+
+    /// Prometheus text exposition of the main metrics, a gauge per metric with a sample per
+    /// benchmark labeled by its scenario, named the same from one run to the next
+    pub fn to_prometheus(&self) -> String {
+        let reports: Vec<(&str, &BenchmarkReport)> = self
+            .benches
+            .iter()
+            .chain([&self.all_bench_report])
+            .map(|report| match report.name.as_str() {
+                "" => ("All Benchmarks", report),
+                name => (name, report),
+            })
+            .collect();
+
+        let mut gauges: Vec<(String, Vec<(&str, f64)>)> = PROMETHEUS_GAUGES
+            .iter()
+            .map(|&(gauge, metric)| {
+                let samples = reports
+                    .iter()
+                    .filter_map(|&(scenario, report)| Some((scenario, report.metric(metric)?)))
+                    .collect();
+
+                (gauge.to_owned(), samples)
+            })
+            .collect();
+
+        for &(scenario, report) in &reports {
+            for (percentile, times) in &report.time_percentiles {
+                let Some(submission) = times.submission else {
+                    continue;
+                };
+
+                let gauge = format!(
+                    "gatling_p{}_latency_ms",
+                    percentile.to_string().replace('.', "_")
+                );
+                let sample = (scenario, submission as f64);
+
+                match gauges.iter_mut().find(|(name, _)| *name == gauge) {
+                    Some((_, samples)) => samples.push(sample),
+                    None => gauges.push((gauge, vec![sample])),
+                }
+            }
+        }
+
+        let mut text = String::new();
+
+        for (gauge, samples) in gauges {
+            // NaN and infinite values would only break the dashboards
+            let samples: Vec<_> = samples
+                .into_iter()
+                .filter(|(_, value)| value.is_finite())
+                .collect();
+            if samples.is_empty() {
+                continue;
+            }
+
+            text.push_str(&format!("# TYPE {gauge} gauge\n"));
+            for (scenario, value) in samples {
+                text.push_str(&format!(
+                    "{gauge}{{scenario=\"{}\"}} {value}\n",
+                    prometheus_label(scenario)
+                ));
+            }
+        }
+
+        text
+    }
+
+    /// InfluxDB line protocol, a `gatling` point per benchmark tagged with its scenario and
+    /// the gatling version, with a float field per numeric metric and time percentile
+    pub fn to_influx(&self, timestamp: SystemTime) -> String {
+        let timestamp = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let version = influx_tag(self.gatling_version);
+
+        let mut lines = String::new();
+
+        for report in self.benches.iter().chain([&self.all_bench_report]) {
+            let scenario = match report.name.as_str() {
+                "" => "All Benchmarks",
+                name => name,
+            };
+
+            let metrics = report
+                .metrics
+                .iter()
+                .filter_map(|metric| Some((influx_field_key(metric.name), metric.value.as_f64()?)));
+            let percentiles = report
+                .time_percentiles
+                .iter()
+                .flat_map(|(percentile, times)| {
+                    [
+                        ("Submission", times.submission),
+                        ("Verification", times.verification),
+                    ]
+                    .into_iter()
+                    .filter_map(move |(phase, time)| {
+                        Some((
+                            influx_field_key(&format!("P{percentile} {phase} Time")),
+                            time? as f64,
+                        ))
+                    })
+                });
+
+            // Influx has no NaN nor infinite floats
+            let fields: Vec<String> = metrics
+                .chain(percentiles)
+                .filter(|(_, value)| value.is_finite())
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect();
+
+            // A point needs at least one field
+            if fields.is_empty() {
+                continue;
+            }
+
+            lines.push_str(&format!(
+                "gatling,scenario={},gatling_version={version} {} {timestamp}\n",
+                influx_tag(scenario),
+                fields.join(",")
+            ));
+        }
+
+        lines
+    }
+}
+
+impl HealthProbe {
+    pub fn is_healthy(&self) -> bool {
+        self.block_advancing && self.read_call && self.mempool_drained != Some(false)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NodeMetricSeries {
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+    pub average: f64,
+    pub samples: Vec<NodeMetricSample>,
+}
+
+/// Value of a node series `time` seconds after the start of the run
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct NodeMetricSample {
+    pub time: f64,
+    pub value: f64,
+}
+
+impl NodeMetricSeries {
+    pub fn new(name: String, samples: Vec<NodeMetricSample>) -> Self {
+        let values = samples.iter().map(|sample| sample.value);
+
+        Self {
+            name,
+            min: values.clone().fold(f64::INFINITY, f64::min),
+            max: values.clone().fold(f64::NEG_INFINITY, f64::max),
+            average: values.sum::<f64>() / samples.len() as f64,
+            samples,
+        }
+    }
+}
+
 /// Metric struct that contains the name, unit and compute function for a metric
 /// A Metric is a measure of a specific performance aspect of a benchmark through
 /// the compute function which receives a vector of number of transactions per block
@@ -29,6 +371,7 @@ pub struct GlobalReport {
 /// ### Example
 /// { name: "Average TPS", unit: "transactions/second", compute: average_tps }
 /// "Average TPS: 1000 transactions/second"
+#[allow(unpredictable_function_pointer_comparisons)]
 #[derive(PartialEq, Eq, Hash, Clone)]
 pub struct NodeMetrics {
     pub name: &'static str,
@@ -58,8 +401,187 @@ pub struct BenchmarkReport {
     pub name: String,
     pub amount: usize,
     pub metrics: Vec<MetricResult>,
+    /// Submission and verification times at each of `report.percentiles`, keyed by the percentile
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_time_percentiles"
+    )]
+    pub time_percentiles: Vec<(f64, TimePercentiles)>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_x_blocks_metrics: Option<LastXBlocksMetric>,
+    /// Time-to-inclusion in seconds, bucketed by the in-flight depth at submission
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inclusion_time_by_depth: Vec<DepthBucket>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_correlation: Option<FeeCorrelation>,
+    /// Transactions of the measured blocks by the fee they paid, with `report.fee_buckets`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fee_histogram: Vec<FeeBucket>,
+    /// Requested and achieved TPS for every segment of the load profile
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub load_profile: Vec<ProfileSegment>,
+    /// Transactions sent from every account, with weighted account selection
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub account_distribution: Vec<AccountShare>,
+    /// Transactions per second of every block, over the interval since its parent
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub per_block_throughput: Vec<(u64, f64)>,
+    /// Transactions of the pending block sampled while the benchmark ran, by seconds since its start
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mempool_samples: Vec<(f64, u64)>,
+    /// UOPS of every contract address or entrypoint selector, with `report.uops_breakdown`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub uops_breakdown: BTreeMap<String, f64>,
+    /// Failed requests by the category of their error, `revert`, `timeout`, `nonce_error`,
+    /// `rpc_error` or `other`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub error_breakdown: BTreeMap<&'static str, usize>,
+    /// Submissions of every workload of a mixed benchmark, empty for the other benchmarks
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub workloads: Vec<WorkloadSubmissions>,
+    /// Raw JSON-RPC exchanges of the first failed requests
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_requests: Vec<RpcExchange>,
+    /// Blocks the metrics were computed over
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_range: Option<BlockRange>,
+    /// Seconds between the consecutive blocks of `block_range`, `None` without two of them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<BlockTime>,
+}
+
+/// Submissions goose measured for one of the workloads of a mixed benchmark, its transactions
+/// being verified along with the ones of the other workloads
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkloadSubmissions {
+    pub name: String,
+    pub transactions: usize,
+    pub failed: usize,
+    /// Part of the transactions of the benchmark that were of this workload
+    pub share: f64,
+    /// Milliseconds taken to submit a transaction on average
+    pub average_time: f64,
+    pub max_time: usize,
+}
+
+/// Times in milliseconds, `None` when there was no request to measure
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TimePercentiles {
+    pub submission: Option<usize>,
+    pub verification: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BlockRange {
+    pub first: u64,
+    pub last: u64,
+}
+
+/// Intervals between the timestamps of consecutive blocks, in seconds
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BlockTime {
+    pub average: f64,
+    pub min: u64,
+    pub max: u64,
+}
+
+/// Answers of the node to transactions resubmitted with the same hash
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateSubmissions {
+    pub accepted: u64,
+    pub rejected: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcExchange {
+    pub request: serde_json::Value,
+    pub response: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountShare {
+    pub address: FieldElement,
+    pub transactions: u64,
+    /// Part of all the transactions that were sent from this account
+    pub share: f64,
+}
+
+/// A transaction accepted by the node during a benchmark
+#[derive(Debug, Clone, Copy)]
+pub struct SubmittedTransaction {
+    pub hash: FieldElement,
+    pub submitted_at: SystemTime,
+}
+
+/// Time-to-inclusion percentiles over the transactions that were submitted
+/// while the amount of in-flight transactions was within `min_depth..=max_depth`
+#[derive(Debug, Clone, Serialize)]
+pub struct DepthBucket {
+    pub name: &'static str,
+    pub min_depth: usize,
+    pub max_depth: usize,
+    pub transactions: usize,
+    pub p50_inclusion_time: f64,
+    pub p90_inclusion_time: f64,
+}
+
+/// Correlation between the fee paid by the included transactions and their time-to-inclusion
+///
+/// On a fee market the coefficient should be negative, a weak or positive one means
+/// the sequencer isn't prioritizing transactions by fee
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeCorrelation {
+    pub transactions: usize,
+    /// Pearson coefficient, `None` when either the fees or the inclusion times don't vary
+    pub coefficient: Option<f64>,
+    /// The transactions split into quartiles from the lowest to the highest fee
+    pub quartiles: Vec<FeeQuartile>,
+}
+
+/// Transactions that paid a fee from `min_fee` included to `max_fee` excluded, in wei as fees
+/// paid in STRK are left out, the last bucket having no `max_fee`
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeBucket {
+    pub min_fee: u128,
+    pub max_fee: Option<u128>,
+    pub transactions: usize,
+    /// Share of the transactions of the measured blocks, from 0 to 1
+    pub share: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeQuartile {
+    pub min_fee: u128,
+    pub max_fee: u128,
+    pub transactions: usize,
+    pub average_inclusion_time: f64,
+}
+
+/// Wall-clock time a phase of a benchmark took over all the users,
+/// with the amount of transactions it went through
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseTiming {
+    pub duration: Duration,
+    pub transactions: usize,
+}
+
+/// TPS requested by a load profile from `start` and for `duration` seconds
+#[derive(Debug, Clone, Copy)]
+pub struct RequestedRate {
+    pub start: SystemTime,
+    pub duration: f64,
+    pub tps: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileSegment {
+    /// Seconds from the start of the profile
+    pub start: f64,
+    pub duration: f64,
+    /// Average TPS requested over the segment
+    pub requested_tps: f64,
+    /// TPS at which the node accepted the transactions during the segment
+    pub achieved_tps: f64,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -75,59 +597,742 @@ impl BenchmarkReport {
             amount,
             metrics: Vec::new(),
             last_x_blocks_metrics: None,
+            inclusion_time_by_depth: Vec::new(),
+            fee_correlation: None,
+            fee_histogram: Vec::new(),
+            load_profile: Vec::new(),
+            account_distribution: Vec::new(),
+            per_block_throughput: Vec::new(),
+            mempool_samples: Vec::new(),
+            uops_breakdown: BTreeMap::new(),
+            error_breakdown: BTreeMap::new(),
+            workloads: Vec::new(),
+            failed_requests: Vec::new(),
+            time_percentiles: Vec::new(),
+            block_range: None,
+            block_time: None,
         }
     }
 
+    /// Compute the node metrics over the blocks from `start_block` to `end_block`, leaving them
+    /// out when no block was produced after `start_block`
     pub async fn with_block_range(
         &mut self,
-        starknet_rpc: &JsonRpcClient<HttpTransport>,
-        mut start_block: u64,
-        mut end_block: u64,
+        starknet_rpc: &Arc<StarknetRpc>,
+        max_retries: u32,
+        start_block: u64,
+        end_block: u64,
+        config: &ReportConfig,
     ) -> Result<()> {
-        // Whenever possible, skip the first and last blocks from the metrics
-        // to make sure all the blocks used for calculating metrics are full
-        if end_block - start_block > 2 {
-            start_block += 1;
-            end_block -= 1;
+        if end_block <= start_block && !config.include_pending_block {
+            log::warn!(
+                "No block was produced after block {start_block}, skipping the block metrics"
+            );
+            return Ok(());
         }
 
-        let num_tx_per_block = get_num_tx_per_block(starknet_rpc, start_block, end_block).await?;
-        let metrics = compute_node_metrics(num_tx_per_block);
+        let blocks = get_blocks_with_txs_including_pending(
+            starknet_rpc,
+            start_block..=end_block,
+            max_retries,
+            config.max_concurrent_requests,
+            config.include_pending_block,
+        )
+        .await?;
+        self.with_blocks(&blocks);
 
-        self.metrics.extend_from_slice(&metrics);
+        if let Some(breakdown) = config.uops_breakdown {
+            self.with_uops_breakdown(&blocks, breakdown);
+        }
+
+        if config.fee_metrics {
+            self.with_fees(
+                starknet_rpc,
+                max_retries,
+                &blocks,
+                config.receipt_batch_size,
+                &config.fee_buckets,
+            )
+            .await?;
+        }
 
         Ok(())
     }
 
+    /// Compute the fees spent by all the transactions of `blocks`, read from their receipts,
+    /// along with their histogram over `fee_buckets` when there are any
+    pub async fn with_fees(
+        &mut self,
+        starknet_rpc: &Arc<StarknetRpc>,
+        max_retries: u32,
+        blocks: &[BlockWithTxs],
+        receipt_batch_size: usize,
+        fee_buckets: &[u64],
+    ) -> Result<()> {
+        let fees = receipt_fees(starknet_rpc, max_retries, blocks, receipt_batch_size).await?;
+        self.metrics.extend(fee_metrics(&fees));
+
+        if !fee_buckets.is_empty() && !fees.is_empty() {
+            self.fee_histogram = fee_histogram(&fees, fee_buckets);
+        }
+
+        Ok(())
+    }
+
+    /// Compute the node metrics over blocks that were already fetched, there are none without blocks
+    pub fn with_blocks(&mut self, blocks: &[BlockWithTxs]) {
+        if blocks.is_empty() {
+            return;
+        }
+
+        let metrics = compute_node_metrics(full_blocks(blocks));
+
+        self.metrics.extend_from_slice(&metrics);
+        self.per_block_throughput = per_block_throughput(blocks);
+        self.block_range = match (blocks.first(), blocks.last()) {
+            (Some(first), Some(last)) => Some(BlockRange {
+                first: first.block_number,
+                last: last.block_number,
+            }),
+            _ => None,
+        };
+        self.block_time = block_time(blocks);
+    }
+
+    /// Compute the UOPS of every invoked contract or entrypoint, the operations of a multicall
+    /// all going to the target of its first call
+    ///
+    /// Transactions other than invokes are counted under their type, as they call no entrypoint
+    pub fn with_uops_breakdown(&mut self, blocks: &[BlockWithTxs], breakdown: UopsBreakdown) {
+        let blocks = full_blocks(blocks);
+        let mut operations: BTreeMap<String, u64> = BTreeMap::new();
+
+        for tx in blocks.iter().flat_map(|block| &block.transactions) {
+            *operations
+                .entry(operation_target(tx, breakdown))
+                .or_default() += user_operations(tx);
+        }
+
+        let seconds = (blocks.len() as u64 * BLOCK_TIME) as f64;
+
+        self.uops_breakdown = operations
+            .into_iter()
+            .map(|(target, operations)| (target, operations as f64 / seconds))
+            .collect();
+    }
+
+    /// Section of the HTML report with the block range and a table of the metrics
+    fn to_html_table(&self) -> String {
+        let name = match self.name.as_str() {
+            "" => "All Benchmarks",
+            name => name,
+        };
+
+        let mut html = format!("<h2>{} ({})</h2>\n", html_escape(name), self.amount);
+
+        if let Some(BlockRange { first, last }) = self.block_range {
+            html.push_str(&format!("<p>Blocks {first} to {last}</p>\n"));
+        }
+
+        if let Some(BlockTime { average, min, max }) = self.block_time {
+            html.push_str(&format!(
+                "<p>Block time {average:.2} seconds on average, from {min} to {max} seconds</p>\n"
+            ));
+        }
+
+        html.push_str("<table>\n<tr><th>Metric</th><th>Value</th><th>Unit</th></tr>\n");
+
+        for MetricResult { name, unit, value } in &self.metrics {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"value\">{}</td><td>{}</td></tr>\n",
+                html_escape(name),
+                html_escape(&value),
+                html_escape(unit)
+            ));
+        }
+
+        html.push_str("</table>\n");
+
+        if !self.workloads.is_empty() {
+            html.push_str("<table>\n<tr><th>Workload</th><th>Transactions</th><th>Share</th><th>Failed</th><th>Average Submission Time</th><th>Max Submission Time</th></tr>\n");
+
+            for workload in &self.workloads {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td class=\"value\">{}</td><td class=\"value\">{:.1}%</td><td class=\"value\">{}</td><td class=\"value\">{:.1} ms</td><td class=\"value\">{} ms</td></tr>\n",
+                    html_escape(&workload.name),
+                    workload.transactions,
+                    workload.share * 100.0,
+                    workload.failed,
+                    workload.average_time,
+                    workload.max_time
+                ));
+            }
+
+            html.push_str("</table>\n");
+        }
+
+        html
+    }
+
+    /// Row of the CSV report with the value of the metrics in `columns`
+    pub fn to_csv_row(&self, columns: &[&str]) -> String {
+        let name = match self.name.as_str() {
+            "" => "All Benchmarks",
+            name => name,
+        };
+
+        let (first_block, last_block) = match self.block_range {
+            Some(BlockRange { first, last }) => (first.to_string(), last.to_string()),
+            None => Default::default(),
+        };
+
+        let metrics = columns.iter().map(|column| {
+            match self.metrics.iter().find(|metric| metric.name == *column) {
+                Some(MetricResult {
+                    value: Value::String(value),
+                    ..
+                }) => csv_field(value),
+                Some(MetricResult {
+                    value: Value::Null, ..
+                })
+                | None => String::new(),
+                Some(metric) => metric.value.to_string(),
+            }
+        });
+
+        [
+            csv_field(name),
+            self.amount.to_string(),
+            first_block,
+            last_block,
+        ]
+        .into_iter()
+        .chain(metrics)
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+
+    /// Compute the throughput counting only the transactions sent by `senders`,
+    /// leaving out the ones the sequencer or other users added to the blocks
+    ///
+    /// Transactions without a sender (L1 handlers, deploys and account deployments)
+    /// can't be attributed and are never counted as our own
+    pub fn with_own_traffic(&mut self, blocks: &[BlockWithTxs], senders: &HashSet<FieldElement>) {
+        let num_tx_per_block: Vec<u64> = full_blocks(blocks)
+            .iter()
+            .map(|block| {
+                block
+                    .transactions
+                    .iter()
+                    .filter(|tx| sender_address(tx).is_some_and(|sender| senders.contains(&sender)))
+                    .count() as u64
+            })
+            .collect();
+
+        self.metrics.extend_from_slice(&[
+            MetricResult {
+                name: "Average TPS (own traffic)",
+                unit: "transactions/second",
+                value: average_tps(&num_tx_per_block).into(),
+            },
+            MetricResult {
+                name: "Average Extrinsics per block (own traffic)",
+                unit: "extrinsics/block",
+                value: average_tpb(&num_tx_per_block).into(),
+            },
+        ]);
+    }
+
     pub async fn with_last_x_blocks(
         &mut self,
-        starknet_rpc: &JsonRpcClient<HttpTransport>,
+        starknet_rpc: &Arc<StarknetRpc>,
+        max_retries: u32,
+        max_concurrent: usize,
         num_blocks: u64,
+        fee_batch_size: Option<usize>,
     ) -> Result<()> {
         // The last block won't be full of transactions, so we skip it
-        let end_block = starknet_rpc.block_number().await? - 1;
-        let start_block = end_block - num_blocks;
+        let Some(end_block) = block_number(starknet_rpc, max_retries)
+            .await?
+            .checked_sub(1)
+        else {
+            log::warn!("The chain has no block before the latest one, skipping the last {num_blocks} block metrics");
+            return Ok(());
+        };
+        let start_block = end_block.checked_sub(num_blocks).unwrap_or_else(|| {
+            log::warn!(
+                "The chain only has {} blocks before the latest one, measuring them instead of the last {num_blocks}",
+                end_block + 1
+            );
+            0
+        });
+
+        let blocks = get_blocks_with_txs(
+            starknet_rpc,
+            start_block..=end_block,
+            max_retries,
+            max_concurrent,
+        )
+        .await?;
+        let mut metrics = compute_node_metrics(&blocks).to_vec();
 
-        let num_tx_per_block = get_num_tx_per_block(starknet_rpc, start_block, end_block).await?;
-        let metrics = compute_node_metrics(num_tx_per_block).to_vec();
+        if let Some(batch_size) = fee_batch_size {
+            let fees = receipt_fees(starknet_rpc, max_retries, &blocks, batch_size).await?;
+            metrics.extend(fee_metrics(&fees));
+        }
 
         self.last_x_blocks_metrics = Some(LastXBlocksMetric {
-            num_blocks,
+            num_blocks: end_block - start_block,
             metrics,
         });
 
         Ok(())
     }
 
-    pub fn with_goose_metrics(&mut self, metrics: &GooseMetrics) -> Result<()> {
+    /// Look up a sample of the submitted transactions that are missing from `blocks`
+    /// to tell apart the ones that were silently dropped by the node from the ones
+    /// that were included in a block outside of the fetched range
+    pub async fn with_dropped_transactions(
+        &mut self,
+        starknet_rpc: &Arc<StarknetRpc>,
+        max_retries: u32,
+        submitted: &[SubmittedTransaction],
+        blocks: &[BlockWithTxs],
+        sample_size: usize,
+        receipt_batch_size: usize,
+    ) -> Result<()> {
+        let included: HashSet<FieldElement> = blocks
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .map(|tx| *tx.transaction_hash())
+            .collect();
+
+        let missing: Vec<FieldElement> = submitted
+            .iter()
+            .map(|tx| tx.hash)
+            .filter(|tx| !included.contains(tx))
+            .collect();
+
+        let sample: Vec<FieldElement> = missing
+            .choose_multiple(&mut rand::thread_rng(), sample_size)
+            .copied()
+            .collect();
+
+        // `starknet_getTransactionStatus` is not part of the RPC version we target,
+        // a missing receipt means the node has no record of the transaction
+        let receipts = get_receipts(
+            starknet_rpc,
+            sample.iter().copied(),
+            receipt_batch_size,
+            max_retries,
+        )
+        .await?;
+
+        let mut dropped = 0;
+
+        for (tx, receipt) in receipts {
+            if let ReceiptLookup::NotFound = receipt {
+                log::warn!("Transaction {tx:#064x} was dropped by the node");
+                dropped += 1;
+            }
+        }
+
+        self.metrics.extend_from_slice(&[
+            MetricResult {
+                name: "Transactions Missing From Blocks",
+                unit: "",
+                value: missing.len().into(),
+            },
+            MetricResult {
+                name: "Sampled Missing Transactions",
+                unit: "",
+                value: sample.len().into(),
+            },
+            MetricResult {
+                name: "Dropped Transactions",
+                unit: "",
+                value: dropped.into(),
+            },
+        ]);
+
+        Ok(())
+    }
+
+    /// Bucket the time-to-inclusion of the submitted transactions by how many
+    /// transactions were in flight when they were submitted
+    ///
+    /// Inclusion is taken from the timestamp of the block including the transaction,
+    /// which makes it precise to the second
+    pub fn with_inclusion_by_depth(
+        &mut self,
+        submitted: &[SubmittedTransaction],
+        blocks: &[BlockWithTxs],
+    ) {
+        let timings = inclusion_timings(submitted, blocks);
+
+        if timings.is_empty() {
+            return;
+        }
+
+        let mut submissions: Vec<f64> =
+            timings.iter().map(|(_, submitted, _)| *submitted).collect();
+        let mut inclusions: Vec<f64> = timings.iter().map(|(_, _, included)| *included).collect();
+        submissions.sort_unstable_by(f64::total_cmp);
+        inclusions.sort_unstable_by(f64::total_cmp);
+
+        // A transaction is in flight at `t` when it was submitted before `t`
+        // and was not included yet
+        let depths: Vec<(usize, f64)> = timings
+            .iter()
+            .map(|(_, submitted, included)| {
+                let submitted_before = submissions.partition_point(|t| t < submitted);
+                let included_before = inclusions.partition_point(|t| t <= submitted);
+
+                (
+                    submitted_before.saturating_sub(included_before),
+                    included - submitted,
+                )
+            })
+            .collect();
+
+        let max_depth = depths.iter().map(|(depth, _)| *depth).max().unwrap_or(0);
+        let (low, medium) = (max_depth / 3, max_depth * 2 / 3);
+
+        self.inclusion_time_by_depth = [
+            ("low", 0, low),
+            ("medium", low + 1, medium),
+            ("high", medium + 1, max_depth),
+        ]
+        .into_iter()
+        .filter_map(|(name, min_depth, max_depth)| {
+            let mut latencies: Vec<f64> = depths
+                .iter()
+                .filter(|(depth, _)| (min_depth..=max_depth).contains(depth))
+                .map(|(_, latency)| *latency)
+                .collect();
+
+            if latencies.is_empty() {
+                return None;
+            }
+
+            latencies.sort_unstable_by(f64::total_cmp);
+
+            Some(DepthBucket {
+                name,
+                min_depth,
+                max_depth,
+                transactions: latencies.len(),
+                p50_inclusion_time: percentile(&latencies, 50.0),
+                p90_inclusion_time: percentile(&latencies, 90.0),
+            })
+        })
+        .collect();
+    }
+
+    /// Compute the rate at which the client attempted to submit transactions, failed
+    /// submissions included, next to the rate at which the node accepted them
+    ///
+    /// A big gap between the two means the node is rejecting transactions,
+    /// low rates with a small gap mean the client is the limiting factor
+    pub fn with_submission_rate(
+        &mut self,
+        submitted: &[SubmittedTransaction],
+        attempted: &[SystemTime],
+    ) {
+        let attempted_tps = rate(attempted.iter().map(|attempt| unix_secs(*attempt)));
+        let submission_tps = rate(submitted.iter().map(|tx| unix_secs(tx.submitted_at)));
+
+        if let Some(attempted_tps) = attempted_tps {
+            self.metrics.push(MetricResult {
+                name: "Average Attempted TPS",
+                unit: "transactions/second",
+                value: attempted_tps.into(),
+            });
+        }
+
+        if let Some(submission_tps) = submission_tps {
+            self.metrics.push(MetricResult {
+                name: "Average Submission TPS",
+                unit: "transactions/second",
+                value: submission_tps.into(),
+            });
+        }
+    }
+
+    /// Record how many calls every transaction packs when there is more than one
+    pub fn with_calls_per_tx(&mut self, calls_per_tx: u64) {
+        if calls_per_tx > 1 {
+            self.metrics.push(MetricResult {
+                name: "Calls per Transaction",
+                unit: "calls/transaction",
+                value: calls_per_tx.into(),
+            });
+        }
+    }
+
+    /// Summarize the time-to-inclusion measured by polling the receipts of the transactions
+    /// with `report.inclusion_times`, which unlike goose's times includes the block time
+    pub fn with_inclusion_times(&mut self, inclusion_times: &[Duration]) {
+        if inclusion_times.is_empty() {
+            return;
+        }
+
+        let mut times: Vec<f64> = inclusion_times.iter().map(Duration::as_secs_f64).collect();
+        times.sort_unstable_by(f64::total_cmp);
+
+        let average = times.iter().sum::<f64>() / times.len() as f64;
+
+        self.metrics.extend_from_slice(&[
+            MetricResult {
+                name: "Min Time to Inclusion",
+                unit: "seconds",
+                value: times[0].into(),
+            },
+            MetricResult {
+                name: "Average Time to Inclusion",
+                unit: "seconds",
+                value: average.into(),
+            },
+            MetricResult {
+                name: "P50 Time to Inclusion",
+                unit: "seconds",
+                value: percentile(&times, 50.0).into(),
+            },
+            MetricResult {
+                name: "P90 Time to Inclusion",
+                unit: "seconds",
+                value: percentile(&times, 90.0).into(),
+            },
+            MetricResult {
+                name: "Max Time to Inclusion",
+                unit: "seconds",
+                value: times[times.len() - 1].into(),
+            },
+        ]);
+    }
+
+    /// Record the TPS the transactions were paced at with `run.target_tps`, to compare with
+    /// the `Average Submission TPS` the node actually accepted them at
+    pub fn with_target_tps(&mut self, target_tps: Option<u64>) {
+        if let Some(target_tps) = target_tps {
+            self.metrics.push(MetricResult {
+                name: "Target TPS",
+                unit: "transactions/second",
+                value: target_tps.into(),
+            });
+        }
+    }
+
+    /// Overlay the TPS achieved by the submitted transactions on the rates requested by the load profile
+    pub fn with_load_profile(
+        &mut self,
+        requested_rates: &[RequestedRate],
+        submitted: &[SubmittedTransaction],
+    ) {
+        let Some(profile_start) = requested_rates.first().map(|rate| unix_secs(rate.start)) else {
+            return;
+        };
+
+        self.load_profile = requested_rates
+            .iter()
+            .map(|rate| {
+                let start = unix_secs(rate.start);
+                let end = start + rate.duration;
+
+                let sent = submitted
+                    .iter()
+                    .map(|tx| unix_secs(tx.submitted_at))
+                    .filter(|submitted_at| (start..end).contains(submitted_at))
+                    .count();
+
+                ProfileSegment {
+                    start: start - profile_start,
+                    duration: rate.duration,
+                    requested_tps: rate.tps,
+                    achieved_tps: sent as f64 / rate.duration,
+                }
+            })
+            .collect();
+    }
+
+    /// Value of the metric called `name`, if it was computed and is a number
+    pub fn metric(&self, name: &str) -> Option<f64> {
+        self.metrics
+            .iter()
+            .find(|metric| metric.name == name)
+            .and_then(|metric| metric.value.as_f64())
+    }
+
+    /// Correlate the actual fee of the included transactions, read from their receipts,
+    /// with their time-to-inclusion
+    pub async fn with_fee_correlation(
+        &mut self,
+        starknet_rpc: &Arc<StarknetRpc>,
+        max_retries: u32,
+        submitted: &[SubmittedTransaction],
+        blocks: &[BlockWithTxs],
+        receipt_batch_size: usize,
+    ) -> Result<()> {
+        let inclusion_times: HashMap<FieldElement, f64> = inclusion_timings(submitted, blocks)
+            .into_iter()
+            .map(|(hash, submitted, included)| (hash, included - submitted))
+            .collect();
+
+        let receipts = get_receipts(
+            starknet_rpc,
+            inclusion_times.keys().copied(),
+            receipt_batch_size,
+            max_retries,
+        )
+        .await?;
+
+        let mut samples: Vec<(u128, f64)> = receipts
+            .into_iter()
+            .filter_map(|(hash, receipt)| {
+                let ReceiptLookup::Included(receipt) = receipt else {
+                    return None;
+                };
+
+                let fee = u128::try_from(actual_fee(&receipt)?).ok()?;
+
+                Some((fee, inclusion_times[&hash]))
+            })
+            .collect();
+
+        if samples.is_empty() {
+            return Ok(());
+        }
+
+        samples.sort_unstable_by_key(|(fee, _)| *fee);
+
+        let values: Vec<(f64, f64)> = samples
+            .iter()
+            .map(|(fee, inclusion_time)| (*fee as f64, *inclusion_time))
+            .collect();
+
+        let quartiles = samples
+            .chunks(samples.len().div_ceil(4))
+            .map(|quartile| FeeQuartile {
+                min_fee: quartile[0].0,
+                max_fee: quartile[quartile.len() - 1].0,
+                transactions: quartile.len(),
+                average_inclusion_time: quartile.iter().map(|(_, time)| time).sum::<f64>()
+                    / quartile.len() as f64,
+            })
+            .collect();
+
+        self.fee_correlation = Some(FeeCorrelation {
+            transactions: samples.len(),
+            coefficient: pearson_correlation(&values),
+            quartiles,
+        });
+
+        Ok(())
+    }
+
+    pub fn with_duplicates(&mut self, duplicates: DuplicateSubmissions) {
+        self.metrics.extend_from_slice(&[
+            MetricResult {
+                name: "Duplicate Submissions",
+                unit: "",
+                value: (duplicates.accepted + duplicates.rejected).into(),
+            },
+            MetricResult {
+                name: "Duplicates Accepted",
+                unit: "",
+                value: duplicates.accepted.into(),
+            },
+            MetricResult {
+                name: "Duplicates Rejected",
+                unit: "",
+                value: duplicates.rejected.into(),
+            },
+        ]);
+    }
+
+    pub fn with_signing_keys(&mut self, signing_keys: u64) {
+        self.metrics.push(MetricResult {
+            name: "Distinct Signing Keys",
+            unit: "",
+            value: signing_keys.into(),
+        });
+    }
+
+    /// Break down the time spent after submitting the transactions, waiting for the last
+    /// transaction of every user and then verifying the receipts of all of them
+    pub fn with_phase_timings(
+        &mut self,
+        finalizing: Option<PhaseTiming>,
+        verification: Option<PhaseTiming>,
+    ) {
+        if let Some(finalizing) = finalizing {
+            self.metrics.push(MetricResult {
+                name: "Finalizing Wall Time",
+                unit: "seconds",
+                value: finalizing.duration.as_secs_f64().into(),
+            });
+        }
+
+        if let Some(verification) = verification {
+            let duration = verification.duration.as_secs_f64();
+
+            self.metrics.extend_from_slice(&[
+                MetricResult {
+                    name: "Verification Wall Time",
+                    unit: "seconds",
+                    value: duration.into(),
+                },
+                MetricResult {
+                    name: "Verified Transactions",
+                    unit: "",
+                    value: verification.transactions.into(),
+                },
+                MetricResult {
+                    name: "Verification Throughput",
+                    unit: "transactions/second",
+                    value: (verification.transactions as f64 / duration).into(),
+                },
+            ]);
+        }
+    }
+
+    /// Fold in the metrics measured by goose, along with the amount of `reverted`
+    /// transactions found while verifying them
+    pub fn with_goose_metrics(
+        &mut self,
+        metrics: &GooseMetrics,
+        reverted: u64,
+        percentiles: &[f64],
+    ) -> Result<()> {
         let transactions = metrics
             .transactions
             .first()
             .ok_or(eyre!("Could no find scenario's transactions"))?;
 
-        let [_setup, requests, _finalizing, verification] = transactions.as_slice() else {
+        let [_setup, sends @ .., _finalizing, verification] = transactions.as_slice() else {
             bail!("Failed at getting all transaction aggragates")
         };
+        let requests = &merge_transactions(sends)?;
+
+        // Only a mixed benchmark sends more than one workload
+        if let [_, _, ..] = sends {
+            self.workloads = sends
+                .iter()
+                .map(|send| WorkloadSubmissions {
+                    name: send.transaction_name.clone(),
+                    transactions: send.counter,
+                    failed: send.fail_count,
+                    share: send.counter as f64 / requests.counter as f64,
+                    average_time: transaction_average(send),
+                    max_time: send.max_time,
+                })
+                .collect();
+        }
 
         let verification_requests = metrics
             .requests
@@ -136,6 +1341,12 @@ impl BenchmarkReport {
 
         const GOOSE_TIME_UNIT: &str = "milliseconds";
 
+        self.error_breakdown = error_breakdown(metrics);
+
+        let verified = verification_requests.success_count + verification_requests.fail_count;
+        // Receipts of reverted transactions are found all the same
+        let confirmed = (verification_requests.success_count as u64).saturating_sub(reverted);
+
         self.metrics.extend_from_slice(&[
             MetricResult {
                 name: "Total Submission Time",
@@ -152,6 +1363,26 @@ impl BenchmarkReport {
                 unit: "",
                 value: verification_requests.fail_count.into(),
             },
+            MetricResult {
+                name: "Reverted Transactions",
+                unit: "",
+                value: reverted.into(),
+            },
+            MetricResult {
+                name: "Revert Rate",
+                unit: "",
+                value: (reverted as f64 / verified.max(1) as f64).into(),
+            },
+            MetricResult {
+                name: "Confirmed Transactions",
+                unit: "",
+                value: confirmed.into(),
+            },
+            MetricResult {
+                name: "Confirmation Rate",
+                unit: "",
+                value: (confirmed as f64 / self.amount as f64).into(),
+            },
             MetricResult {
                 name: "Failed Transaction Submissions",
                 unit: "",
@@ -189,10 +1420,255 @@ impl BenchmarkReport {
             },
         ]);
 
+        self.time_percentiles = percentiles
+            .iter()
+            .map(|&percentile| {
+                let times = TimePercentiles {
+                    submission: histogram_percentile(&requests.times, percentile),
+                    verification: histogram_percentile(
+                        &verification_requests.raw_data.times,
+                        percentile,
+                    ),
+                };
+
+                (percentile, times)
+            })
+            .collect();
+
         Ok(())
     }
 }
 
+/// Submission and inclusion of the submitted transactions found in `blocks`,
+/// as `(hash, submitted, included)` with unix timestamps in seconds
+///
+/// Inclusion is clamped to the submission since block timestamps are only
+/// precise to the second
+fn inclusion_timings(
+    submitted: &[SubmittedTransaction],
+    blocks: &[BlockWithTxs],
+) -> Vec<(FieldElement, f64, f64)> {
+    let block_timestamps: HashMap<FieldElement, u64> = blocks
+        .iter()
+        .flat_map(|block| {
+            block
+                .transactions
+                .iter()
+                .map(|tx| (*tx.transaction_hash(), block.timestamp))
+        })
+        .collect();
+
+    submitted
+        .iter()
+        .filter_map(|tx| {
+            let included_at = *block_timestamps.get(&tx.hash)? as f64;
+            let submitted_at = unix_secs(tx.submitted_at);
+
+            Some((tx.hash, submitted_at, included_at.max(submitted_at)))
+        })
+        .collect()
+}
+
+/// Pearson correlation coefficient, `None` when either of the values doesn't vary
+fn pearson_correlation(values: &[(f64, f64)]) -> Option<f64> {
+    let n = values.len() as f64;
+    let mean_x = values.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = values.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let (mut covariance, mut variance_x, mut variance_y) = (0.0, 0.0, 0.0);
+
+    for (x, y) in values {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+        variance_y += (y - mean_y).powi(2);
+    }
+
+    if variance_x == 0.0 || variance_y == 0.0 {
+        return None;
+    }
+
+    Some(covariance / (variance_x * variance_y).sqrt())
+}
+
+/// Amount of events per second between the first and the last one,
+/// `None` when they all happened at once
+fn rate(timestamps: impl Iterator<Item = f64>) -> Option<f64> {
+    let (mut count, mut first, mut last) = (0, f64::INFINITY, f64::NEG_INFINITY);
+
+    for timestamp in timestamps {
+        count += 1;
+        first = first.min(timestamp);
+        last = last.max(timestamp);
+    }
+
+    (last > first).then(|| count as f64 / (last - first))
+}
+
+/// Nearest-rank percentile of already sorted values
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn unix_secs(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or_default()
+}
+
+/// Serializes a report with its floating point numbers rounded to `precision` decimal places,
+/// or at full precision without one
+pub struct Rounded<'a, T> {
+    pub report: &'a T,
+    pub precision: Option<u32>,
+}
+
+impl<T: serde::Serialize> serde::Serialize for Rounded<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(precision) = self.precision else {
+            return self.report.serialize(serializer);
+        };
+
+        let mut value = serde_json::to_value(self.report).map_err(S::Error::custom)?;
+        round_floats(&mut value, 10f64.powi(precision as i32));
+
+        value.serialize(serializer)
+    }
+}
+
+fn round_floats(value: &mut Value, scale: f64) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            let rounded = number.as_f64().map(|float| (float * scale).round() / scale);
+
+            // Numbers too large to be scaled stay as they are
+            if let Some(rounded) = rounded.and_then(serde_json::Number::from_f64) {
+                *number = rounded;
+            }
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| round_floats(value, scale)),
+        Value::Object(values) => values
+            .values_mut()
+            .for_each(|value| round_floats(value, scale)),
+        _ => {}
+    }
+}
+
+/// Label value escaped for the Prometheus text exposition
+fn prometheus_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Quote a CSV field when it holds a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Tag value with the commas, equal signs and spaces escaped
+fn influx_tag(value: &str) -> String {
+    value
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// Metric name as a snake case field key, `Average TPS (own traffic)` being `average_tps_own_traffic`
+fn influx_field_key(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn display_option(value: Option<usize>) -> String {
+    value.map_or("none".into(), |value| value.to_string())
+}
+
+/// Nearest-rank percentile of the times goose recorded, as a histogram of time to amount of requests
+fn histogram_percentile(times: &BTreeMap<usize, usize>, percentile: f64) -> Option<usize> {
+    let total: usize = times.values().sum();
+    let rank = ((percentile / 100.0 * total as f64).ceil() as usize).max(1);
+
+    let mut seen = 0;
+    times.iter().find_map(|(&time, &count)| {
+        seen += count;
+        (seen >= rank).then_some(time)
+    })
+}
+
+/// Serialize the percentiles as an object keyed by the percentile, such as `"99.9"`
+fn serialize_time_percentiles<S: Serializer>(
+    time_percentiles: &[(f64, TimePercentiles)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        time_percentiles
+            .iter()
+            .map(|(percentile, times)| (percentile.to_string(), times)),
+    )
+}
+
+/// Transactions the users sent between their setup and their finalizing, over all the workloads
+pub fn sent_transactions(metrics: &GooseMetrics) -> Result<usize> {
+    let transactions = metrics
+        .transactions
+        .first()
+        .ok_or(eyre!("Could no find scenario's transactions"))?;
+
+    match transactions.as_slice() {
+        [_setup, sends @ .., _finalizing, _verification] => {
+            Ok(sends.iter().map(|send| send.counter).sum())
+        }
+        _ => bail!("Failed at getting all transaction aggragates"),
+    }
+}
+
+/// Aggregate of the transactions of all the workloads of a benchmark, as if they were one
+fn merge_transactions(sends: &[TransactionMetricAggregate]) -> Result<TransactionMetricAggregate> {
+    let (first, others) = sends.split_first().ok_or(eyre!(
+        "Found no transaction between the setup and the finalizing"
+    ))?;
+    let mut merged = first.clone();
+
+    for send in others {
+        for (time, counter) in &send.times {
+            *merged.times.entry(*time).or_default() += counter;
+        }
+
+        // The min time of a transaction that never ran is 0
+        merged.min_time = match (merged.counter, send.counter) {
+            (_, 0) => merged.min_time,
+            (0, _) => send.min_time,
+            _ => merged.min_time.min(send.min_time),
+        };
+        merged.max_time = merged.max_time.max(send.max_time);
+        merged.total_time += send.total_time;
+        merged.counter += send.counter;
+        merged.success_count += send.success_count;
+        merged.fail_count += send.fail_count;
+    }
+
+    Ok(merged)
+}
+
 fn transaction_average(requests: &TransactionMetricAggregate) -> f64 {
     requests.total_time as f64 / requests.counter as f64
 }
@@ -211,7 +1687,21 @@ impl fmt::Display for BenchmarkReport {
             name,
             amount,
             metrics,
+            time_percentiles,
             last_x_blocks_metrics: last_x_blocks,
+            inclusion_time_by_depth,
+            fee_correlation,
+            fee_histogram,
+            load_profile,
+            account_distribution,
+            per_block_throughput: _,
+            mempool_samples: _,
+            uops_breakdown,
+            error_breakdown,
+            workloads,
+            failed_requests: _,
+            block_range: _,
+            block_time,
         } = self;
 
         writeln!(f, "Benchmark Report: {name} ({amount})")?;
@@ -220,6 +1710,55 @@ impl fmt::Display for BenchmarkReport {
             writeln!(f, "{metric}")?;
         }
 
+        if let Some(BlockTime { average, min, max }) = block_time {
+            writeln!(
+                f,
+                "Block Time: {average} seconds on average, min {min} seconds, max {max} seconds"
+            )?;
+        }
+
+        for (percentile, times) in time_percentiles {
+            writeln!(
+                f,
+                "P{percentile} Submission Time: {} milliseconds, P{percentile} Verification Time: {} milliseconds",
+                display_option(times.submission),
+                display_option(times.verification)
+            )?;
+        }
+
+        if !error_breakdown.is_empty() {
+            let errors: Vec<String> = error_breakdown
+                .iter()
+                .map(|(category, occurrences)| format!("{occurrences} {category}"))
+                .collect();
+
+            writeln!(f, "Errors: {}", errors.join(", "))?;
+        }
+
+        for WorkloadSubmissions {
+            name,
+            transactions,
+            failed,
+            share,
+            average_time,
+            max_time,
+        } in workloads
+        {
+            writeln!(
+                f,
+                "{name}: {transactions} transactions ({:.1}%), {failed} failed, {average_time:.1} milliseconds on average and {max_time} at most to submit",
+                share * 100.0
+            )?;
+        }
+
+        if !uops_breakdown.is_empty() {
+            writeln!(f, "UOPS breakdown:")?;
+
+            for (target, uops) in uops_breakdown {
+                writeln!(f, "{target}: {uops} operations/second")?;
+            }
+        }
+
         if let Some(last_x_blocks) = last_x_blocks {
             writeln!(f, "Last {} block metrics:", last_x_blocks.num_blocks)?;
 
@@ -228,29 +1767,438 @@ impl fmt::Display for BenchmarkReport {
             }
         }
 
+        if !inclusion_time_by_depth.is_empty() {
+            writeln!(f, "Time-to-inclusion by in-flight depth:")?;
+
+            for bucket in inclusion_time_by_depth {
+                writeln!(
+                    f,
+                    "{} ({}-{}): {} transactions, P50 {} seconds, P90 {} seconds",
+                    bucket.name,
+                    bucket.min_depth,
+                    bucket.max_depth,
+                    bucket.transactions,
+                    bucket.p50_inclusion_time,
+                    bucket.p90_inclusion_time
+                )?;
+            }
+        }
+
+        if let Some(fee_correlation) = fee_correlation {
+            match fee_correlation.coefficient {
+                Some(coefficient) => writeln!(
+                    f,
+                    "Fee to time-to-inclusion correlation: {coefficient} ({} transactions)",
+                    fee_correlation.transactions
+                )?,
+                None => writeln!(
+                    f,
+                    "Fee to time-to-inclusion correlation: undefined ({} transactions)",
+                    fee_correlation.transactions
+                )?,
+            }
+
+            for quartile in &fee_correlation.quartiles {
+                writeln!(
+                    f,
+                    "Fee {}-{}: {} transactions, average {} seconds",
+                    quartile.min_fee,
+                    quartile.max_fee,
+                    quartile.transactions,
+                    quartile.average_inclusion_time
+                )?;
+            }
+        }
+
+        if !fee_histogram.is_empty() {
+            writeln!(f, "Fee histogram:")?;
+
+            for bucket in fee_histogram {
+                let max_fee = bucket
+                    .max_fee
+                    .map_or("".into(), |max_fee| max_fee.to_string());
+
+                writeln!(
+                    f,
+                    "{}-{max_fee} wei: {} transactions ({:.2}%)",
+                    bucket.min_fee,
+                    bucket.transactions,
+                    bucket.share * 100.0
+                )?;
+            }
+        }
+
+        if !load_profile.is_empty() {
+            writeln!(f, "Load profile:")?;
+
+            for segment in load_profile {
+                writeln!(
+                    f,
+                    "{}s-{}s: requested {} TPS, achieved {} TPS",
+                    segment.start,
+                    segment.start + segment.duration,
+                    segment.requested_tps,
+                    segment.achieved_tps
+                )?;
+            }
+        }
+
+        if !account_distribution.is_empty() {
+            writeln!(f, "Account distribution:")?;
+
+            for account in account_distribution {
+                writeln!(
+                    f,
+                    "{:#064x}: {} transactions ({:.2}%)",
+                    account.address,
+                    account.transactions,
+                    account.share * 100.0
+                )?;
+            }
+        }
+
         Ok(())
     }
 }
 
-pub fn compute_node_metrics(num_tx_per_block: Vec<u64>) -> [MetricResult; 2] {
+pub fn compute_node_metrics(blocks: &[BlockWithTxs]) -> [MetricResult; 7] {
+    let num_tx_per_block: Vec<u64> = blocks
+        .iter()
+        .map(|block| block.transactions.len() as u64)
+        .collect();
+
+    let calldata_lengths: Vec<u64> = blocks
+        .iter()
+        .flat_map(|block| &block.transactions)
+        .map(calldata_len)
+        .collect();
+
+    let tx_sizes: Vec<u64> = blocks
+        .iter()
+        .flat_map(|block| &block.transactions)
+        .map(tx_size)
+        .collect();
+
+    let num_ops_per_block: Vec<u64> = blocks
+        .iter()
+        .map(|block| block.transactions.iter().map(user_operations).sum())
+        .collect();
+
     [
         MetricResult {
             name: "Average TPS",
             unit: "transactions/second",
             value: average_tps(&num_tx_per_block).into(),
         },
+        MetricResult {
+            name: "Average UOPS",
+            unit: "operations/second",
+            value: average_tps(&num_ops_per_block).into(),
+        },
         MetricResult {
             name: "Average Extrinsics per block",
             unit: "extrinsics/block",
             value: average_tpb(&num_tx_per_block).into(),
         },
+        MetricResult {
+            name: "Average Calldata Size",
+            unit: "felts/transaction",
+            value: average_calldata_len(&calldata_lengths).into(),
+        },
+        MetricResult {
+            name: "Max Calldata Size",
+            unit: "felts",
+            value: calldata_lengths.iter().max().copied().unwrap_or(0).into(),
+        },
+        MetricResult {
+            name: "Total Calldata Size",
+            unit: "felts",
+            value: calldata_lengths.iter().sum::<u64>().into(),
+        },
+        MetricResult {
+            name: "Average Transaction Size",
+            unit: "bytes/transaction",
+            value: average_calldata_len(&tx_sizes).into(),
+        },
     ]
 }
 
+/// Total and average fee paid by the transactions of `blocks`, in wei, leaving out the
+/// fees paid in STRK
+fn fee_metrics(fees: &[u128]) -> Vec<MetricResult> {
+    if fees.is_empty() {
+        return Vec::new();
+    }
+
+    let total: u128 = fees.iter().sum();
+
+    vec![
+        MetricResult {
+            name: "Total Fee",
+            unit: "wei",
+            value: u64::try_from(total)
+                .map(Value::from)
+                .unwrap_or_else(|_| (total as f64).into()),
+        },
+        MetricResult {
+            name: "Average Fee",
+            unit: "wei/transaction",
+            value: (total as f64 / fees.len() as f64).into(),
+        },
+    ]
+}
+
+/// Fees paid by the transactions of `blocks` in wei, read from their receipts
+async fn receipt_fees(
+    starknet_rpc: &Arc<StarknetRpc>,
+    max_retries: u32,
+    blocks: &[BlockWithTxs],
+    receipt_batch_size: usize,
+) -> Result<Vec<u128>> {
+    let tx_hashes = blocks
+        .iter()
+        .flat_map(|block| &block.transactions)
+        .map(|tx| *tx.transaction_hash());
+
+    Ok(
+        get_receipts(starknet_rpc, tx_hashes, receipt_batch_size, max_retries)
+            .await?
+            .into_iter()
+            .filter_map(|(_, receipt)| match receipt {
+                ReceiptLookup::Included(receipt) => {
+                    actual_fee(&receipt).and_then(|fee| u128::try_from(fee).ok())
+                }
+                ReceiptLookup::Pending | ReceiptLookup::NotFound => None,
+            })
+            .collect(),
+    )
+}
+
+/// Transactions whose fee falls in every bucket, from `0` to the first of `bounds`, between each of
+/// them and from the last one up, a fee equal to a bound falling in the bucket starting at it
+fn fee_histogram(fees: &[u128], bounds: &[u64]) -> Vec<FeeBucket> {
+    let mut bounds: Vec<u128> = bounds.iter().map(|&bound| u128::from(bound)).collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mins = std::iter::once(0).chain(bounds.iter().copied());
+    let maxes = bounds.iter().copied().map(Some).chain([None]);
+
+    mins.zip(maxes)
+        .map(|(min_fee, max_fee)| {
+            let transactions = fees
+                .iter()
+                .filter(|&&fee| fee >= min_fee && max_fee.is_none_or(|max_fee| fee < max_fee))
+                .count();
+
+            FeeBucket {
+                min_fee,
+                max_fee,
+                transactions,
+                share: transactions as f64 / fees.len() as f64,
+            }
+        })
+        .collect()
+}
+
+/// Transactions per second of every block whose parent is in `blocks`,
+/// blocks sharing the timestamp of their parent have an infinite throughput
+fn per_block_throughput(blocks: &[BlockWithTxs]) -> Vec<(u64, f64)> {
+    blocks
+        .windows(2)
+        .filter(|pair| pair[1].block_number == pair[0].block_number + 1)
+        .map(|pair| {
+            let interval = pair[1].timestamp.saturating_sub(pair[0].timestamp);
+
+            (
+                pair[1].block_number,
+                pair[1].transactions.len() as f64 / interval as f64,
+            )
+        })
+        .collect()
+}
+
+/// Intervals between the blocks that follow each other, there are none with a single block
+fn block_time(blocks: &[BlockWithTxs]) -> Option<BlockTime> {
+    let intervals: Vec<u64> = blocks
+        .windows(2)
+        .filter(|pair| pair[1].block_number == pair[0].block_number + 1)
+        .map(|pair| pair[1].timestamp.saturating_sub(pair[0].timestamp))
+        .collect();
+
+    Some(BlockTime {
+        average: intervals.iter().sum::<u64>() as f64 / intervals.len() as f64,
+        min: *intervals.iter().min()?,
+        max: *intervals.iter().max()?,
+    })
+}
+
+/// Occurrences of the errors goose recorded, summed by [`error_category`]
+fn error_breakdown(metrics: &GooseMetrics) -> BTreeMap<&'static str, usize> {
+    let mut breakdown = BTreeMap::new();
+
+    for error in metrics.errors.values() {
+        *breakdown.entry(error_category(&error.error)).or_default() += error.occurrences;
+    }
+
+    breakdown
+}
+
+/// Category of the tag a goose user failed a request with
+fn error_category(tag: &str) -> &'static str {
+    let code = tag
+        .strip_prefix("RPC Response was Error ")
+        .or_else(|| tag.strip_prefix("Error Code "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|code| code.parse::<i64>().ok());
+
+    if tag.ends_with("has been rejected/reverted") {
+        "revert"
+    } else if tag.starts_with("Timeout while waiting") {
+        "timeout"
+    } else if let Some(code) = code {
+        const INVALID_TRANSACTION_NONCE: i64 = 52;
+
+        if code == INVALID_TRANSACTION_NONCE {
+            "nonce_error"
+        } else {
+            "rpc_error"
+        }
+    } else if tag.starts_with("RPC Response") {
+        "rpc_error"
+    } else {
+        "other"
+    }
+}
+
+/// Whenever possible, skip the first and last blocks from the metrics
+/// to make sure all the blocks used for calculating metrics are full
+fn full_blocks(blocks: &[BlockWithTxs]) -> &[BlockWithTxs] {
+    if blocks.len() > 3 {
+        &blocks[1..blocks.len() - 1]
+    } else {
+        blocks
+    }
+}
+
+/// Fee paid by a transaction in wei, `None` for the v3 transactions paying in STRK
+fn actual_fee(receipt: &TransactionReceipt) -> Option<FieldElement> {
+    let fee = match receipt {
+        TransactionReceipt::Invoke(receipt) => &receipt.actual_fee,
+        TransactionReceipt::L1Handler(receipt) => &receipt.actual_fee,
+        TransactionReceipt::Declare(receipt) => &receipt.actual_fee,
+        TransactionReceipt::Deploy(receipt) => &receipt.actual_fee,
+        TransactionReceipt::DeployAccount(receipt) => &receipt.actual_fee,
+    };
+
+    (fee.unit == PriceUnit::Wei).then_some(fee.amount)
+}
+
+/// Address of the account that sent a transaction, when it has one
+fn sender_address(tx: &Transaction) -> Option<FieldElement> {
+    match tx {
+        Transaction::Invoke(InvokeTransaction::V0(tx)) => Some(tx.contract_address),
+        Transaction::Invoke(InvokeTransaction::V1(tx)) => Some(tx.sender_address),
+        Transaction::Invoke(InvokeTransaction::V3(tx)) => Some(tx.sender_address),
+        Transaction::Declare(DeclareTransaction::V0(tx)) => Some(tx.sender_address),
+        Transaction::Declare(DeclareTransaction::V1(tx)) => Some(tx.sender_address),
+        Transaction::Declare(DeclareTransaction::V2(tx)) => Some(tx.sender_address),
+        Transaction::Declare(DeclareTransaction::V3(tx)) => Some(tx.sender_address),
+        Transaction::L1Handler(_) | Transaction::Deploy(_) | Transaction::DeployAccount(_) => None,
+    }
+}
+
+/// User operations of a transaction, the calls of a multicall invoke and one for the others
+fn user_operations(tx: &Transaction) -> u64 {
+    match tx {
+        Transaction::Invoke(InvokeTransaction::V1(tx)) => calls_count(&tx.calldata),
+        Transaction::Invoke(InvokeTransaction::V3(tx)) => calls_count(&tx.calldata),
+        _ => 1,
+    }
+}
+
+/// Amount of calls of an invoke calldata, both the legacy and the new encodings
+/// of the calls start with it
+fn calls_count(calldata: &[FieldElement]) -> u64 {
+    calldata
+        .first()
+        .and_then(|calls| u64::try_from(*calls).ok())
+        .unwrap_or(1)
+}
+
+/// Contract address or entrypoint selector the first call of an invoke goes to
+fn operation_target(tx: &Transaction, breakdown: UopsBreakdown) -> String {
+    let (contract_address, selector) = match tx {
+        Transaction::Invoke(InvokeTransaction::V0(tx)) => {
+            (Some(tx.contract_address), Some(tx.entry_point_selector))
+        }
+        // Both the legacy and the new encodings of the calls start with `to` and
+        // `selector` of the first call, right after the amount of calls
+        Transaction::Invoke(InvokeTransaction::V1(tx)) => {
+            (tx.calldata.get(1).copied(), tx.calldata.get(2).copied())
+        }
+        Transaction::Invoke(InvokeTransaction::V3(tx)) => {
+            (tx.calldata.get(1).copied(), tx.calldata.get(2).copied())
+        }
+        Transaction::L1Handler(_) => return "L1 Handler".into(),
+        Transaction::Declare(_) => return "Declare".into(),
+        Transaction::Deploy(_) => return "Deploy".into(),
+        Transaction::DeployAccount(_) => return "Deploy Account".into(),
+    };
+
+    let target = match breakdown {
+        UopsBreakdown::Contract => contract_address,
+        UopsBreakdown::Selector => selector,
+    };
+
+    match target {
+        Some(target) => format!("{target:#x}"),
+        None => "Unknown".into(),
+    }
+}
+
+/// Length of the calldata of a transaction, for deployments this is the constructor calldata
+fn calldata_len(tx: &Transaction) -> u64 {
+    let calldata = match tx {
+        Transaction::Invoke(InvokeTransaction::V0(tx)) => &tx.calldata,
+        Transaction::Invoke(InvokeTransaction::V1(tx)) => &tx.calldata,
+        Transaction::Invoke(InvokeTransaction::V3(tx)) => &tx.calldata,
+        Transaction::L1Handler(tx) => &tx.calldata,
+        Transaction::Deploy(tx) => &tx.constructor_calldata,
+        Transaction::DeployAccount(DeployAccountTransaction::V1(tx)) => &tx.constructor_calldata,
+        Transaction::DeployAccount(DeployAccountTransaction::V3(tx)) => &tx.constructor_calldata,
+        Transaction::Declare(_) => return 0,
+    };
+
+    calldata.len() as u64
+}
+
+/// Estimated size of a transaction in bytes, its calldata and signature felts taking 32 bytes each
+fn tx_size(tx: &Transaction) -> u64 {
+    let signature = match tx {
+        Transaction::Invoke(InvokeTransaction::V0(tx)) => &tx.signature,
+        Transaction::Invoke(InvokeTransaction::V1(tx)) => &tx.signature,
+        Transaction::Invoke(InvokeTransaction::V3(tx)) => &tx.signature,
+        Transaction::Declare(DeclareTransaction::V0(tx)) => &tx.signature,
+        Transaction::Declare(DeclareTransaction::V1(tx)) => &tx.signature,
+        Transaction::Declare(DeclareTransaction::V2(tx)) => &tx.signature,
+        Transaction::Declare(DeclareTransaction::V3(tx)) => &tx.signature,
+        Transaction::DeployAccount(DeployAccountTransaction::V1(tx)) => &tx.signature,
+        Transaction::DeployAccount(DeployAccountTransaction::V3(tx)) => &tx.signature,
+        Transaction::L1Handler(_) | Transaction::Deploy(_) => return calldata_len(tx) * 32,
+    };
+
+    (calldata_len(tx) + signature.len() as u64) * 32
+}
+
+fn average_calldata_len(calldata_lengths: &[u64]) -> f64 {
+    calldata_lengths.iter().sum::<u64>() as f64 / calldata_lengths.len().max(1) as f64
+}
+
 fn average_tps(num_tx_per_block: &[u64]) -> f64 {
     average_tpb(num_tx_per_block) / BLOCK_TIME as f64
 }
 
 fn average_tpb(num_tx_per_block: &[u64]) -> f64 {
-    num_tx_per_block.iter().sum::<u64>() as f64 / num_tx_per_block.len() as f64
+    num_tx_per_block.iter().sum::<u64>() as f64 / num_tx_per_block.len().max(1) as f64
 }
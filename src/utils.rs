@@ -1,21 +1,128 @@
+use std::future::Future;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::SystemTime;
 
-use color_eyre::{eyre::eyre, Result};
+use async_trait::async_trait;
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use futures::{SinkExt, StreamExt};
 use lazy_static::lazy_static;
-use log::debug;
+use log::{debug, info, warn};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::config::StartupGracePeriod;
 
-use starknet::core::types::{BlockId, ExecutionResult, StarknetError};
+use starknet::core::types::MaybePendingTransactionReceipt::{PendingReceipt, Receipt};
+use starknet::core::types::{
+    BlockId, BlockStatus, BlockTag, BlockWithTxs, ExecutionResult, MaybePendingBlockWithTxHashes,
+    MaybePendingBlockWithTxs, PendingBlockWithTxs, ResourceBounds, ResourceBoundsMapping,
+    StarknetError, TransactionReceipt,
+};
 use starknet::core::{crypto::compute_hash_on_elements, types::FieldElement};
-use starknet::providers::{jsonrpc::HttpTransport, JsonRpcClient, Provider};
-use starknet::providers::{MaybeUnknownErrorCode, ProviderError};
-use starknet::{
-    core::types::MaybePendingTransactionReceipt::{PendingReceipt, Receipt},
-    providers::StarknetErrorWithMessage,
+use starknet::macros::felt;
+use starknet::providers::ProviderError;
+use starknet::providers::{
+    jsonrpc::{
+        HttpTransport, HttpTransportError, JsonRpcClientError, JsonRpcMethod, JsonRpcResponse,
+        JsonRpcTransport,
+    },
+    JsonRpcClient, Provider,
 };
+use starknet_crypto::poseidon_hash_many;
 
 use std::time::Duration;
-use sysinfo::{CpuExt, System, SystemExt};
+use sysinfo::{CpuExt, ProcessExt, System, SystemExt};
+use tokio::{
+    sync::{oneshot, watch},
+    task::{JoinHandle, JoinSet},
+};
+use tokio_tungstenite::tungstenite::Message;
+use url::Url;
+
+/// Delay before the first retry of a request, doubled after every retry
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+/// Longest delay between two retries of a request
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+type RpcResult<T> = std::result::Result<T, ProviderError>;
+
+/// Client of the nodes of `rpc.url`
+pub type StarknetRpc = JsonRpcClient<FailoverTransport>;
+
+/// Transport sending the requests to the first of its endpoints that is reachable
+///
+/// Requests go to the current endpoint until it fails with a connection level error,
+/// the request is then sent again to the next endpoints in turn, which stays the current
+/// one if it answers. With a single endpoint it is the same as a [`HttpTransport`]
+#[derive(Debug)]
+pub struct FailoverTransport {
+    endpoints: Vec<(Url, HttpTransport)>,
+    current: AtomicUsize,
+}
+
+impl FailoverTransport {
+    pub fn new(urls: impl IntoIterator<Item = Url>) -> Self {
+        Self {
+            endpoints: urls
+                .into_iter()
+                .map(|url| (url.clone(), HttpTransport::new(url)))
+                .collect(),
+            current: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcTransport for FailoverTransport {
+    type Error = HttpTransportError;
+
+    async fn send_request<P, R>(
+        &self,
+        method: JsonRpcMethod,
+        params: P,
+    ) -> std::result::Result<JsonRpcResponse<R>, Self::Error>
+    where
+        P: Serialize + Send,
+        R: DeserializeOwned,
+    {
+        // The request is sent again to the next endpoint on failure,
+        // `JsonRpcMethod` isn't `Clone` so it is kept serialized
+        let method = serde_json::to_value(method).map_err(HttpTransportError::Json)?;
+        let params = serde_json::to_value(params).map_err(HttpTransportError::Json)?;
+        let first = self.current.load(Ordering::Relaxed);
+        let mut last_error = None;
+
+        for attempt in 0..self.endpoints.len() {
+            let index = (first + attempt) % self.endpoints.len();
+            let (url, transport) = &self.endpoints[index];
+
+            let method =
+                serde_json::from_value(method.clone()).map_err(HttpTransportError::Json)?;
+
+            match transport.send_request(method, &params).await {
+                Err(HttpTransportError::Reqwest(err)) => {
+                    warn!("RPC endpoint {url} is unreachable: {err}");
+                    last_error = Some(HttpTransportError::Reqwest(err));
+                }
+                result => {
+                    if index != first {
+                        // Another request can have failed over concurrently, the last one to succeed wins
+                        self.current.store(index, Ordering::Relaxed);
+                        info!("Failed over to RPC endpoint {url}");
+                    }
+
+                    return result;
+                }
+            }
+        }
+
+        Err(last_error.expect("Transport should have at least one endpoint"))
+    }
+}
 
 lazy_static! {
     pub static ref SYSINFO: SysInfo = SysInfo::new();
@@ -52,6 +159,52 @@ pub fn compute_contract_address(
     ]) % ADDR_BOUND
 }
 
+/// Cairo string for "invoke"
+const PREFIX_INVOKE: FieldElement = felt!("0x696e766f6b65");
+
+/// Hash of a v3 invoke sent by `sender_address`, which its signature is over
+///
+/// Like the v3 invokes of starknet-rs, the invoke has no paymaster data nor account
+/// deployment data, and both its nonce and its fee are on L1
+pub fn invoke_v3_transaction_hash(
+    sender_address: FieldElement,
+    chain_id: FieldElement,
+    nonce: FieldElement,
+    calldata: &[FieldElement],
+    resource_bounds: &ResourceBoundsMapping,
+    tip: u64,
+) -> FieldElement {
+    let fee_hash = poseidon_hash_many(&[
+        FieldElement::from(tip),
+        resource_bound(b"L1_GAS", &resource_bounds.l1_gas),
+        resource_bound(b"L2_GAS", &resource_bounds.l2_gas),
+    ]);
+
+    poseidon_hash_many(&[
+        PREFIX_INVOKE,
+        FieldElement::THREE,
+        sender_address,
+        fee_hash,
+        poseidon_hash_many(&[]),
+        chain_id,
+        nonce,
+        FieldElement::ZERO,
+        poseidon_hash_many(&[]),
+        poseidon_hash_many(calldata),
+    ])
+}
+
+/// Resource bounds packed in a felt as the name of the resource followed by
+/// its max amount and its max price per unit
+fn resource_bound(name: &[u8; 6], bounds: &ResourceBounds) -> FieldElement {
+    let mut bytes = [0; 32];
+    bytes[2..8].copy_from_slice(name);
+    bytes[8..16].copy_from_slice(&bounds.max_amount.to_be_bytes());
+    bytes[16..].copy_from_slice(&bounds.max_price_per_unit.to_be_bytes());
+
+    FieldElement::from_bytes_be(&bytes).expect("Resource bounds should fit in a felt")
+}
+
 #[derive(Debug, Clone)]
 pub struct SysInfo {
     pub os_name: String,
@@ -105,79 +258,714 @@ pub fn sysinfo_string() -> String {
     )
 }
 
-const WAIT_FOR_TX_TIMEOUT: Duration = Duration::from_secs(60);
+/// Samples the resident memory of the Gatling process in the background until stopped
+pub struct MemorySampler {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<u64>,
+}
+
+impl MemorySampler {
+    pub fn start() -> Self {
+        const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+        let (stop, mut stopped) = oneshot::channel();
+
+        let handle = tokio::spawn(async move {
+            let mut sys = System::new();
+            let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+            let mut peak = 0;
+
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = interval.tick() => {}
+                }
+
+                peak = peak.max(process_memory(&mut sys));
+            }
+
+            peak
+        });
+
+        Self { stop, handle }
+    }
+
+    /// Stop sampling and return the peak and the current resident memory, in bytes
+    pub async fn stop(self) -> Result<(u64, u64)> {
+        // The sampler only stops by itself when it panicked, which `handle` reports
+        let _ = self.stop.send(());
+        let peak = self.handle.await?;
+        let current = process_memory(&mut System::new());
+
+        Ok((peak.max(current), current))
+    }
+}
+
+fn process_memory(sys: &mut System) -> u64 {
+    let Ok(pid) = sysinfo::get_current_pid() else {
+        return 0;
+    };
+
+    sys.refresh_process(pid);
+    sys.process(pid)
+        .map(|process| process.memory())
+        .unwrap_or(0)
+}
+
+/// Memory usage of the Gatling process, to append to `sysinfo_string`
+pub fn memory_string(peak: u64, current: u64) -> String {
+    let megabyte = 1024 * 1024;
+
+    format!(
+        "Peak Gatling Memory: {} MB\n\
+        Final Gatling Memory: {} MB",
+        peak / megabyte,
+        current / megabyte,
+    )
+}
+
+/// Wait for the node to be up and producing blocks, either until its block number
+/// advances or until it reaches `target_block` when set
+///
+/// Errors while the node is still starting up are retried until `timeout`
+pub async fn wait_for_block_production(
+    starknet_rpc: &StarknetRpc,
+    grace_period: &StartupGracePeriod,
+) -> Result<()> {
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    let timeout = Duration::from_secs(grace_period.timeout);
+    let start = SystemTime::now();
+    let mut first_block = None;
+
+    loop {
+        match starknet_rpc.block_number().await {
+            Ok(block) => {
+                match (grace_period.target_block, first_block) {
+                    (Some(target_block), _) if block >= target_block => {
+                        info!("Node reached block {block}, target was {target_block}");
+                        return Ok(());
+                    }
+                    (Some(target_block), _) => {
+                        info!("Waiting for the node to reach block {target_block}, currently at {block}")
+                    }
+                    (None, Some(first_block)) if block > first_block => {
+                        info!("Node is producing blocks, advanced from {first_block} to {block}");
+                        return Ok(());
+                    }
+                    (None, Some(_)) => {
+                        info!("Waiting for the node to produce a block, still at {block}")
+                    }
+                    (None, None) => {
+                        info!("Waiting for the node to advance from block {block}");
+                        first_block = Some(block);
+                    }
+                }
+            }
+            Err(err) => info!("Waiting for the node to be reachable: {err}"),
+        }
+
+        if start.elapsed().unwrap_or_default() >= timeout {
+            bail!(
+                "Node is not producing blocks after waiting for {} seconds",
+                grace_period.timeout
+            );
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
 
 pub async fn wait_for_tx(
-    provider: &JsonRpcClient<HttpTransport>,
+    provider: &StarknetRpc,
     tx_hash: FieldElement,
     check_interval: Duration,
+    max_interval: Duration,
+    timeout: Duration,
+    max_retries: u32,
 ) -> Result<()> {
     let start = SystemTime::now();
+    let mut interval = check_interval;
 
     loop {
-        if start.elapsed().unwrap() >= WAIT_FOR_TX_TIMEOUT {
+        if start.elapsed().unwrap() >= timeout {
             return Err(eyre!(
                 "Timeout while waiting for transaction {tx_hash:#064x}"
             ));
         }
 
-        match provider.get_transaction_receipt(tx_hash).await {
-            Ok(Receipt(receipt)) => match receipt.execution_result() {
-                ExecutionResult::Succeeded => {
-                    return Ok(());
-                }
-                ExecutionResult::Reverted { reason } => {
-                    return Err(eyre!(format!(
-                        "Transaction {tx_hash:#064x} has been rejected/reverted: {reason}"
-                    )));
+        if is_tx_accepted(provider, tx_hash, max_retries).await? {
+            return Ok(());
+        }
+
+        interval = backoff(interval, max_interval).await;
+    }
+}
+
+/// Wait for a transaction to be accepted, only looking up its receipt when
+/// `new_heads` notifies of a new block instead of polling it
+///
+/// Once the subscription drops, the rest of `timeout` is waited for by polling, see [`wait_for_tx`]
+pub async fn wait_for_tx_subscribed(
+    provider: &StarknetRpc,
+    tx_hash: FieldElement,
+    new_heads: &NewHeads,
+    check_interval: Duration,
+    max_interval: Duration,
+    timeout: Duration,
+    max_retries: u32,
+) -> Result<()> {
+    let start = SystemTime::now();
+    let mut heads = new_heads.receiver.clone();
+
+    loop {
+        if is_tx_accepted(provider, tx_hash, max_retries).await? {
+            return Ok(());
+        }
+
+        let remaining = timeout.saturating_sub(start.elapsed().unwrap_or_default());
+
+        match tokio::time::timeout(remaining, heads.changed()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => {
+                warn!("Block subscription dropped, polling for transaction {tx_hash:#064x}");
+
+                return wait_for_tx(
+                    provider,
+                    tx_hash,
+                    check_interval,
+                    max_interval,
+                    remaining,
+                    max_retries,
+                )
+                .await;
+            }
+            Err(_) => bail!("Timeout while waiting for transaction {tx_hash:#064x}"),
+        }
+    }
+}
+
+/// Whether a transaction is accepted, `false` while it is pending or unknown to the node
+async fn is_tx_accepted(
+    provider: &StarknetRpc,
+    tx_hash: FieldElement,
+    max_retries: u32,
+) -> Result<bool> {
+    match with_retries(max_retries, || provider.get_transaction_receipt(tx_hash)).await {
+        Ok(Receipt(receipt)) => match receipt.execution_result() {
+            ExecutionResult::Succeeded => Ok(true),
+            ExecutionResult::Reverted { reason } => Err(eyre!(format!(
+                "Transaction {tx_hash:#064x} has been rejected/reverted: {reason}"
+            ))),
+        },
+        Ok(PendingReceipt(pending)) => {
+            if let ExecutionResult::Reverted { reason } = pending.execution_result() {
+                return Err(eyre!(format!(
+                    "Transaction {tx_hash:#064x} has been rejected/reverted: {reason}"
+                )));
+            }
+            debug!("Waiting for transaction {tx_hash:#064x} to be accepted");
+            Ok(false)
+        }
+        Err(ProviderError::StarknetError(StarknetError::TransactionHashNotFound)) => {
+            debug!("Waiting for transaction {tx_hash:#064x} to show up");
+            Ok(false)
+        }
+        Err(err) => Err(eyre!(err).wrap_err(format!(
+            "Error while waiting for transaction {tx_hash:#064x}"
+        ))),
+    }
+}
+
+/// Subscription to the new blocks of a node over its WebSocket endpoint,
+/// the receivers are closed when the connection drops
+#[derive(Debug, Clone)]
+pub struct NewHeads {
+    /// Amount of new blocks notified so far
+    receiver: watch::Receiver<u64>,
+}
+
+impl NewHeads {
+    /// Subscribe with `starknet_subscribeNewHeads`, failing when the node can't
+    /// be reached at `url` or doesn't support the subscription
+    pub async fn subscribe(url: &Url) -> Result<Self> {
+        let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "starknet_subscribeNewHeads",
+            "params": {},
+        });
+        socket.send(Message::Text(request.to_string())).await?;
+
+        // The node answers with the id of the subscription before notifying of blocks
+        let answer = match socket.next().await {
+            Some(Ok(Message::Text(answer))) => answer,
+            Some(Ok(message)) => bail!("Unexpected answer to the block subscription: {message}"),
+            Some(Err(err)) => return Err(err.into()),
+            None => bail!("Connection closed before the block subscription was answered"),
+        };
+        let answer: serde_json::Value = serde_json::from_str(&answer)?;
+        if let Some(error) = answer.get("error") {
+            bail!("Node refused the block subscription: {error}");
+        }
+
+        info!("Subscribed to the new blocks of {url}");
+
+        let (sender, receiver) = watch::channel(0);
+        let url = url.clone();
+
+        tokio::spawn(async move {
+            while let Some(Ok(message)) = socket.next().await {
+                if sender.is_closed() {
+                    return;
                 }
-            },
-            Ok(PendingReceipt(pending)) => {
-                if let ExecutionResult::Reverted { reason } = pending.execution_result() {
-                    return Err(eyre!(format!(
-                        "Transaction {tx_hash:#064x} has been rejected/reverted: {reason}"
-                    )));
+
+                if let Message::Text(_) = message {
+                    sender.send_modify(|heads| *heads += 1);
                 }
-                debug!("Waiting for transaction {tx_hash:#064x} to be accepted");
-                tokio::time::sleep(check_interval).await;
             }
-            Err(ProviderError::StarknetError(StarknetErrorWithMessage {
-                code: MaybeUnknownErrorCode::Known(StarknetError::TransactionHashNotFound),
-                ..
-            })) => {
-                debug!("Waiting for transaction {tx_hash:#064x} to show up");
-                tokio::time::sleep(check_interval).await;
+
+            warn!("Block subscription to {url} dropped");
+        });
+
+        Ok(Self { receiver })
+    }
+
+    /// Another handle on the subscription, only notified of the blocks after this one
+    pub fn from_now(&self) -> Self {
+        let mut receiver = self.receiver.clone();
+        receiver.borrow_and_update();
+
+        Self { receiver }
+    }
+
+    /// Wait for the next block for at most `timeout`, `false` once the subscription dropped
+    pub async fn next_block(&mut self, timeout: Duration) -> bool {
+        !matches!(
+            tokio::time::timeout(timeout, self.receiver.changed()).await,
+            Ok(Err(_))
+        )
+    }
+}
+
+/// Version of the JSON-RPC spec the node at `url` implements, such as `0.4.0`
+pub async fn spec_version(url: &Url) -> Result<String> {
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "starknet_specVersion",
+        "params": [],
+    });
+
+    let answer: serde_json::Value = reqwest::Client::new()
+        .post(url.clone())
+        .json(&request)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    match answer.get("result").and_then(serde_json::Value::as_str) {
+        Some(version) => Ok(version.to_owned()),
+        None => bail!("Node answered the spec version with {answer}"),
+    }
+}
+
+/// Whether a node implementing the `spec_version` of the JSON-RPC spec can be subscribed to,
+/// the subscriptions came with 0.8
+pub fn supports_subscriptions(spec_version: &str) -> bool {
+    let mut parts = spec_version
+        .split('.')
+        .map(|part| part.parse::<u64>().unwrap_or_default());
+    let (major, minor) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+
+    (major, minor) >= (0, 8)
+}
+
+/// Sleep for `interval` and return the next, doubled, interval capped to `max_interval`
+pub async fn backoff(interval: Duration, max_interval: Duration) -> Duration {
+    tokio::time::sleep(interval).await;
+
+    (interval * 2).min(max_interval)
+}
+
+/// Send `request` again, up to `max_retries` times, while it fails with a transient error,
+/// backing off from [`RETRY_INTERVAL`] up to [`MAX_RETRY_INTERVAL`] between tries
+///
+/// Only connection level errors, which include the error pages of rate limiting proxies
+/// that are not JSON, are transient; errors the node answered with are returned right away
+pub async fn with_retries<T, F, Fut>(max_retries: u32, mut request: F) -> RpcResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = RpcResult<T>>,
+{
+    let mut interval = RETRY_INTERVAL;
+
+    for retry in 1..=max_retries {
+        match request().await {
+            Err(err) if is_transient(&err) => {
+                debug!("Retrying request ({retry}/{max_retries}) after transient error: {err}");
+                interval = backoff(interval, MAX_RETRY_INTERVAL).await;
             }
-            Err(err) => {
-                return Err(eyre!(err).wrap_err(format!(
-                    "Error while waiting for transaction {tx_hash:#064x}"
-                )))
+            result => return result,
+        }
+    }
+
+    request().await
+}
+
+/// Whether `err` is a rate limit or a connection level error worth retrying
+fn is_transient(err: &ProviderError) -> bool {
+    match err {
+        ProviderError::RateLimited => true,
+        ProviderError::Other(err) => matches!(
+            err.as_any()
+                .downcast_ref::<JsonRpcClientError<HttpTransportError>>(),
+            Some(JsonRpcClientError::TransportError(_))
+        ),
+        _ => false,
+    }
+}
+
+/// Amount of transactions in the pending block of the node, `None` when it can't be fetched
+pub async fn pending_tx_count(starknet_rpc: &StarknetRpc) -> Option<u64> {
+    match starknet_rpc
+        .get_block_with_tx_hashes(BlockId::Tag(BlockTag::Pending))
+        .await
+    {
+        Ok(MaybePendingBlockWithTxHashes::PendingBlock(block)) => {
+            Some(block.transactions.len() as u64)
+        }
+        // Without a pending block, there is nothing waiting to be included
+        Ok(MaybePendingBlockWithTxHashes::Block(_)) => Some(0),
+        Err(err) => {
+            warn!("Could not get the pending block: {err}");
+            None
+        }
+    }
+}
+
+/// Latest block number of the node, see [`with_retries`]
+pub async fn block_number(starknet_rpc: &StarknetRpc, max_retries: u32) -> Result<u64> {
+    Ok(with_retries(max_retries, || starknet_rpc.block_number()).await?)
+}
+
+/// Wait for all of `tx_hashes` to be accepted, with at most `max_concurrent`
+/// transactions waited for at once, failing with the first error
+///
+/// The transactions are waited for on `new_heads` when subscribed, see [`wait_for_tx_subscribed`]
+#[allow(clippy::too_many_arguments)]
+pub async fn wait_for_txs(
+    provider: &Arc<StarknetRpc>,
+    tx_hashes: impl Iterator<Item = FieldElement>,
+    new_heads: Option<&NewHeads>,
+    check_interval: Duration,
+    max_interval: Duration,
+    timeout: Duration,
+    max_retries: u32,
+    max_concurrent: usize,
+) -> Result<()> {
+    wait_for_txs_with(
+        provider,
+        tx_hashes,
+        new_heads,
+        check_interval,
+        max_interval,
+        timeout,
+        max_retries,
+        max_concurrent,
+        Err,
+    )
+    .await
+}
+
+/// Wait for all of `tx_hashes` like [`wait_for_txs`], without stopping at the transactions
+/// that fail, returning the error of each of them
+#[allow(clippy::too_many_arguments)]
+pub async fn wait_for_every_tx(
+    provider: &Arc<StarknetRpc>,
+    tx_hashes: impl Iterator<Item = FieldElement>,
+    new_heads: Option<&NewHeads>,
+    check_interval: Duration,
+    max_interval: Duration,
+    timeout: Duration,
+    max_retries: u32,
+    max_concurrent: usize,
+) -> Result<Vec<color_eyre::Report>> {
+    let mut errors = Vec::new();
+
+    wait_for_txs_with(
+        provider,
+        tx_hashes,
+        new_heads,
+        check_interval,
+        max_interval,
+        timeout,
+        max_retries,
+        max_concurrent,
+        |err| {
+            errors.push(err);
+            Ok(())
+        },
+    )
+    .await?;
+
+    Ok(errors)
+}
+
+/// Wait for all of `tx_hashes`, handing the error of a failed transaction to `on_error`
+/// which decides whether to keep waiting for the others
+#[allow(clippy::too_many_arguments)]
+async fn wait_for_txs_with(
+    provider: &Arc<StarknetRpc>,
+    tx_hashes: impl Iterator<Item = FieldElement>,
+    new_heads: Option<&NewHeads>,
+    check_interval: Duration,
+    max_interval: Duration,
+    timeout: Duration,
+    max_retries: u32,
+    max_concurrent: usize,
+    mut on_error: impl FnMut(color_eyre::Report) -> Result<()>,
+) -> Result<()> {
+    let mut set = JoinSet::new();
+
+    for tx_hash in tx_hashes {
+        while set.len() >= max_concurrent.max(1) {
+            if let Some(result) = set.join_next().await {
+                if let Err(err) = result? {
+                    on_error(err)?;
+                }
+            }
+        }
+
+        let provider = provider.clone();
+        let new_heads = new_heads.cloned();
+        set.spawn(async move {
+            match new_heads {
+                Some(new_heads) => {
+                    wait_for_tx_subscribed(
+                        &provider,
+                        tx_hash,
+                        &new_heads,
+                        check_interval,
+                        max_interval,
+                        timeout,
+                        max_retries,
+                    )
+                    .await
+                }
+                None => {
+                    wait_for_tx(
+                        &provider,
+                        tx_hash,
+                        check_interval,
+                        max_interval,
+                        timeout,
+                        max_retries,
+                    )
+                    .await
+                }
             }
+        });
+    }
+
+    while let Some(result) = set.join_next().await {
+        if let Err(err) = result? {
+            on_error(err)?;
         }
     }
+
+    Ok(())
 }
 
-/// Get a Map of the number of transactions per block from `start_block` to
-/// `end_block` (including both)
+/// Get the blocks with their transactions from `block_range`, fetched concurrently
+/// with at most `max_concurrent` requests in flight at once
 /// This is meant to be used to calculate multiple metrics such as TPS and TPB
 /// without hitting the StarkNet RPC multiple times
-// TODO: add a cache to avoid hitting the RPC for the same block
-pub async fn get_num_tx_per_block(
-    starknet_rpc: &JsonRpcClient<HttpTransport>,
-    start_block: u64,
-    end_block: u64,
-) -> Result<Vec<u64>> {
-    let mut num_tx_per_block = Vec::new();
+pub async fn get_blocks_with_txs(
+    starknet_rpc: &Arc<StarknetRpc>,
+    block_range: impl Iterator<Item = u64>,
+    max_retries: u32,
+    max_concurrent: usize,
+) -> Result<Vec<BlockWithTxs>> {
+    let (blocks, pending) = get_blocks_with_txs_and_pending(
+        starknet_rpc,
+        block_range,
+        max_retries,
+        max_concurrent,
+        false,
+    )
+    .await?;
+
+    if pending.is_some() {
+        bail!("Blocks should not be pending!");
+    }
+
+    Ok(blocks)
+}
+
+/// Get the blocks with their transactions from `block_range` like [`get_blocks_with_txs`],
+/// returning the block that is still pending instead of failing when the range reaches it
+///
+/// With `include_pending` the pending block is also fetched after the range, so that the
+/// transactions it already holds are returned even when the range stops before it
+pub async fn get_blocks_with_txs_and_pending(
+    starknet_rpc: &Arc<StarknetRpc>,
+    block_range: impl Iterator<Item = u64>,
+    max_retries: u32,
+    max_concurrent: usize,
+    include_pending: bool,
+) -> Result<(Vec<BlockWithTxs>, Option<PendingBlockWithTxs>)> {
+    let mut set = JoinSet::new();
+    let mut blocks = Vec::with_capacity(block_range.size_hint().0 + 1);
+
+    let block_ids = block_range
+        .map(BlockId::Number)
+        .chain(include_pending.then_some(BlockId::Tag(BlockTag::Pending)));
+
+    for block_id in block_ids {
+        while set.len() >= max_concurrent.max(1) {
+            if let Some(block) = set.join_next().await {
+                blocks.push(block??);
+            }
+        }
+
+        let starknet_rpc = starknet_rpc.clone();
+        set.spawn(async move {
+            with_retries(max_retries, || starknet_rpc.get_block_with_txs(block_id))
+                .await
+                .map(|block| (block_id, block))
+        });
+    }
+
+    while let Some(block) = set.join_next().await {
+        blocks.push(block??);
+    }
+
+    let mut pending = None;
+    let mut blocks: Vec<BlockWithTxs> = blocks
+        .into_iter()
+        .filter_map(|(block_id, block)| match block {
+            // Nodes without a pending block answer the pending tag with the latest one,
+            // which is either in the range already or after it
+            MaybePendingBlockWithTxs::Block(_) if block_id == BlockId::Tag(BlockTag::Pending) => {
+                None
+            }
+            MaybePendingBlockWithTxs::Block(block) => Some(block),
+            MaybePendingBlockWithTxs::PendingBlock(block) => {
+                pending = Some(block);
+                None
+            }
+        })
+        .collect();
+
+    blocks.sort_unstable_by_key(|block| block.block_number);
+
+    Ok((blocks, pending))
+}
+
+/// Get the blocks with their transactions from `block_range`, followed by the pending block
+/// when `include_pending` is set and it already holds transactions
+///
+/// The pending block has no hash nor number yet, it is given the number after the last block
+/// of the range and a zero hash and root
+pub async fn get_blocks_with_txs_including_pending(
+    starknet_rpc: &Arc<StarknetRpc>,
+    block_range: impl Iterator<Item = u64>,
+    max_retries: u32,
+    max_concurrent: usize,
+    include_pending: bool,
+) -> Result<Vec<BlockWithTxs>> {
+    let (mut blocks, pending) = get_blocks_with_txs_and_pending(
+        starknet_rpc,
+        block_range,
+        max_retries,
+        max_concurrent,
+        include_pending,
+    )
+    .await?;
 
-    for block_number in start_block..=end_block {
-        let n = starknet_rpc
-            .get_block_transaction_count(BlockId::Number(block_number))
-            .await?;
+    if let Some(pending) = pending.filter(|pending| !pending.transactions.is_empty()) {
+        let block_number = match blocks.last() {
+            Some(last) => last.block_number + 1,
+            None => block_number(starknet_rpc, max_retries).await? + 1,
+        };
 
-        num_tx_per_block.push(n);
+        blocks.push(BlockWithTxs {
+            status: BlockStatus::Pending,
+            block_hash: FieldElement::ZERO,
+            parent_hash: pending.parent_hash,
+            block_number,
+            new_root: FieldElement::ZERO,
+            timestamp: pending.timestamp,
+            sequencer_address: pending.sequencer_address,
+            l1_gas_price: pending.l1_gas_price,
+            starknet_version: pending.starknet_version,
+            transactions: pending.transactions,
+        });
     }
 
-    Ok(num_tx_per_block)
+    Ok(blocks)
+}
+
+/// Outcome of looking up the receipt of a transaction
+#[derive(Debug, Clone)]
+pub enum ReceiptLookup {
+    Included(Box<TransactionReceipt>),
+    Pending,
+    NotFound,
+}
+
+/// Get the receipts of `tx_hashes`, fetched concurrently with at most
+/// `batch_size` requests in flight at once
+/// Receipts are fetched separately from blocks so receipt heavy reporting
+/// can be throttled independently from block fetching
+pub async fn get_receipts(
+    starknet_rpc: &Arc<StarknetRpc>,
+    tx_hashes: impl Iterator<Item = FieldElement>,
+    batch_size: usize,
+    max_retries: u32,
+) -> Result<Vec<(FieldElement, ReceiptLookup)>> {
+    let mut set = JoinSet::new();
+    let mut receipts = Vec::with_capacity(tx_hashes.size_hint().0);
+
+    for tx_hash in tx_hashes {
+        while set.len() >= batch_size.max(1) {
+            if let Some(receipt) = set.join_next().await {
+                receipts.push(receipt??);
+            }
+        }
+
+        let starknet_rpc = starknet_rpc.clone();
+        set.spawn(async move {
+            let receipt = match with_retries(max_retries, || {
+                starknet_rpc.get_transaction_receipt(tx_hash)
+            })
+            .await
+            {
+                Ok(Receipt(receipt)) => ReceiptLookup::Included(Box::new(receipt)),
+                Ok(PendingReceipt(_)) => ReceiptLookup::Pending,
+                Err(ProviderError::StarknetError(StarknetError::TransactionHashNotFound)) => {
+                    ReceiptLookup::NotFound
+                }
+                Err(err) => return Err(eyre!(err)),
+            };
+
+            Ok((tx_hash, receipt))
+        });
+    }
+
+    while let Some(receipt) = set.join_next().await {
+        receipts.push(receipt??);
+    }
+
+    Ok(receipts)
 }
 
 /// Sanitize a string to be used as a filename by removing/replacing illegal chars
@@ -199,12 +987,201 @@ pub fn sanitize_filename(input: &str) -> String {
         .collect::<String>();
 
     // Truncate the string to a reasonable length for file names
-    let max_length = 255; // Maximum file name length for many file systems
-    let truncated = if sanitized.len() > max_length {
-        &sanitized[..max_length]
-    } else {
-        &sanitized
-    };
+    let max_length = 255; // Maximum file name length for many file systems, in bytes
+
+    // Cut at the last char boundary that fits so that multibyte chars are never split
+    let truncate_at = sanitized
+        .char_indices()
+        .map(|(index, c)| index + c.len_utf8())
+        .take_while(|end| *end <= max_length)
+        .last()
+        .unwrap_or_default();
+    let truncated = &sanitized[..truncate_at];
 
     truncated.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    /// Receipt of an accepted invoke, the same for every transaction hash
+    const ACCEPTED_RECEIPT: &str = r#"{"jsonrpc":"2.0","id":1,"result":{"type":"INVOKE","transaction_hash":"0x1","actual_fee":{"amount":"0x0","unit":"WEI"},"finality_status":"ACCEPTED_ON_L2","block_hash":"0x1","block_number":1,"messages_sent":[],"events":[],"execution_resources":{"steps":1},"execution_status":"SUCCEEDED"}}"#;
+
+    /// Requests the mock node is answering at the moment, and the most it answered at once
+    #[derive(Default)]
+    struct InFlight {
+        current: AtomicUsize,
+        max: AtomicUsize,
+        total: AtomicUsize,
+    }
+
+    /// Node answering every request with [`ACCEPTED_RECEIPT`] after `delay`, counting
+    /// how many requests it is answering at once
+    async fn mock_node(delay: Duration) -> (Arc<StarknetRpc>, Arc<InFlight>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let in_flight = Arc::new(InFlight::default());
+
+        let counts = in_flight.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(answer_requests(stream, counts.clone(), delay));
+            }
+        });
+
+        (
+            Arc::new(JsonRpcClient::new(FailoverTransport::new([url]))),
+            in_flight,
+        )
+    }
+
+    async fn answer_requests(mut stream: TcpStream, in_flight: Arc<InFlight>, delay: Duration) {
+        let mut buffer = Vec::new();
+
+        loop {
+            // Read a whole request, its headers and then its body
+            let request_len = loop {
+                if let Some(headers_end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let headers = String::from_utf8_lossy(&buffer[..headers_end]).to_lowercase();
+                    let body_len: usize = headers
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .map_or(0, |len| len.trim().parse().unwrap());
+
+                    if buffer.len() >= headers_end + 4 + body_len {
+                        break headers_end + 4 + body_len;
+                    }
+                }
+
+                let mut chunk = [0; 4096];
+                match stream.read(&mut chunk).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+                }
+            };
+            buffer.drain(..request_len);
+
+            let current = in_flight.current.fetch_add(1, Ordering::SeqCst) + 1;
+            in_flight.max.fetch_max(current, Ordering::SeqCst);
+            in_flight.total.fetch_add(1, Ordering::SeqCst);
+
+            tokio::time::sleep(delay).await;
+
+            in_flight.current.fetch_sub(1, Ordering::SeqCst);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{ACCEPTED_RECEIPT}",
+                ACCEPTED_RECEIPT.len()
+            );
+            if stream.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn subscriptions_need_spec_0_8() {
+        assert!(!supports_subscriptions("0.4.0"));
+        assert!(!supports_subscriptions("0.7.1"));
+        assert!(supports_subscriptions("0.8.0"));
+        assert!(supports_subscriptions("0.10.0"));
+        assert!(supports_subscriptions("1.0.0-rc1"));
+    }
+
+    #[tokio::test]
+    async fn wait_for_txs_holds_at_most_max_concurrent_tasks() {
+        let (provider, in_flight) = mock_node(Duration::from_millis(20)).await;
+
+        wait_for_txs(
+            &provider,
+            (1..=20_u64).map(FieldElement::from),
+            None,
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            0,
+            3,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(in_flight.total.load(Ordering::SeqCst), 20);
+        assert_eq!(in_flight.max.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn invoke_v3_transaction_hash_matches_starknet_rs() {
+        // Hash of the same invoke computed by the `ExecutionV3` of a later starknet-rs
+        let resource_bounds = ResourceBoundsMapping {
+            l1_gas: ResourceBounds {
+                max_amount: 100_000,
+                max_price_per_unit: 100_000_000_000_000,
+            },
+            l2_gas: ResourceBounds {
+                max_amount: 0,
+                max_price_per_unit: 0,
+            },
+        };
+        let calldata = [1u64, 0x49, 0x83, 2, 1, 2].map(FieldElement::from);
+
+        let hash = invoke_v3_transaction_hash(
+            felt!("0x1234"),
+            felt!("0x534e5f5345504f4c4941"),
+            felt!("0x5"),
+            &calldata,
+            &resource_bounds,
+            0,
+        );
+
+        assert_eq!(
+            hash,
+            felt!("0x3712d1baa15671ddf5135404978c8ad005a52356fae59afca46437e4e13944")
+        );
+    }
+
+    #[test]
+    fn sanitize_filename_keeps_short_names() {
+        assert_eq!(sanitize_filename("Erc20 Transfers"), "erc20_transfers");
+        assert_eq!(sanitize_filename("負荷/試験"), "負荷_試験");
+    }
+
+    #[test]
+    fn sanitize_filename_truncates_emoji_on_char_boundaries() {
+        // 4 bytes each, 255 isn't a multiple of it
+        let name = "🚀".repeat(100);
+        let sanitized = sanitize_filename(&name);
+
+        assert_eq!(sanitized.len(), 252);
+        assert_eq!(sanitized, "🚀".repeat(63));
+    }
+
+    #[test]
+    fn sanitize_filename_truncates_cjk_on_char_boundaries() {
+        // 3 bytes each, after a single byte one so that the limit falls inside a char
+        let name = format!("a{}", "負荷試験".repeat(50));
+        let sanitized = sanitize_filename(&name);
+
+        assert_eq!(sanitized.len(), 253);
+        assert!(name.starts_with(&sanitized));
+        assert!(sanitized.chars().skip(1).all(|c| c.len_utf8() == 3));
+    }
+
+    #[test]
+    fn sanitize_filename_stays_within_the_byte_limit() {
+        for prefix_len in 0..4 {
+            let name = format!("{}{}", "x".repeat(prefix_len), "é🚀負".repeat(40));
+            let sanitized = sanitize_filename(&name);
+
+            assert!(sanitized.len() <= 255);
+            // Another char wouldn't have fit
+            let next = name[sanitized.len()..].chars().next().unwrap();
+            assert!(sanitized.len() + next.len_utf8() > 255);
+        }
+    }
+}